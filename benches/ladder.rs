@@ -0,0 +1,243 @@
+//! Compares `OrderBook` with `LadderOrderBook` on a feed that stays near the
+//! touch, and on reading the top of a sparse book, where the ladder has to
+//! skip empty ticks.
+//!
+//! ```text
+//! cargo bench --bench ladder
+//! ```
+
+use coinbase::{
+    CompactOrderBook, OrderBook,
+    exchange::{
+        rest::products::{Product, Status},
+        websocket::channels::level_three::{Message as LevelThreeMessage, Side},
+    },
+    ladder::LadderOrderBook,
+};
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rust_decimal::Decimal;
+use std::hint::black_box;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+fn make_product() -> Product {
+    Product {
+        auction_mode: false,
+        base_currency: "BTC".into(),
+        base_increment: Decimal::new(1, 8),
+        cancel_only: false,
+        display_name: "BTC-USD".into(),
+        fx_stablecoin: false,
+        high_bid_limit_percentage: "".into(),
+        id: "BTC-USD".into(),
+        limit_only: false,
+        margin_enabled: false,
+        max_slippage_percentage: Decimal::ZERO,
+        min_market_funds: Decimal::ONE,
+        post_only: false,
+        quote_currency: "USD".into(),
+        quote_increment: Decimal::new(1, 2),
+        status: Status::Online,
+        status_message: "".into(),
+        trading_disabled: false,
+    }
+}
+
+fn make_empty_book() -> CompactOrderBook {
+    serde_json::from_value(serde_json::json!({
+        "product": make_product(),
+        "asks": [],
+        "bids": [],
+        "sequence": 0,
+        "updated_at": OffsetDateTime::UNIX_EPOCH,
+    }))
+    .unwrap()
+}
+
+/// Generates a valid feed against the orders it has opened.
+struct Feed {
+    rng: StdRng,
+    sequence: u64,
+    /// Resting orders as id, side, price and size.
+    orders: Vec<(Uuid, Side, Decimal, Decimal)>,
+}
+
+impl Feed {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            sequence: 0,
+            orders: vec![],
+        }
+    }
+
+    /// Open an order `ticks` cents behind a touch of 99.99/100.01.
+    fn open(&mut self, ticks: i64) -> LevelThreeMessage {
+        let side = match self.rng.random_bool(0.5) {
+            true => Side::Buy,
+            false => Side::Sell,
+        };
+        let price = match side {
+            Side::Buy => Decimal::new(9_999 - ticks, 2),
+            Side::Sell => Decimal::new(10_001 + ticks, 2),
+        };
+        let order_id = Uuid::new_v4();
+        let size = Decimal::new(self.rng.random_range(1..10_000), 4);
+
+        self.sequence += 1;
+        self.orders.push((order_id, side, price, size));
+
+        LevelThreeMessage::Open {
+            product_id: "BTC-USD".into(),
+            sequence: self.sequence,
+            order_id,
+            side,
+            price,
+            size,
+            time: OffsetDateTime::UNIX_EPOCH,
+        }
+    }
+
+    /// Mostly opens near the touch, changes, matches and cancels.
+    fn next(&mut self) -> LevelThreeMessage {
+        if self.orders.len() < 1_000 || self.rng.random_range(0..10) < 4 {
+            let ticks = self.rng.random_range(0..50i64);
+
+            return self.open(ticks * ticks / 25);
+        }
+
+        let product_id = "BTC-USD".into();
+        let time = OffsetDateTime::UNIX_EPOCH;
+        let index = self.rng.random_range(0..self.orders.len());
+        let (order_id, _, price, size) = self.orders[index];
+
+        self.sequence += 1;
+
+        match self.rng.random_range(0..6) {
+            0 => {
+                let size = size / Decimal::TWO;
+
+                self.orders[index].3 = size;
+
+                LevelThreeMessage::Change {
+                    product_id,
+                    sequence: self.sequence,
+                    order_id,
+                    price,
+                    size,
+                    time,
+                }
+            }
+            1..4 => {
+                let fill = match self.rng.random_bool(0.5) {
+                    true => {
+                        self.orders.swap_remove(index);
+
+                        size
+                    }
+                    false => {
+                        self.orders[index].3 = size / Decimal::TWO;
+
+                        size / Decimal::TWO
+                    }
+                };
+
+                LevelThreeMessage::Match {
+                    product_id,
+                    sequence: self.sequence,
+                    maker_order_id: order_id,
+                    taker_order_id: Uuid::new_v4(),
+                    price,
+                    size: fill,
+                    time,
+                }
+            }
+            _ => {
+                self.orders.swap_remove(index);
+
+                LevelThreeMessage::Done {
+                    product_id,
+                    sequence: self.sequence,
+                    order_id,
+                    time,
+                }
+            }
+        }
+    }
+}
+
+/// A book warmed up by the feed, and the next `count` messages.
+fn make_feed(warm_up: usize, count: usize) -> (CompactOrderBook, Vec<LevelThreeMessage>) {
+    let mut feed = Feed::new(7);
+    let mut order_book = OrderBook::try_from(make_empty_book()).unwrap();
+
+    for _ in 0..warm_up {
+        order_book.update_with(&feed.next()).unwrap();
+    }
+
+    let messages = (0..count).map(|_| feed.next()).collect();
+
+    (order_book.to_compact(), messages)
+}
+
+fn update_with(c: &mut Criterion) {
+    let (book, messages) = make_feed(20_000, 10_000);
+    let mut group = c.benchmark_group("update_with");
+
+    group.bench_function("order_book", |b| {
+        b.iter_batched(
+            || OrderBook::try_from(book.clone()).unwrap(),
+            |mut order_book| {
+                for message in messages.iter() {
+                    black_box(order_book.update_with(message).unwrap());
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("ladder", |b| {
+        b.iter_batched(
+            || LadderOrderBook::try_from(book.clone()).unwrap(),
+            |mut ladder| {
+                for message in messages.iter() {
+                    black_box(ladder.update_with(message).unwrap());
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn top_levels(c: &mut Criterion) {
+    // Levels 100 ticks apart, so most of the window is empty.
+    let mut feed = Feed::new(11);
+    let mut order_book = OrderBook::try_from(make_empty_book()).unwrap();
+
+    while feed.orders.len() < 40 {
+        let ticks = feed.orders.len() as i64 / 2 * 100;
+
+        order_book.update_with(&feed.open(ticks)).unwrap();
+    }
+
+    let ladder = LadderOrderBook::from_order_book(&order_book).unwrap();
+    let mut group = c.benchmark_group("top_levels");
+
+    group.bench_function("order_book", |b| {
+        b.iter(|| {
+            black_box(order_book.top_levels(Side::Buy, 10));
+            black_box(order_book.top_levels(Side::Sell, 10));
+        })
+    });
+    group.bench_function("ladder", |b| {
+        b.iter(|| {
+            black_box(ladder.top_levels(Side::Buy, 10));
+            black_box(ladder.top_levels(Side::Sell, 10));
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, update_with, top_levels);
+criterion_main!(benches);
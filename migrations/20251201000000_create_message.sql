@@ -0,0 +1,32 @@
+-- Labels match the Rust variant names so existing recordings stay readable.
+CREATE TYPE message_variant AS ENUM ('Open', 'Change', 'Tick', 'Noop', 'Done');
+CREATE TYPE side AS ENUM ('Buy', 'Sell');
+
+CREATE TABLE product (
+    id SMALLINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+-- A level-three message. `order_id_one` is the order, or the maker of a match,
+-- and `order_id_two` is the taker of a match.
+CREATE TABLE message (
+    product_id SMALLINT NOT NULL REFERENCES product (id),
+    sequence BIGINT NOT NULL,
+    processed_at TIMESTAMPTZ NOT NULL,
+    variant message_variant NOT NULL,
+    order_id_one UUID,
+    order_id_two UUID,
+    side side,
+    price NUMERIC,
+    size NUMERIC,
+    PRIMARY KEY (product_id, sequence)
+);
+
+CREATE TABLE snapshot (
+    product_id SMALLINT NOT NULL REFERENCES product (id),
+    sequence BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    -- The book in the binary snapshot format, which keeps decimals exact.
+    data BYTEA NOT NULL,
+    PRIMARY KEY (product_id, sequence)
+);
@@ -0,0 +1,308 @@
+use crate::{
+    Level, Message, OrderBook,
+    exchange::{common::Error, websocket::channels::level_three::Message as LevelThreeMessage},
+};
+use std::collections::VecDeque;
+use time::OffsetDateTime;
+use tracing::warn;
+
+/// The most BBO changes kept between drains. Older changes are dropped first.
+pub const MAX_BBO_CHANGES: usize = 4096;
+
+/// The BBO changes collected since the last drain.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BboChanges {
+    pub changes: Vec<BboChange>,
+    /// How many of the oldest changes were dropped to stay within
+    /// `MAX_BBO_CHANGES`. If any were, transitions were missed, so read the
+    /// top of book again.
+    pub lost: usize,
+}
+
+impl BboChanges {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.lost == 0
+    }
+}
+
+/// A change in best bid or best ask price or size caused by a single message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BboChange {
+    pub sequence: u64,
+    pub time: OffsetDateTime,
+    pub from_bid: Option<Level>,
+    pub from_ask: Option<Level>,
+    pub to_bid: Option<Level>,
+    pub to_ask: Option<Level>,
+}
+
+impl BboChange {
+    pub fn bid_changed(&self) -> bool {
+        self.from_bid != self.to_bid
+    }
+
+    pub fn ask_changed(&self) -> bool {
+        self.from_ask != self.to_ask
+    }
+}
+
+/// The best bid and ask before an update, to compare against afterwards.
+pub(crate) type Top = (Option<Level>, Option<Level>);
+
+/// BBO changes collected by `update_with` while tracking is enabled.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct BboTracker {
+    enabled: bool,
+    changes: VecDeque<BboChange>,
+    lost: usize,
+}
+
+impl BboTracker {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            changes: VecDeque::new(),
+            lost: 0,
+        }
+    }
+}
+
+impl OrderBook {
+    /// Collect a `BboChange` for every update that moves the best bid or ask,
+    /// to be taken with `drain_bbo_changes`.
+    pub fn set_bbo_tracking(&mut self, enabled: bool) {
+        self.tracking.bbo.enabled = enabled;
+
+        if !enabled {
+            self.tracking.bbo.changes.clear();
+            self.tracking.bbo.lost = 0;
+        }
+    }
+
+    /// Take the BBO changes collected since the last call, oldest first. At
+    /// most `MAX_BBO_CHANGES` are kept between calls, and the number dropped
+    /// is reported as `lost`.
+    pub fn drain_bbo_changes(&mut self) -> BboChanges {
+        let tracker = &mut self.tracking.bbo;
+
+        BboChanges {
+            changes: tracker.changes.drain(..).collect(),
+            lost: std::mem::take(&mut tracker.lost),
+        }
+    }
+
+    pub(crate) fn top_if_tracked(&self) -> Option<Top> {
+        self.tracking
+            .bbo
+            .enabled
+            .then(|| (self.best_bid(), self.best_ask()))
+    }
+
+    pub(crate) fn track_bbo(&mut self, from: Top) {
+        let Some(change) = self.bbo_change(from) else {
+            return;
+        };
+        let tracker = &mut self.tracking.bbo;
+
+        if tracker.changes.len() == MAX_BBO_CHANGES {
+            warn!("BBO changes aren't being drained, dropping the oldest");
+            tracker.changes.pop_front();
+            tracker.lost = tracker.lost.saturating_add(1);
+        }
+
+        tracker.changes.push_back(change);
+    }
+
+    fn bbo_change(&self, (from_bid, from_ask): Top) -> Option<BboChange> {
+        let (to_bid, to_ask) = (self.best_bid(), self.best_ask());
+
+        (from_bid != to_bid || from_ask != to_ask).then_some(BboChange {
+            sequence: self.sequence,
+            time: self.updated_at,
+            from_bid,
+            from_ask,
+            to_bid,
+            to_ask,
+        })
+    }
+
+    /// Update the book like `update_with`, additionally reporting whether the
+    /// message moved the best bid or ask.
+    pub fn update_with_bbo(
+        &mut self,
+        level_three_message: &LevelThreeMessage,
+    ) -> Result<(Message, Option<BboChange>), Error> {
+        let from = (self.best_bid(), self.best_ask());
+        let message = self.update_with(level_three_message)?;
+
+        Ok((message, self.bbo_change(from)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        exchange::websocket::channels::level_three::Side, test_util::make_order_book_with_orders,
+    };
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    #[test]
+    fn reports_changes_at_the_top_only() {
+        let bid = Uuid::new_v4();
+        let ask = Uuid::new_v4();
+        let mut book = make_order_book_with_orders(
+            1000,
+            vec![(Decimal::new(9900, 2), bid, Decimal::ONE)],
+            vec![(Decimal::new(10100, 2), ask, Decimal::TWO)],
+        );
+
+        // A deeper bid leaves the top untouched.
+        let (_, change) = book
+            .update_with_bbo(&LevelThreeMessage::Open {
+                product_id: "BTC-USD".into(),
+                sequence: 1001,
+                order_id: Uuid::new_v4(),
+                side: Side::Buy,
+                price: Decimal::new(9800, 2),
+                size: Decimal::ONE,
+                time: OffsetDateTime::now_utc(),
+            })
+            .unwrap();
+
+        assert!(change.is_none());
+
+        // A better bid moves the bid price.
+        let (_, change) = book
+            .update_with_bbo(&LevelThreeMessage::Open {
+                product_id: "BTC-USD".into(),
+                sequence: 1002,
+                order_id: Uuid::new_v4(),
+                side: Side::Buy,
+                price: Decimal::new(10000, 2),
+                size: Decimal::ONE,
+                time: OffsetDateTime::now_utc(),
+            })
+            .unwrap();
+        let change = change.unwrap();
+
+        assert_eq!(change.sequence, 1002);
+        assert!(change.bid_changed());
+        assert!(!change.ask_changed());
+        assert_eq!(change.from_bid.unwrap().price, Decimal::new(9900, 2));
+        assert_eq!(change.to_bid.unwrap().price, Decimal::new(10000, 2));
+
+        // A partial fill at the best ask changes only its size.
+        let (_, change) = book
+            .update_with_bbo(&LevelThreeMessage::Match {
+                product_id: "BTC-USD".into(),
+                sequence: 1003,
+                maker_order_id: ask,
+                taker_order_id: Uuid::new_v4(),
+                price: Decimal::new(10100, 2),
+                size: Decimal::ONE,
+                time: OffsetDateTime::now_utc(),
+            })
+            .unwrap();
+        let change = change.unwrap();
+
+        assert!(change.ask_changed());
+        assert_eq!(
+            change.to_ask,
+            Some(Level {
+                price: Decimal::new(10100, 2),
+                size: Decimal::ONE,
+                order_count: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn reports_an_emptied_side() {
+        let bid = Uuid::new_v4();
+        let mut book = make_order_book_with_orders(
+            1000,
+            vec![(Decimal::new(9900, 2), bid, Decimal::ONE)],
+            vec![],
+        );
+
+        let (_, change) = book
+            .update_with_bbo(&LevelThreeMessage::Done {
+                product_id: "BTC-USD".into(),
+                sequence: 1001,
+                order_id: bid,
+                time: OffsetDateTime::now_utc(),
+            })
+            .unwrap();
+        let change = change.unwrap();
+
+        assert!(change.from_bid.is_some());
+        assert!(change.to_bid.is_none());
+        assert!(!change.ask_changed());
+    }
+
+    #[test]
+    fn tracked_changes_are_collected_by_update_with() {
+        let bid = Uuid::new_v4();
+        let mut book = make_order_book_with_orders(
+            1000,
+            vec![(Decimal::new(9900, 2), bid, Decimal::ONE)],
+            vec![],
+        );
+
+        book.set_bbo_tracking(true);
+
+        for (sequence, price) in [
+            (1001, Decimal::new(9800, 2)),
+            (1002, Decimal::new(10000, 2)),
+        ] {
+            book.update_with(&LevelThreeMessage::Open {
+                product_id: "BTC-USD".into(),
+                sequence,
+                order_id: Uuid::new_v4(),
+                side: Side::Buy,
+                price,
+                size: Decimal::ONE,
+                time: OffsetDateTime::now_utc(),
+            })
+            .unwrap();
+        }
+
+        let BboChanges { changes, lost } = book.drain_bbo_changes();
+
+        assert_eq!(lost, 0);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].sequence, 1002);
+        assert_eq!(changes[0].to_bid.unwrap().price, Decimal::new(10000, 2));
+        assert!(book.drain_bbo_changes().is_empty());
+    }
+
+    #[test]
+    fn dropped_changes_are_reported_as_lost() {
+        let mut book = make_order_book_with_orders(1000, vec![], vec![]);
+
+        book.set_bbo_tracking(true);
+
+        // Every open improves the best bid.
+        for sequence in 1001..1004 + MAX_BBO_CHANGES as u64 {
+            book.update_with(&LevelThreeMessage::Open {
+                product_id: "BTC-USD".into(),
+                sequence,
+                order_id: Uuid::new_v4(),
+                side: Side::Buy,
+                price: Decimal::from(sequence),
+                size: Decimal::ONE,
+                time: OffsetDateTime::now_utc(),
+            })
+            .unwrap();
+        }
+
+        let drained = book.drain_bbo_changes();
+
+        assert_eq!(drained.lost, 3);
+        assert_eq!(drained.changes.len(), MAX_BBO_CHANGES);
+        assert_eq!(drained.changes[0].sequence, 1004);
+        assert!(book.drain_bbo_changes().is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+use crate::{
+    Message, Tick,
+    exchange::{common::Error, websocket::channels::level_three::Side},
+};
+use rust_decimal::Decimal;
+use time::{Duration, OffsetDateTime};
+
+/// The most candles closed at once. After a longer gap in trading, the rest
+/// of the gap is skipped and the next candle opens where the gap ends.
+pub const MAX_CLOSED_CANDLES: usize = 1_440;
+
+/// An OHLCV bar covering `[start, end)`. A bar without trades carries the
+/// previous close as its open, high, low and close.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candle {
+    pub start: OffsetDateTime,
+    pub end: OffsetDateTime,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: Decimal,
+    pub buy_volume: Decimal,
+    pub sell_volume: Decimal,
+    pub quote_volume: Decimal,
+    pub trade_count: usize,
+}
+
+impl Candle {
+    fn empty(start: OffsetDateTime, interval: Duration, price: Decimal) -> Self {
+        Self {
+            start,
+            end: start + interval,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: Decimal::ZERO,
+            buy_volume: Decimal::ZERO,
+            sell_volume: Decimal::ZERO,
+            quote_volume: Decimal::ZERO,
+            trade_count: 0,
+        }
+    }
+
+    /// Get the volume-weighted average price, if anything traded.
+    pub fn vwap(&self) -> Option<Decimal> {
+        self.quote_volume.checked_div(self.volume)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trade_count == 0
+    }
+
+    fn add(&mut self, tick: &Tick) -> Result<(), Error> {
+        if self.is_empty() {
+            self.open = tick.price;
+            self.high = tick.price;
+            self.low = tick.price;
+        }
+
+        let funds = tick
+            .price
+            .checked_mul(tick.size)
+            .ok_or_else(|| Error::math("trade funds overflow", None))?;
+        let side_volume = match tick.side {
+            Side::Buy => &mut self.buy_volume,
+            Side::Sell => &mut self.sell_volume,
+        };
+
+        *side_volume = side_volume
+            .checked_add(tick.size)
+            .ok_or_else(|| Error::math("side volume overflow", None))?;
+        self.volume = self
+            .volume
+            .checked_add(tick.size)
+            .ok_or_else(|| Error::math("volume overflow", None))?;
+        self.quote_volume = self
+            .quote_volume
+            .checked_add(funds)
+            .ok_or_else(|| Error::math("quote volume overflow", None))?;
+        self.high = self.high.max(tick.price);
+        self.low = self.low.min(tick.price);
+        self.close = tick.price;
+        self.trade_count += 1;
+
+        Ok(())
+    }
+}
+
+/// Aggregates trades into candles aligned to the UTC day.
+#[derive(Debug, Clone)]
+pub struct CandleBuilder {
+    interval: Duration,
+    current: Option<Candle>,
+}
+
+impl CandleBuilder {
+    /// Create a builder for an interval between one second and one day that
+    /// evenly divides a day.
+    pub fn new(interval: Duration) -> Result<Self, Error> {
+        if interval < Duration::SECOND
+            || interval > Duration::DAY
+            || interval.subsec_nanoseconds() != 0
+            || Duration::DAY.whole_seconds() % interval.whole_seconds() != 0
+        {
+            return Err(Error::invalid("candle interval"));
+        }
+
+        Ok(Self {
+            interval,
+            current: None,
+        })
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Get the candle that is still open, if any trade has been seen.
+    pub fn current(&self) -> Option<&Candle> {
+        self.current.as_ref()
+    }
+
+    /// Add a trade, returning the candles it closed, at most
+    /// `MAX_CLOSED_CANDLES`. Trades older than the open candle are counted
+    /// towards it.
+    pub fn push(&mut self, tick: &Tick) -> Result<Vec<Candle>, Error> {
+        let closed = self.close_until(tick.time);
+        let start = self.start_of(tick.time)?;
+        let interval = self.interval;
+
+        self.current
+            .get_or_insert_with(|| Candle::empty(start, interval, tick.price))
+            .add(tick)?;
+
+        Ok(closed)
+    }
+
+    /// Add the trade from a `Match`, ignoring any other message.
+    pub fn push_message(&mut self, message: &Message) -> Result<Vec<Candle>, Error> {
+        match message.tick() {
+            Some(tick) => self.push(&tick),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Close every candle that ends at or before `time`, including empty ones,
+    /// up to `MAX_CLOSED_CANDLES`. Call this on a timer to emit candles while
+    /// nothing trades.
+    pub fn close_until(&mut self, time: OffsetDateTime) -> Vec<Candle> {
+        let mut closed = vec![];
+        let interval = self.interval;
+        let gap_end = self.start_of(time);
+
+        if let Some(current) = self.current.as_mut() {
+            while current.end <= time {
+                let next = Candle::empty(current.end, interval, current.close);
+
+                closed.push(std::mem::replace(current, next));
+
+                if closed.len() == MAX_CLOSED_CANDLES {
+                    if let Ok(start) = gap_end {
+                        current.start = start;
+                        current.end = start + interval;
+                    }
+
+                    break;
+                }
+            }
+        }
+
+        closed
+    }
+
+    fn start_of(&self, time: OffsetDateTime) -> Result<OffsetDateTime, Error> {
+        let interval = self.interval.whole_nanoseconds();
+        let start = time.unix_timestamp_nanos().div_euclid(interval) * interval;
+
+        OffsetDateTime::from_unix_timestamp_nanos(start)
+            .map_err(|_| Error::invalid("candle start time"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn make_tick(price: i64, size: i64, side: Side, time: OffsetDateTime) -> Tick {
+        Tick::new(Decimal::from(price), Decimal::from(size), side, time, 0)
+    }
+
+    #[test]
+    fn rejects_unaligned_intervals() {
+        assert!(CandleBuilder::new(Duration::milliseconds(500)).is_err());
+        assert!(CandleBuilder::new(Duration::seconds(7)).is_err());
+        assert!(CandleBuilder::new(Duration::days(2)).is_err());
+        assert!(CandleBuilder::new(Duration::SECOND).is_ok());
+        assert!(CandleBuilder::new(Duration::minutes(15)).is_ok());
+        assert!(CandleBuilder::new(Duration::DAY).is_ok());
+    }
+
+    #[test]
+    fn builds_ohlcv_with_side_split() {
+        let mut builder = CandleBuilder::new(Duration::MINUTE).unwrap();
+        let start = OffsetDateTime::from_unix_timestamp(1_735_732_800).unwrap(); // 2025-01-01 12:00
+
+        for (price, size, side, seconds) in [
+            (100, 1, Side::Buy, 5),
+            (104, 2, Side::Buy, 20),
+            (98, 1, Side::Sell, 40),
+            (101, 4, Side::Sell, 59),
+        ] {
+            let tick = make_tick(price, size, side, start + Duration::seconds(seconds));
+
+            assert!(builder.push(&tick).unwrap().is_empty());
+        }
+
+        let closed = builder
+            .push(&make_tick(102, 1, Side::Buy, start + Duration::seconds(61)))
+            .unwrap();
+        let candle = closed[0];
+
+        assert_eq!(closed.len(), 1);
+        assert_eq!(candle.start, start);
+        assert_eq!(candle.end, start + Duration::MINUTE);
+        assert_eq!(
+            (candle.open, candle.high, candle.low, candle.close),
+            (
+                Decimal::from(100),
+                Decimal::from(104),
+                Decimal::from(98),
+                Decimal::from(101)
+            )
+        );
+        assert_eq!(candle.volume, Decimal::from(8));
+        assert_eq!(candle.buy_volume, Decimal::from(3));
+        assert_eq!(candle.sell_volume, Decimal::from(5));
+        assert_eq!(candle.trade_count, 4);
+        // (100 + 208 + 98 + 404) / 8
+        assert_eq!(candle.vwap(), Some(Decimal::new(10125, 2)));
+        assert_eq!(builder.current().unwrap().open, Decimal::from(102));
+    }
+
+    #[test]
+    fn fills_empty_intervals_with_previous_close() {
+        let mut builder = CandleBuilder::new(Duration::SECOND).unwrap();
+        let start = OffsetDateTime::from_unix_timestamp(1_735_689_600).unwrap(); // 2025-01-01 00:00
+
+        builder
+            .push(&make_tick(
+                100,
+                1,
+                Side::Buy,
+                start + Duration::milliseconds(500),
+            ))
+            .unwrap();
+
+        let closed = builder
+            .push(&make_tick(
+                90,
+                1,
+                Side::Sell,
+                start + Duration::milliseconds(3200),
+            ))
+            .unwrap();
+
+        assert_eq!(closed.len(), 3);
+        assert!(!closed[0].is_empty());
+        assert!(closed[1..].iter().all(|candle| candle.is_empty()
+            && candle.close == Decimal::from(100)
+            && candle.vwap().is_none()));
+        assert_eq!(closed[2].end, start + Duration::seconds(3));
+
+        // Nothing else trades, so the timer closes the open candle.
+        let closed = builder.close_until(start + Duration::seconds(4));
+
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].open, Decimal::from(90));
+    }
+
+    #[test]
+    fn skips_the_rest_of_a_long_gap() {
+        let mut builder = CandleBuilder::new(Duration::SECOND).unwrap();
+        let start = OffsetDateTime::from_unix_timestamp(1_735_689_600).unwrap(); // 2025-01-01 00:00
+        let end = start + Duration::hours(6) + Duration::milliseconds(500);
+
+        builder.push(&make_tick(100, 1, Side::Buy, start)).unwrap();
+
+        let closed = builder.push(&make_tick(90, 1, Side::Sell, end)).unwrap();
+        let current = builder.current().unwrap();
+
+        assert_eq!(closed.len(), MAX_CLOSED_CANDLES);
+        assert!(closed[1..].iter().all(|candle| candle.is_empty()));
+        assert_eq!(
+            closed.last().unwrap().end,
+            start + Duration::seconds(MAX_CLOSED_CANDLES as i64)
+        );
+        assert_eq!(current.start, start + Duration::hours(6));
+        assert_eq!((current.open, current.trade_count), (Decimal::from(90), 1));
+    }
+}
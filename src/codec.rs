@@ -0,0 +1,171 @@
+//! Varint and decimal primitives shared by the binary journal and snapshot
+//! formats.
+//!
+//! Unsigned integers are LEB128 varints, signed integers are zigzag encoded
+//! first, and decimals are a signed mantissa followed by a one byte scale.
+
+use crate::exchange::common::Error;
+use rust_decimal::Decimal;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+pub(crate) fn put_varint(buffer: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    buffer.push(value as u8);
+}
+
+pub(crate) fn put_signed(buffer: &mut Vec<u8>, value: i128) {
+    put_varint(buffer, ((value << 1) ^ (value >> 127)) as u128);
+}
+
+pub(crate) fn put_decimal(buffer: &mut Vec<u8>, value: &Decimal) {
+    put_signed(buffer, value.mantissa());
+    buffer.push(value.scale() as u8);
+}
+
+/// How a format describes its decoding errors.
+pub(crate) struct Errors {
+    pub(crate) truncated: &'static str,
+    pub(crate) varint: &'static str,
+    pub(crate) length: &'static str,
+    pub(crate) sequence: &'static str,
+    pub(crate) timestamp: &'static str,
+    pub(crate) decimal: &'static str,
+}
+
+/// A cursor over encoded bytes.
+pub(crate) struct Decoder<'a> {
+    bytes: &'a [u8],
+    errors: &'static Errors,
+}
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn new(bytes: &'a [u8], errors: &'static Errors) -> Self {
+        Self { bytes, errors }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < len {
+            return Err(Error::invalid(self.errors.truncated));
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    /// Take everything left.
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.bytes)
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn varint(&mut self) -> Result<u128, Error> {
+        let mut value = 0u128;
+
+        for shift in (0..128).step_by(7) {
+            let byte = self.byte()?;
+
+            value |= ((byte & 0x7f) as u128) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(Error::invalid(self.errors.varint))
+    }
+
+    pub(crate) fn signed(&mut self) -> Result<i128, Error> {
+        let value = self.varint()?;
+
+        Ok((value >> 1) as i128 ^ -((value & 1) as i128))
+    }
+
+    pub(crate) fn length(&mut self) -> Result<usize, Error> {
+        usize::try_from(self.varint()?).map_err(|_| Error::invalid(self.errors.length))
+    }
+
+    pub(crate) fn sequence(&mut self) -> Result<u64, Error> {
+        u64::try_from(self.varint()?).map_err(|_| Error::invalid(self.errors.sequence))
+    }
+
+    pub(crate) fn time(&mut self) -> Result<OffsetDateTime, Error> {
+        OffsetDateTime::from_unix_timestamp_nanos(self.signed()?)
+            .map_err(|_| Error::invalid(self.errors.timestamp))
+    }
+
+    pub(crate) fn uuid(&mut self) -> Result<Uuid, Error> {
+        Uuid::from_slice(self.take(16)?).map_err(|_| Error::Impossible)
+    }
+
+    pub(crate) fn decimal(&mut self) -> Result<Decimal, Error> {
+        let mantissa = self.signed()?;
+        let scale = self.byte()? as u32;
+
+        Decimal::try_from_i128_with_scale(mantissa, scale)
+            .map_err(|error| Error::math(self.errors.decimal, Some(Box::new(error))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ERRORS: Errors = Errors {
+        truncated: "truncated test record",
+        varint: "test varint overflow",
+        length: "test length",
+        sequence: "test sequence",
+        timestamp: "test timestamp",
+        decimal: "test decimal",
+    };
+
+    #[test]
+    fn primitives_round_trip_and_name_their_format() {
+        let values = [0, -1, 1, i128::MIN, i128::MAX];
+        let decimals = [Decimal::MIN, Decimal::MAX, Decimal::new(-5, 28)];
+        let mut buffer = vec![];
+
+        for value in values {
+            put_signed(&mut buffer, value);
+        }
+
+        for decimal in decimals.iter() {
+            put_decimal(&mut buffer, decimal);
+        }
+
+        let mut decoder = Decoder::new(&buffer, &ERRORS);
+
+        for value in values {
+            assert_eq!(decoder.signed().unwrap(), value);
+        }
+
+        for decimal in decimals.iter() {
+            assert_eq!(&decoder.decimal().unwrap(), decimal);
+        }
+
+        assert!(decoder.is_empty());
+        assert!(matches!(
+            decoder.byte(),
+            Err(Error::Invalid("truncated test record"))
+        ));
+        assert!(matches!(
+            Decoder::new(&[0xff; 19], &ERRORS).varint(),
+            Err(Error::Invalid("test varint overflow"))
+        ));
+    }
+}
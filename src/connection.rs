@@ -0,0 +1,412 @@
+use crate::{
+    Message, OrderBook,
+    exchange::{
+        common::{
+            Error,
+            authentication::Signer,
+            rate_limit::{BackOffBucket, TokenBucket},
+        },
+        rest::{
+            Client,
+            products::{Product, ProductBook, Products},
+        },
+        websocket::channels::{
+            Channel, ChannelBuilder, ChannelType, level_three::Message as LevelThreeMessage,
+        },
+    },
+};
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use smartstring::{LazyCompact, SmartString};
+use std::{sync::Arc, time::Duration};
+use tokio::time::sleep;
+use tokio_rustls::rustls::ClientConfig;
+use tracing::{debug, warn};
+
+/// The most live messages buffered while a snapshot is fetched for a resync.
+pub const MAX_RESYNC_BUFFER: usize = 65_536;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    Disconnected,
+    Reconnecting { attempt: usize },
+    Failed,
+}
+
+/// Exponential backoff with jitter between reconnection attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub min_delay: Duration,
+    pub max_delay: Duration,
+    pub max_attempts: Option<usize>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            min_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(60),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn new(min_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            min_delay,
+            max_delay,
+            max_attempts: None,
+        }
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = Some(max_attempts);
+
+        self
+    }
+
+    /// Get the delay before the given (one-based) attempt. The delay doubles
+    /// with every attempt up to `max_delay`, and is then drawn uniformly from
+    /// the upper half of that window.
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31) as u32;
+        let ceiling = self
+            .min_delay
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_delay);
+        let floor = ceiling / 2;
+
+        if ceiling.is_zero() {
+            return ceiling;
+        }
+
+        rand::rng().random_range(floor..=ceiling)
+    }
+
+    pub fn allows(&self, attempt: usize) -> bool {
+        self.max_attempts
+            .is_none_or(|max_attempts| attempt <= max_attempts)
+    }
+}
+
+/// Everything needed to (re)establish a synchronized order book.
+#[derive(Clone)]
+pub(crate) struct Connector {
+    pub(crate) key: String,
+    pub(crate) signer: Signer,
+    pub(crate) passphrase: String,
+    pub(crate) domain: String,
+    pub(crate) port: u16,
+    pub(crate) cache_delay: Duration,
+    pub(crate) http_client: Client,
+    pub(crate) book_backoff_bucket: BackOffBucket,
+    pub(crate) websocket_token_bucket: TokenBucket,
+    pub(crate) tls_config: Option<Arc<ClientConfig>>,
+}
+
+impl Connector {
+    /// Subscribe to the given products with a freshly signed handshake.
+    pub(crate) async fn connect<T>(
+        &self,
+        product_ids: &[SmartString<LazyCompact>],
+    ) -> Result<Channel<T>, Error>
+    where
+        T: 'static + ChannelType + DeserializeOwned + Send + Clone,
+    {
+        let builder = product_ids.iter().fold(
+            ChannelBuilder::default()
+                .with_key(self.key.as_str())
+                .with_signer(self.signer.clone())
+                .with_passphrase(self.passphrase.clone())
+                .with_endpoint(self.domain.as_str(), self.port)
+                .with_token_bucket(self.websocket_token_bucket.clone())
+                .with_tls_config(self.tls_config.clone()),
+            |builder, product_id| builder.with_product_id(product_id.as_str()),
+        );
+
+        builder.connect::<T>().await
+    }
+
+    /// Fetch a level-three snapshot, respecting the shared backoff bucket.
+    pub(crate) async fn fetch_product_book(&self, product_id: &str) -> Result<ProductBook, Error> {
+        let book_token = self.book_backoff_bucket.get_token().await?;
+        let product_book = self.http_client.get_product_book(product_id).await;
+
+        // Return the token before handling a potential error.
+        self.book_backoff_bucket.return_token(book_token).await;
+
+        product_book
+    }
+
+    /// Connect to the level-three feed, cache messages while a snapshot is
+    /// fetched, then bring the snapshot up to date with the cached messages.
+    pub(crate) async fn sync(
+        &self,
+        product: Product,
+    ) -> Result<(OrderBook, Channel<LevelThreeMessage>), Error> {
+        debug!("Establishing websocket channel");
+        let channel = self
+            .connect::<LevelThreeMessage>(std::slice::from_ref(&product.id))
+            .await?;
+
+        debug!("Caching messages in separate task");
+        let caching_channel = channel.cache().await;
+
+        sleep(self.cache_delay).await;
+
+        debug!("Fetching level-three order book snapshot");
+        let product_book = self.fetch_product_book(product.id.as_str()).await?;
+
+        debug!("Creating order book");
+        let mut order_book = OrderBook::from_product_book(product, product_book)?;
+        let mut websocket = caching_channel.join().await?;
+
+        debug!("Extracting last cached message");
+        let last_cached = websocket
+            .last_cached()
+            .ok_or_else(|| Error::InsufficientCacheDelay)?;
+
+        // Make sure the last cached message is dated after the order book snapshot.
+        if last_cached.sequence() < order_book.sequence {
+            return Err(Error::InsufficientCacheDelay);
+        }
+
+        debug!(?last_cached, "Updating order book with cached messages");
+        for message in websocket.cached_items() {
+            match order_book.update_with(&message) {
+                Ok(_) => {}
+                Err(Error::OutOfSequence) => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok((order_book, websocket))
+    }
+}
+
+/// Live messages buffered while a book is rebuilt from a snapshot.
+pub(crate) struct Resync {
+    from: u64,
+    buffer: Vec<LevelThreeMessage>,
+}
+
+impl Resync {
+    /// Start buffering from the message that diverged from a book at `from`.
+    pub(crate) fn new(from: u64, message: LevelThreeMessage) -> Self {
+        Self {
+            from,
+            buffer: vec![message],
+        }
+    }
+
+    /// Start buffering the messages that follow a book at `from`.
+    pub(crate) fn since(from: u64) -> Self {
+        Self {
+            from,
+            buffer: vec![],
+        }
+    }
+
+    /// Buffer a live message, failing once `MAX_RESYNC_BUFFER` are held.
+    pub(crate) fn push(&mut self, message: LevelThreeMessage) -> Result<(), Error> {
+        if self.buffer.len() >= MAX_RESYNC_BUFFER {
+            return Err(Error::unavailable("resync buffer capacity"));
+        }
+
+        self.buffer.push(message);
+
+        Ok(())
+    }
+
+    /// Whether a snapshot is older than every buffered message, leaving a gap.
+    pub(crate) fn predates(&self, product_book: &ProductBook) -> bool {
+        self.buffer
+            .first()
+            .is_some_and(|message| message.sequence() > product_book.sequence + 1)
+    }
+
+    /// Whether the buffer has caught up with a snapshot.
+    pub(crate) fn reaches(&self, product_book: &ProductBook) -> bool {
+        self.buffer
+            .last()
+            .is_some_and(|message| message.sequence() >= product_book.sequence)
+    }
+
+    /// Build a book from the snapshot and bring it up to date with the buffer.
+    pub(crate) fn replay(
+        &self,
+        product: Product,
+        product_book: ProductBook,
+    ) -> Result<(OrderBook, Message), Error> {
+        self.replay_onto(OrderBook::from_product_book(product, product_book)?)
+    }
+
+    /// Bring a book up to date with the buffered messages that follow it.
+    pub(crate) fn replay_onto(
+        &self,
+        mut order_book: OrderBook,
+    ) -> Result<(OrderBook, Message), Error> {
+        let book_sequence = order_book.sequence;
+
+        debug!(buffered = self.buffer.len(), "Replaying buffered messages");
+        for message in self
+            .buffer
+            .iter()
+            .filter(|message| message.sequence() > book_sequence)
+        {
+            order_book.update_with(message).inspect_err(|error| {
+                warn!(%error, sequence = message.sequence(), "Failed to replay buffered message")
+            })?;
+        }
+
+        let message = Message::Resynced {
+            from: self.from,
+            to: order_book.sequence,
+        };
+
+        Ok((order_book, message))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        exchange::websocket::channels::level_three::Side,
+        test_util::{make_order_book_with_orders, make_product},
+    };
+    use rust_decimal::Decimal;
+    use time::OffsetDateTime;
+    use uuid::Uuid;
+
+    #[test]
+    fn reconnect_delay_grows_and_stays_within_bounds() {
+        let policy = ReconnectPolicy::new(Duration::from_millis(100), Duration::from_secs(1));
+
+        for _ in 0..100 {
+            let first = policy.delay(1);
+            let third = policy.delay(3);
+            let late = policy.delay(50);
+
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+            assert!(late >= Duration::from_millis(500) && late <= Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn reconnect_attempts_are_bounded_when_configured() {
+        let unbounded = ReconnectPolicy::default();
+        let bounded = ReconnectPolicy::default().with_max_attempts(3);
+
+        assert!(unbounded.allows(1_000));
+        assert!(bounded.allows(3));
+        assert!(!bounded.allows(4));
+    }
+
+    fn make_open(sequence: u64, order_id: Uuid, price: Decimal) -> LevelThreeMessage {
+        LevelThreeMessage::Open {
+            product_id: "BTC-USD".into(),
+            sequence,
+            order_id,
+            side: Side::Buy,
+            price,
+            size: Decimal::ONE,
+            time: OffsetDateTime::now_utc(),
+        }
+    }
+
+    fn make_product_book(sequence: u64, bids: Vec<(Decimal, Decimal, Uuid)>) -> ProductBook {
+        ProductBook {
+            asks: vec![],
+            auction: None,
+            auction_mode: false,
+            bids,
+            sequence,
+            time: OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[test]
+    fn resync_replays_the_buffer_over_a_snapshot_after_a_gap() {
+        let resting = Uuid::new_v4();
+        let missed = Uuid::new_v4();
+        let opened = Uuid::new_v4();
+        let price = Decimal::new(9900, 2);
+        let mut order_book =
+            make_order_book_with_orders(1000, vec![(price, resting, Decimal::ONE)], vec![]);
+
+        // Messages 1001 and 1002 never arrive.
+        let diverged = make_open(1003, opened, price);
+        let error = order_book.update_with(&diverged).unwrap_err();
+
+        assert!(matches!(error, Error::OutOfSequence));
+        assert!(error.is_divergence());
+
+        let mut resync = Resync::new(order_book.sequence, diverged);
+
+        resync
+            .push(LevelThreeMessage::Match {
+                product_id: "BTC-USD".into(),
+                sequence: 1004,
+                maker_order_id: resting,
+                taker_order_id: Uuid::new_v4(),
+                price,
+                size: Decimal::ONE,
+                time: OffsetDateTime::now_utc(),
+            })
+            .unwrap();
+
+        // Too old to connect to the buffer, or too new to be reached by it.
+        assert!(resync.predates(&make_product_book(1001, vec![])));
+        assert!(!resync.reaches(&make_product_book(1005, vec![])));
+
+        let snapshot = make_product_book(
+            1002,
+            vec![
+                (price, Decimal::ONE, resting),
+                (price, Decimal::TWO, missed),
+            ],
+        );
+
+        assert!(!resync.predates(&snapshot));
+        assert!(resync.reaches(&snapshot));
+
+        let (order_book, message) = resync.replay(make_product(), snapshot).unwrap();
+
+        assert!(matches!(
+            message,
+            Message::Resynced {
+                from: 1000,
+                to: 1004
+            }
+        ));
+        assert_eq!(order_book.sequence(), 1004);
+        assert!(order_book.order(resting).is_none());
+        assert_eq!(order_book.queue_position(missed).unwrap().orders_ahead, 0);
+        assert_eq!(order_book.queue_position(opened).unwrap().orders_ahead, 1);
+        assert_eq!(order_book.best_bid().unwrap().size, Decimal::new(3, 0));
+    }
+
+    #[test]
+    fn resync_buffer_is_bounded() {
+        let mut resync = Resync::new(0, make_open(1, Uuid::new_v4(), Decimal::ONE));
+
+        for sequence in 2..=MAX_RESYNC_BUFFER as u64 {
+            resync
+                .push(LevelThreeMessage::Noop {
+                    product_id: "BTC-USD".into(),
+                    sequence,
+                    time: OffsetDateTime::now_utc(),
+                })
+                .unwrap();
+        }
+
+        assert!(matches!(
+            resync.push(make_open(0, Uuid::new_v4(), Decimal::ONE)),
+            Err(Error::Unavailable(_))
+        ));
+    }
+}
@@ -0,0 +1,496 @@
+use crate::{
+    Message, OrderBook,
+    exchange::{
+        common::Error,
+        websocket::channels::level_three::{Message as LevelThreeMessage, Side},
+    },
+    queue::TouchedLevels,
+};
+use rust_decimal::Decimal;
+use std::collections::{HashMap, VecDeque};
+use time::{Duration, OffsetDateTime};
+use tracing::warn;
+
+/// The new aggregate size at a price level. A size of zero removes the level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelDelta {
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
+/// Level deltas that bring a ladder up to date with the book as of `sequence`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeltaBatch {
+    pub sequence: u64,
+    pub time: OffsetDateTime,
+    pub deltas: Vec<LevelDelta>,
+}
+
+/// The delta batches collected since the last drain.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeltaBatches {
+    pub batches: Vec<DeltaBatch>,
+    /// How many of the oldest batches were dropped to stay within
+    /// `MAX_DELTA_BATCHES`. If any were, the batches no longer bring a ladder
+    /// up to date, so rebuild it from `levels` instead.
+    pub lost: usize,
+}
+
+impl DeltaBatches {
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty() && self.lost == 0
+    }
+}
+
+/// The most delta batches kept between drains. Older batches are dropped first.
+pub const MAX_DELTA_BATCHES: usize = 4096;
+
+/// The sizes of the levels a message touches, taken before it is applied.
+type LevelSizes = [Option<LevelDelta>; 2];
+
+/// Delta batches collected by `update_with` while tracking is enabled.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct DeltaTracker {
+    enabled: bool,
+    batches: VecDeque<DeltaBatch>,
+    lost: usize,
+}
+
+impl DeltaTracker {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            batches: VecDeque::new(),
+            lost: 0,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+impl OrderBook {
+    /// Collect a `DeltaBatch` for every update that changes a level's
+    /// aggregate size, to be taken with `drain_deltas`.
+    pub fn set_delta_tracking(&mut self, enabled: bool) {
+        self.tracking.deltas.enabled = enabled;
+
+        if !enabled {
+            self.tracking.deltas.batches.clear();
+            self.tracking.deltas.lost = 0;
+        }
+    }
+
+    /// Take the delta batches collected since the last call, one per message
+    /// and oldest first. At most `MAX_DELTA_BATCHES` are kept between calls,
+    /// and the number dropped is reported as `lost`. After a rebuild or any
+    /// lost batches, rebuild the ladder from `levels` rather than deltas.
+    pub fn drain_deltas(&mut self) -> DeltaBatches {
+        let tracker = &mut self.tracking.deltas;
+
+        DeltaBatches {
+            batches: tracker.batches.drain(..).collect(),
+            lost: std::mem::take(&mut tracker.lost),
+        }
+    }
+
+    /// Update the book like `update_with`, additionally returning the price
+    /// levels whose aggregate size changed.
+    pub fn update_with_deltas(
+        &mut self,
+        level_three_message: &LevelThreeMessage,
+    ) -> Result<(Message, Vec<LevelDelta>), Error> {
+        let before = self.level_sizes(&self.touched_levels(level_three_message));
+        let message = self.update_with(level_three_message)?;
+
+        Ok((message, self.level_deltas(&before)))
+    }
+
+    pub(crate) fn level_sizes(&self, touched: &TouchedLevels) -> LevelSizes {
+        let size = |level: Option<(Side, Decimal)>| {
+            level.map(|(side, price)| LevelDelta {
+                side,
+                price,
+                size: self.level_size(side, price),
+            })
+        };
+
+        // A change that keeps its price touches the same level twice.
+        match touched {
+            [first, second] if first == second => [size(*first), None],
+            [first, second] => [size(*first), size(*second)],
+        }
+    }
+
+    pub(crate) fn track_deltas(&mut self, before: &LevelSizes) {
+        let deltas = self.level_deltas(before);
+
+        if deltas.is_empty() {
+            return;
+        }
+
+        let tracker = &mut self.tracking.deltas;
+
+        if tracker.batches.len() == MAX_DELTA_BATCHES {
+            warn!("Delta batches aren't being drained, dropping the oldest");
+            tracker.batches.pop_front();
+            tracker.lost = tracker.lost.saturating_add(1);
+        }
+
+        tracker.batches.push_back(DeltaBatch {
+            sequence: self.sequence,
+            time: self.updated_at,
+            deltas,
+        });
+    }
+
+    fn level_deltas(&self, before: &LevelSizes) -> Vec<LevelDelta> {
+        before
+            .iter()
+            .flatten()
+            .filter_map(|before| {
+                let size = self.level_size(before.side, before.price);
+
+                (size != before.size).then_some(LevelDelta { size, ..*before })
+            })
+            .collect()
+    }
+
+    fn level_size(&self, side: Side, price: Decimal) -> Decimal {
+        let halfbook = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+
+        halfbook
+            .get(&price)
+            .map(|orders| orders.total_size)
+            .unwrap_or(Decimal::ZERO)
+    }
+}
+
+/// Merges level deltas over a window of messages, keeping only the latest size
+/// per level. Without a window, every message is emitted on its own.
+#[derive(Debug, Clone, Default)]
+pub struct DeltaCoalescer {
+    max_messages: Option<usize>,
+    max_interval: Option<Duration>,
+    pending: Vec<LevelDelta>,
+    positions: HashMap<(Side, Decimal), usize>,
+    messages: usize,
+    opened_at: Option<OffsetDateTime>,
+    last: Option<(u64, OffsetDateTime)>,
+}
+
+impl DeltaCoalescer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Emit a batch once this many messages have been collected.
+    pub fn with_max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = Some(max_messages.max(1));
+
+        self
+    }
+
+    /// Emit a batch once a message arrives this long (in exchange time) after
+    /// the first message of the window.
+    pub fn with_max_interval(mut self, max_interval: Duration) -> Self {
+        self.max_interval = Some(max_interval);
+
+        self
+    }
+
+    /// Add the deltas of one message, returning a batch if a window closed.
+    pub fn push(
+        &mut self,
+        sequence: u64,
+        time: OffsetDateTime,
+        deltas: Vec<LevelDelta>,
+    ) -> Option<DeltaBatch> {
+        // A message past the time window closes the window before it.
+        let expired = match (self.opened_at, self.max_interval) {
+            (Some(opened_at), Some(max_interval)) => time - opened_at >= max_interval,
+            _ => false,
+        };
+        let mut batch = if expired { self.flush() } else { None };
+
+        for delta in deltas {
+            match self.positions.get(&(delta.side, delta.price)) {
+                Some(&position) => self.pending[position] = delta,
+                None => {
+                    self.positions
+                        .insert((delta.side, delta.price), self.pending.len());
+                    self.pending.push(delta);
+                }
+            }
+        }
+
+        self.messages += 1;
+        self.opened_at.get_or_insert(time);
+        self.last = Some((sequence, time));
+
+        let full = match (self.max_messages, self.max_interval) {
+            (Some(max_messages), _) => self.messages >= max_messages,
+            (None, Some(_)) => false,
+            (None, None) => true,
+        };
+
+        if full {
+            batch = batch.or_else(|| self.flush());
+        }
+
+        batch
+    }
+
+    /// Emit whatever has been collected, if any messages arrived since the
+    /// last batch.
+    pub fn flush(&mut self) -> Option<DeltaBatch> {
+        let (sequence, time) = self.last.take()?;
+
+        self.positions.clear();
+        self.messages = 0;
+        self.opened_at = None;
+
+        Some(DeltaBatch {
+            sequence,
+            time,
+            deltas: std::mem::take(&mut self.pending),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::make_order_book_with_orders;
+    use std::collections::BTreeMap;
+    use uuid::Uuid;
+
+    fn apply(ladder: &mut BTreeMap<(bool, Decimal), Decimal>, deltas: &[LevelDelta]) {
+        for delta in deltas {
+            let key = (delta.side == Side::Buy, delta.price);
+
+            if delta.size.is_zero() {
+                ladder.remove(&key);
+            } else {
+                ladder.insert(key, delta.size);
+            }
+        }
+    }
+
+    fn ladder_of(book: &OrderBook) -> BTreeMap<(bool, Decimal), Decimal> {
+        book.levels(Side::Buy)
+            .map(|level| ((true, level.price), level.size))
+            .chain(
+                book.levels(Side::Sell)
+                    .map(|level| ((false, level.price), level.size)),
+            )
+            .collect()
+    }
+
+    fn make_messages(bid: Uuid, ask: Uuid) -> Vec<LevelThreeMessage> {
+        let time = OffsetDateTime::UNIX_EPOCH;
+        let new_bid = Uuid::new_v4();
+
+        vec![
+            LevelThreeMessage::Open {
+                product_id: "BTC-USD".into(),
+                sequence: 1001,
+                order_id: new_bid,
+                side: Side::Buy,
+                price: Decimal::new(9900, 2),
+                size: Decimal::TWO,
+                time,
+            },
+            LevelThreeMessage::Match {
+                product_id: "BTC-USD".into(),
+                sequence: 1002,
+                maker_order_id: ask,
+                taker_order_id: Uuid::new_v4(),
+                price: Decimal::new(10100, 2),
+                size: Decimal::ONE,
+                time: time + Duration::milliseconds(10),
+            },
+            LevelThreeMessage::Change {
+                product_id: "BTC-USD".into(),
+                sequence: 1003,
+                order_id: bid,
+                price: Decimal::new(9800, 2),
+                size: Decimal::ONE,
+                time: time + Duration::milliseconds(20),
+            },
+            LevelThreeMessage::Noop {
+                product_id: "BTC-USD".into(),
+                sequence: 1004,
+                time: time + Duration::milliseconds(30),
+            },
+            LevelThreeMessage::Done {
+                product_id: "BTC-USD".into(),
+                sequence: 1005,
+                order_id: new_bid,
+                time: time + Duration::milliseconds(40),
+            },
+        ]
+    }
+
+    fn make_book(bid: Uuid, ask: Uuid) -> OrderBook {
+        make_order_book_with_orders(
+            1000,
+            vec![(Decimal::new(9900, 2), bid, Decimal::ONE)],
+            vec![(Decimal::new(10100, 2), ask, Decimal::TWO)],
+        )
+    }
+
+    #[test]
+    fn deltas_rebuild_the_ladder() {
+        let (bid, ask) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut book = make_book(bid, ask);
+        let mut ladder = ladder_of(&book);
+
+        for message in make_messages(bid, ask) {
+            let (_, deltas) = book.update_with_deltas(&message).unwrap();
+
+            apply(&mut ladder, &deltas);
+            assert_eq!(ladder, ladder_of(&book));
+        }
+
+        // The repriced order moved to a new level, and the new bid is gone.
+        assert_eq!(
+            ladder.get(&(true, Decimal::new(9800, 2))),
+            Some(&Decimal::ONE)
+        );
+        assert!(!ladder.contains_key(&(true, Decimal::new(9900, 2))));
+    }
+
+    #[test]
+    fn coalesced_batches_rebuild_the_ladder() {
+        let (bid, ask) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut book = make_book(bid, ask);
+        let mut ladder = ladder_of(&book);
+        let mut coalescer = DeltaCoalescer::new().with_max_messages(3);
+        let mut batches = vec![];
+
+        for message in make_messages(bid, ask) {
+            let (_, deltas) = book.update_with_deltas(&message).unwrap();
+
+            batches.extend(coalescer.push(message.sequence(), book.updated_at(), deltas));
+        }
+
+        batches.extend(coalescer.flush());
+
+        assert_eq!(
+            batches
+                .iter()
+                .map(|batch| batch.sequence)
+                .collect::<Vec<_>>(),
+            vec![1003, 1005]
+        );
+
+        for batch in batches.iter() {
+            apply(&mut ladder, &batch.deltas);
+        }
+
+        assert_eq!(ladder, ladder_of(&book));
+
+        // The bid level at 99.00 changed twice in the first window, but is
+        // reported once with its final size.
+        let changes = batches[0]
+            .deltas
+            .iter()
+            .filter(|delta| delta.price == Decimal::new(9900, 2))
+            .collect::<Vec<_>>();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].size, Decimal::TWO);
+    }
+
+    #[test]
+    fn time_window_closes_on_later_message() {
+        let start = OffsetDateTime::UNIX_EPOCH;
+        let delta = LevelDelta {
+            side: Side::Buy,
+            price: Decimal::ONE,
+            size: Decimal::ONE,
+        };
+        let mut coalescer = DeltaCoalescer::new().with_max_interval(Duration::milliseconds(100));
+
+        assert!(coalescer.push(1, start, vec![delta]).is_none());
+        assert!(
+            coalescer
+                .push(2, start + Duration::milliseconds(50), vec![delta])
+                .is_none()
+        );
+
+        let batch = coalescer
+            .push(3, start + Duration::milliseconds(150), vec![delta])
+            .unwrap();
+
+        assert_eq!(batch.sequence, 2);
+        assert_eq!(batch.deltas.len(), 1);
+        assert_eq!(coalescer.flush().unwrap().sequence, 3);
+        assert!(coalescer.flush().is_none());
+    }
+
+    #[test]
+    fn tracked_batches_rebuild_the_ladder() {
+        let (bid, ask) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut book = make_book(bid, ask);
+        let mut ladder = ladder_of(&book);
+
+        book.set_delta_tracking(true);
+
+        for message in make_messages(bid, ask) {
+            book.update_with(&message).unwrap();
+        }
+
+        let DeltaBatches { batches, lost } = book.drain_deltas();
+
+        // The noop changes nothing, so it has no batch.
+        assert_eq!(
+            batches
+                .iter()
+                .map(|batch| batch.sequence)
+                .collect::<Vec<_>>(),
+            vec![1001, 1002, 1003, 1005]
+        );
+
+        for batch in batches.iter() {
+            apply(&mut ladder, &batch.deltas);
+        }
+
+        assert_eq!(lost, 0);
+        assert_eq!(ladder, ladder_of(&book));
+        assert!(book.drain_deltas().is_empty());
+    }
+
+    #[test]
+    fn dropped_batches_are_reported_as_lost() {
+        let mut book = make_book(Uuid::new_v4(), Uuid::new_v4());
+
+        book.set_delta_tracking(true);
+
+        for sequence in 1001..1003 + MAX_DELTA_BATCHES as u64 {
+            book.update_with(&LevelThreeMessage::Open {
+                product_id: "BTC-USD".into(),
+                sequence,
+                order_id: Uuid::new_v4(),
+                side: Side::Buy,
+                price: Decimal::new(9900, 2),
+                size: Decimal::ONE,
+                time: OffsetDateTime::UNIX_EPOCH,
+            })
+            .unwrap();
+        }
+
+        let drained = book.drain_deltas();
+
+        assert_eq!(drained.lost, 2);
+        assert_eq!(drained.batches.len(), MAX_DELTA_BATCHES);
+        assert_eq!(drained.batches[0].sequence, 1003);
+        assert!(book.drain_deltas().is_empty());
+    }
+}
@@ -1,9 +1,9 @@
 use crate::exchange::common::Error;
-use base64::{prelude::BASE64_STANDARD, Engine};
+use base64::{Engine, prelude::BASE64_STANDARD};
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Signer {
     key: Vec<u8>,
 }
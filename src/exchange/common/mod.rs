@@ -2,6 +2,10 @@ pub mod authentication;
 pub mod rate_limit;
 pub mod types;
 
+use crate::exchange::websocket::channels::level_three::Side;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
 #[derive(Debug)]
 pub enum Error {
     Api {
@@ -11,13 +15,18 @@ pub enum Error {
     Impossible,
     ChannelClosed,
     PriceDoesNotExist {
-        side: crate::exchange::websocket::channels::level_three::Side,
+        side: Side,
     },
     OrderAlreadyExists,
     OrderDoesNotExist,
     OutOfSequence,
     InsufficientCacheDelay,
     Unavailable(&'static str),
+    Invalid(&'static str),
+    Inconsistent {
+        sequence: u64,
+        discrepancies: Vec<Discrepancy>,
+    },
     Math {
         description: &'static str,
         source: Option<Box<dyn std::error::Error + Send + Sync>>,
@@ -28,6 +37,56 @@ pub enum Error {
     },
 }
 
+/// A single broken invariant found by `OrderBook::validate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// The index points at an order that isn't in any queue.
+    IndexedOrderMissing {
+        order_id: Uuid,
+        side: Side,
+        price: Decimal,
+    },
+    /// A queued order has no index entry.
+    UnindexedOrder {
+        order_id: Uuid,
+        side: Side,
+        price: Decimal,
+    },
+    /// A queued order is indexed at a different side or price, or its
+    /// handle points elsewhere.
+    Misindexed {
+        order_id: Uuid,
+        side: Side,
+        price: Decimal,
+        indexed_side: Side,
+        indexed_price: Decimal,
+    },
+    DuplicateOrder {
+        order_id: Uuid,
+    },
+    EmptyLevel {
+        side: Side,
+        price: Decimal,
+    },
+    TotalSizeMismatch {
+        side: Side,
+        price: Decimal,
+        total_size: Decimal,
+        queue_size: Decimal,
+    },
+    /// The cached best price doesn't match the price map. `actual` is `None`
+    /// for an empty side.
+    BestPriceMismatch {
+        side: Side,
+        cached: Decimal,
+        actual: Option<Decimal>,
+    },
+    Crossed {
+        best_bid: Decimal,
+        best_ask: Decimal,
+    },
+}
+
 impl Error {
     pub fn api(endpoint: &'static str, message: impl Into<String>) -> Self {
         Self::Api {
@@ -40,6 +99,10 @@ impl Error {
         Self::Unavailable(name)
     }
 
+    pub fn invalid(description: &'static str) -> Self {
+        Self::Invalid(description)
+    }
+
     pub fn math(
         description: &'static str,
         error: Option<Box<dyn std::error::Error + Send + Sync>>,
@@ -59,6 +122,20 @@ impl Error {
             source: error,
         }
     }
+
+    /// Whether this error means a local order book no longer matches the feed.
+    pub fn is_divergence(&self) -> bool {
+        matches!(
+            self,
+            Self::OutOfSequence
+                | Self::OrderAlreadyExists
+                | Self::OrderDoesNotExist
+                | Self::PriceDoesNotExist { .. }
+                | Self::Impossible
+                | Self::Math { .. }
+                | Self::Inconsistent { .. }
+        )
+    }
 }
 
 impl From<base64::DecodeError> for Error {
@@ -127,6 +204,13 @@ impl From<tokio::sync::AcquireError> for Error {
     }
 }
 
+#[cfg(feature = "postgres")]
+impl From<sqlx::Error> for Error {
+    fn from(error: sqlx::Error) -> Self {
+        Self::dependency("Postgres error", Box::new(error))
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -139,6 +223,14 @@ impl std::fmt::Display for Error {
             Self::OutOfSequence => write!(f, "Out of sequence"),
             Self::InsufficientCacheDelay => write!(f, "Insufficient cache delay"),
             Self::Unavailable(name) => write!(f, "Unavailable => {name}"),
+            Self::Invalid(description) => write!(f, "Invalid => {description}"),
+            Self::Inconsistent {
+                sequence,
+                discrepancies,
+            } => write!(
+                f,
+                "Inconsistent order book @ {sequence} => {discrepancies:?}"
+            ),
             Self::Math {
                 description,
                 source,
@@ -1,256 +1,378 @@
-// use crate::exchange::common::Error;
-// use rust_decimal::Decimal;
-// use serde::{
-//     Deserialize,
-//     de::{self, Deserializer, Visitor},
-// };
-// use std::fmt::{Display, Formatter, Result as FmtResult};
-
-// #[derive(Debug, Clone, Copy)]
-// pub struct Number {
-//     pub value: u64,
-//     pub decimals: usize,
-// }
-
-// impl TryFrom<Number> for Decimal {
-//     type Error = Error;
-
-//     fn try_from(number: Number) -> Result<Self, Error> {
-//         Ok(Decimal::new(
-//             i64::try_from(number.value)
-//                 .map_err(|error| Error::math("i64 overflow", Some(Box::new(error))))?,
-//             u32::try_from(number.decimals)
-//                 .map_err(|error| Error::math("u32 overflow", Some(Box::new(error))))?,
-//         ))
-//     }
-// }
-
-// impl Number {
-//     pub fn new(value: u64, decimals: usize) -> Self {
-//         Self { value, decimals }
-//     }
-
-//     pub fn normalize(self, decimals: usize) -> Result<u64, Error> {
-//         let scale: u64 = match decimals - self.decimals {
-//             0 => 1,
-//             1 => 10,
-//             2 => 100,
-//             3 => 1_000,
-//             4 => 10_000,
-//             5 => 100_000,
-//             6 => 1_000_000,
-//             7 => 10_000_000,
-//             8 => 100_000_000,
-//             9 => 1_000_000_000,
-//             10 => 10_000_000_000,
-//             11 => 100_000_000_000,
-//             12 => 1_000_000_000_000,
-//             _ => {
-//                 return Err(Error::math("cannot normalize past 12 digits", None));
-//             }
-//         };
-
-//         Ok(self
-//             .value
-//             .checked_mul(scale)
-//             .ok_or_else(|| Error::math("normalization overflow", None))?)
-//     }
-// }
-
-// impl<'de> Deserialize<'de> for Number {
-//     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-//     where
-//         D: Deserializer<'de>,
-//     {
-//         struct NumberVisitor;
-
-//         impl<'de> Visitor<'de> for NumberVisitor {
-//             type Value = Number;
-
-//             fn expecting(&self, formatter: &mut Formatter) -> FmtResult {
-//                 formatter.write_str("Number")
-//             }
-
-//             fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-//             where
-//                 E: de::Error,
-//             {
-//                 let mut split = v.split(".");
-//                 let lhs = split
-//                     .next()
-//                     .ok_or_else(|| de::Error::custom("numeric string is empty"))?
-//                     .parse::<u64>()
-//                     .map_err(|_| {
-//                         de::Error::custom("failed to parse integer component of number")
-//                     })?;
-//                 let (decimals, rhs) = match split.next() {
-//                     Some(str) => (
-//                         str.len(),
-//                         str.parse::<u64>().map_err(|_| {
-//                             de::Error::custom("failed to parse decimal component of number")
-//                         })?,
-//                     ),
-//                     None => (0, 0),
-//                 };
-//                 let scale: u64 = match decimals {
-//                     0 => 1,
-//                     1 => 10,
-//                     2 => 100,
-//                     3 => 1_000,
-//                     4 => 10_000,
-//                     5 => 100_000,
-//                     6 => 1_000_000,
-//                     7 => 10_000_000,
-//                     8 => 100_000_000,
-//                     9 => 1_000_000_000,
-//                     10 => 10_000_000_000,
-//                     11 => 100_000_000_000,
-//                     12 => 1_000_000_000_000,
-//                     _ => return Err(de::Error::custom("decimals must be in range 0-12")),
-//                 };
-//                 let value = lhs
-//                     .checked_mul(scale)
-//                     .ok_or_else(|| de::Error::custom("integer component overflow"))?
-//                     .checked_add(rhs)
-//                     .ok_or_else(|| de::Error::custom("number overflow"))?;
-
-//                 Ok(Number { value, decimals })
-//             }
-//         }
-
-//         deserializer.deserialize_str(NumberVisitor)
-//     }
-// }
-
-// impl Display for Number {
-//     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-//         let mut value = self.value;
-//         let mut buffer = [b'0'; 21];
-//         let mut n = 0;
-
-//         for (i, j) in (0..21).rev().enumerate() {
-//             if i == self.decimals && self.decimals != 0 {
-//                 buffer[j] = b'.';
-//             } else {
-//                 buffer[j] = (value % 10) as u8 + b'0';
-//                 value /= 10;
-//             }
-
-//             n += 1;
-
-//             if value == 0 {
-//                 if i < self.decimals {
-//                     let zero_count = self.decimals - i;
-
-//                     buffer[j - zero_count] = b'.';
-//                     n += 1 + zero_count;
-//                 }
-
-//                 break;
-//             }
-//         }
-
-//         let start = 21 - n;
-//         let repr = unsafe { std::str::from_utf8_unchecked(&buffer[start..]) };
-
-//         write!(f, "{repr}")
-//     }
-// }
-
-// #[cfg(test)]
-// mod test {
-//     use super::*;
-
-//     #[test]
-//     fn can_deserialize_single_digit_without_decimal() {
-//         assert!(matches!(
-//             serde_json::from_slice::<Number>(r#""1""#.as_bytes()).unwrap(),
-//             Number {
-//                 value: 1,
-//                 decimals: 0,
-//             }
-//         ))
-//     }
-
-//     #[test]
-//     fn can_display_single_digit_without_decimal() {
-//         let number = Number {
-//             value: 1,
-//             decimals: 0,
-//         };
-
-//         assert_eq!(format!("{number}"), String::from("1"));
-//     }
-
-//     #[test]
-//     fn can_deserialize_number_without_decimal() {
-//         assert!(matches!(
-//             serde_json::from_slice::<Number>(r#""69""#.as_bytes()).unwrap(),
-//             Number {
-//                 value: 69,
-//                 decimals: 0,
-//             }
-//         ))
-//     }
-
-//     #[test]
-//     fn can_deserialize_number_with_decimal() {
-//         assert!(matches!(
-//             serde_json::from_slice::<Number>(r#""69.42""#.as_bytes()).unwrap(),
-//             Number {
-//                 value: 6942,
-//                 decimals: 2,
-//             }
-//         ))
-//     }
-
-//     #[test]
-//     fn can_display_number_without_decimal() {
-//         let number = Number {
-//             value: 69,
-//             decimals: 0,
-//         };
-
-//         assert_eq!(format!("{number}"), String::from("69"));
-//     }
-
-//     #[test]
-//     fn can_display_number_with_decimal() {
-//         let number = Number {
-//             value: 6942,
-//             decimals: 2,
-//         };
-
-//         assert_eq!(format!("{number}"), String::from("69.42"));
-//     }
-
-//     #[test]
-//     fn can_display_number_with_leading_zero_decimals() {
-//         let number = Number {
-//             value: 6942,
-//             decimals: 6,
-//         };
-
-//         assert_eq!(format!("{number}"), String::from("0.006942"));
-//     }
-
-//     #[test]
-//     fn can_normalize_number_without_decimal() {
-//         let number = Number {
-//             value: 69,
-//             decimals: 0,
-//         };
-
-//         assert_eq!(number.normalize(3).unwrap(), 69000);
-//     }
-
-//     #[test]
-//     fn can_normalize_number_with_decimal() {
-//         let number = Number {
-//             value: 6942,
-//             decimals: 2,
-//         };
-
-//         assert_eq!(number.normalize(3).unwrap(), 69420);
-//     }
-// }
+use crate::exchange::common::Error;
+use rust_decimal::Decimal;
+use serde::{
+    Deserialize,
+    de::{self, Deserializer, Visitor},
+};
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    str::FromStr,
+};
+
+const POWERS: [u64; 20] = [
+    1,
+    10,
+    100,
+    1_000,
+    10_000,
+    100_000,
+    1_000_000,
+    10_000_000,
+    100_000_000,
+    1_000_000_000,
+    10_000_000_000,
+    100_000_000_000,
+    1_000_000_000_000,
+    10_000_000_000_000,
+    100_000_000_000_000,
+    1_000_000_000_000_000,
+    10_000_000_000_000_000,
+    100_000_000_000_000_000,
+    1_000_000_000_000_000_000,
+    10_000_000_000_000_000_000,
+];
+
+fn power(decimals: usize) -> Result<u64, Error> {
+    POWERS
+        .get(decimals)
+        .copied()
+        .ok_or_else(|| Error::math("too many decimals", None))
+}
+
+/// An unsigned fixed-point number: `value` scaled by `10^decimals`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Number {
+    pub value: u64,
+    pub decimals: usize,
+}
+
+impl TryFrom<Number> for Decimal {
+    type Error = Error;
+
+    fn try_from(number: Number) -> Result<Self, Error> {
+        let decimals = u32::try_from(number.decimals)
+            .map_err(|error| Error::math("u32 overflow", Some(Box::new(error))))?;
+
+        Decimal::try_from_i128_with_scale(number.value.into(), decimals)
+            .map_err(|error| Error::math("decimal overflow", Some(Box::new(error))))
+    }
+}
+
+impl TryFrom<Decimal> for Number {
+    type Error = Error;
+
+    fn try_from(decimal: Decimal) -> Result<Self, Error> {
+        if decimal.is_sign_negative() && !decimal.is_zero() {
+            return Err(Error::math("negative number", None));
+        }
+
+        Ok(Self {
+            value: u64::try_from(decimal.mantissa().unsigned_abs())
+                .map_err(|error| Error::math("u64 overflow", Some(Box::new(error))))?,
+            decimals: decimal.scale() as usize,
+        })
+    }
+}
+
+impl FromStr for Number {
+    type Err = Error;
+
+    /// Parse an unsigned decimal string such as `"69.42"` without going
+    /// through `Decimal`.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let (lhs, rhs) = s.split_once('.').unwrap_or((s, ""));
+
+        if lhs.is_empty() && rhs.is_empty() {
+            return Err(Error::invalid("numeric string is empty"));
+        }
+
+        let mut value = 0u64;
+
+        for byte in lhs.bytes().chain(rhs.bytes()) {
+            if !byte.is_ascii_digit() {
+                return Err(Error::invalid("numeric string has a non-digit"));
+            }
+
+            value = value
+                .checked_mul(10)
+                .and_then(|value| value.checked_add((byte - b'0') as u64))
+                .ok_or_else(|| Error::math("number overflow", None))?;
+        }
+
+        Ok(Self {
+            value,
+            decimals: rhs.len(),
+        })
+    }
+}
+
+impl Number {
+    pub fn new(value: u64, decimals: usize) -> Self {
+        Self { value, decimals }
+    }
+
+    /// The value scaled to `decimals` places. Fails if that overflows or
+    /// would drop non-zero digits.
+    pub fn normalize(self, decimals: usize) -> Result<u64, Error> {
+        if decimals >= self.decimals {
+            return self
+                .value
+                .checked_mul(power(decimals - self.decimals)?)
+                .ok_or_else(|| Error::math("normalization overflow", None));
+        }
+
+        match power(self.decimals - decimals) {
+            Ok(scale) if self.value.is_multiple_of(scale) => Ok(self.value / scale),
+            Ok(_) => Err(Error::math("normalization loses precision", None)),
+            // Dividing by more than u64 can hold leaves only zero.
+            Err(_) if self.value == 0 => Ok(0),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Number {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct NumberVisitor;
+
+        impl<'de> Visitor<'de> for NumberVisitor {
+            type Value = Number;
+
+            fn expecting(&self, formatter: &mut Formatter) -> FmtResult {
+                formatter.write_str("Number")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                v.parse().map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_str(NumberVisitor)
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let digits = self.value.to_string();
+
+        if self.decimals == 0 {
+            return write!(f, "{digits}");
+        }
+
+        match digits.len().checked_sub(self.decimals) {
+            Some(0) | None => write!(f, "0.{digits:0>width$}", width = self.decimals),
+            Some(split) => write!(f, "{}.{}", &digits[..split], &digits[split..]),
+        }
+    }
+}
+
+/// Converts between decimals and integer counts of a product increment, such
+/// as its `quote_increment` for prices or `base_increment` for sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scale {
+    step: u64,
+    decimals: usize,
+}
+
+impl Scale {
+    pub fn new(increment: Decimal) -> Result<Self, Error> {
+        if increment <= Decimal::ZERO {
+            return Err(Error::invalid("increment must be positive"));
+        }
+
+        let Number { value, decimals } = Number::try_from(increment.normalize())?;
+
+        power(decimals)?;
+
+        Ok(Self {
+            step: value,
+            decimals,
+        })
+    }
+
+    pub fn increment(&self) -> Decimal {
+        Decimal::from_i128_with_scale(self.step.into(), self.decimals as u32)
+    }
+
+    /// The number of increments in `number`, which must be an exact multiple.
+    pub fn to_units(&self, number: Number) -> Result<u64, Error> {
+        let value = number.normalize(self.decimals)?;
+
+        match value.is_multiple_of(self.step) {
+            true => Ok(value / self.step),
+            false => Err(Error::math("not a multiple of the increment", None)),
+        }
+    }
+
+    pub fn units(&self, decimal: Decimal) -> Result<u64, Error> {
+        self.to_units(Number::try_from(decimal)?)
+    }
+
+    /// Parse a feed string straight into increments.
+    pub fn parse(&self, s: &str) -> Result<u64, Error> {
+        self.to_units(s.parse()?)
+    }
+
+    pub fn to_number(&self, units: u64) -> Result<Number, Error> {
+        Ok(Number {
+            value: units
+                .checked_mul(self.step)
+                .ok_or_else(|| Error::math("units overflow", None))?,
+            decimals: self.decimals,
+        })
+    }
+
+    pub fn to_decimal(&self, units: u64) -> Result<Decimal, Error> {
+        Decimal::try_from(self.to_number(units)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn can_deserialize_single_digit_without_decimal() {
+        assert!(matches!(
+            serde_json::from_slice::<Number>(r#""1""#.as_bytes()).unwrap(),
+            Number {
+                value: 1,
+                decimals: 0,
+            }
+        ))
+    }
+
+    #[test]
+    fn can_display_single_digit_without_decimal() {
+        let number = Number {
+            value: 1,
+            decimals: 0,
+        };
+
+        assert_eq!(format!("{number}"), String::from("1"));
+    }
+
+    #[test]
+    fn can_deserialize_number_without_decimal() {
+        assert!(matches!(
+            serde_json::from_slice::<Number>(r#""69""#.as_bytes()).unwrap(),
+            Number {
+                value: 69,
+                decimals: 0,
+            }
+        ))
+    }
+
+    #[test]
+    fn can_deserialize_number_with_decimal() {
+        assert!(matches!(
+            serde_json::from_slice::<Number>(r#""69.42""#.as_bytes()).unwrap(),
+            Number {
+                value: 6942,
+                decimals: 2,
+            }
+        ))
+    }
+
+    #[test]
+    fn cannot_deserialize_malformed_numbers() {
+        for json in [
+            r#""""#,
+            r#"".""#,
+            r#""-1""#,
+            r#""1.2.3""#,
+            r#""18446744073709551616""#,
+        ] {
+            assert!(serde_json::from_slice::<Number>(json.as_bytes()).is_err());
+        }
+    }
+
+    #[test]
+    fn can_display_number_without_decimal() {
+        let number = Number {
+            value: 69,
+            decimals: 0,
+        };
+
+        assert_eq!(format!("{number}"), String::from("69"));
+    }
+
+    #[test]
+    fn can_display_number_with_decimal() {
+        let number = Number {
+            value: 6942,
+            decimals: 2,
+        };
+
+        assert_eq!(format!("{number}"), String::from("69.42"));
+    }
+
+    #[test]
+    fn can_display_number_with_leading_zero_decimals() {
+        let number = Number {
+            value: 6942,
+            decimals: 6,
+        };
+
+        assert_eq!(format!("{number}"), String::from("0.006942"));
+    }
+
+    #[test]
+    fn can_normalize_number_without_decimal() {
+        let number = Number {
+            value: 69,
+            decimals: 0,
+        };
+
+        assert_eq!(number.normalize(3).unwrap(), 69000);
+    }
+
+    #[test]
+    fn can_normalize_number_with_decimal() {
+        let number = Number {
+            value: 6942,
+            decimals: 2,
+        };
+
+        assert_eq!(number.normalize(3).unwrap(), 69420);
+    }
+
+    #[test]
+    fn normalize_drops_only_trailing_zeros() {
+        assert_eq!(Number::new(69420, 3).normalize(2).unwrap(), 6942);
+        assert!(Number::new(69421, 3).normalize(2).is_err());
+        assert!(Number::new(u64::MAX, 0).normalize(1).is_err());
+    }
+
+    #[test]
+    fn converts_to_and_from_decimal() {
+        let decimal = Decimal::new(6942, 2);
+        let number = Number::try_from(decimal).unwrap();
+
+        assert_eq!(number, Number::new(6942, 2));
+        assert_eq!(Decimal::try_from(number).unwrap(), decimal);
+        assert!(Number::try_from(Decimal::new(-1, 0)).is_err());
+        assert!(Number::try_from(Decimal::MAX).is_err());
+        assert!(Decimal::try_from(Number::new(1, 29)).is_err());
+    }
+
+    #[test]
+    fn scale_counts_increments() {
+        let scale = Scale::new(Decimal::new(5, 3)).unwrap(); // 0.005
+
+        assert_eq!(scale.parse("69.425").unwrap(), 13885);
+        assert_eq!(scale.parse("69.4250").unwrap(), 13885);
+        assert_eq!(scale.units(Decimal::new(69425, 3)).unwrap(), 13885);
+        assert_eq!(scale.to_decimal(13885).unwrap(), Decimal::new(69425, 3));
+        assert_eq!(scale.increment(), Decimal::new(5, 3));
+        assert!(scale.parse("69.426").is_err());
+        assert!(scale.to_decimal(u64::MAX).is_err());
+        assert!(Scale::new(Decimal::ZERO).is_err());
+    }
+}
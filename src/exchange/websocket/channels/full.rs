@@ -0,0 +1,463 @@
+use crate::exchange::{
+    common::Error,
+    websocket::channels::{
+        ChannelType,
+        level_three::{Message as LevelThreeMessage, Side},
+        parse_tagged,
+    },
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, de};
+use smartstring::{LazyCompact, SmartString};
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    str::FromStr,
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderType {
+    Limit,
+    Market,
+}
+
+/// Why an order left the book. Reasons this crate doesn't know are kept as
+/// `Other` rather than failing the feed.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DoneReason {
+    Filled,
+    Canceled,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StopType {
+    Entry,
+    Loss,
+}
+
+/// Activate messages carry their time as fractional Unix seconds.
+fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<OffsetDateTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let timestamp = SmartString::<LazyCompact>::deserialize(deserializer)?;
+    let nanos = Decimal::from_str(&timestamp)
+        .ok()
+        .and_then(|seconds| seconds.checked_mul(Decimal::from(1_000_000_000)))
+        .and_then(|nanos| i128::try_from(nanos.trunc()).ok())
+        .ok_or_else(|| de::Error::custom("invalid timestamp"))?;
+
+    OffsetDateTime::from_unix_timestamp_nanos(nanos).map_err(de::Error::custom)
+}
+
+/// A message from the `full` channel, which reports every order's lifecycle
+/// including orders that never rest on the book.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Message {
+    /// An order was accepted by the matching engine. Market orders may give
+    /// `funds` instead of, or as well as, `size`.
+    Received {
+        product_id: SmartString<LazyCompact>,
+        sequence: u64,
+        order_id: Uuid,
+        client_oid: Option<SmartString<LazyCompact>>,
+        side: Side,
+        order_type: OrderType,
+        size: Option<Decimal>,
+        price: Option<Decimal>,
+        funds: Option<Decimal>,
+        #[serde(with = "time::serde::iso8601")]
+        time: OffsetDateTime,
+    },
+    Open {
+        product_id: SmartString<LazyCompact>,
+        sequence: u64,
+        order_id: Uuid,
+        side: Side,
+        price: Decimal,
+        remaining_size: Decimal,
+        #[serde(with = "time::serde::iso8601")]
+        time: OffsetDateTime,
+    },
+    /// An order left the matching engine. `price` and `remaining_size` are
+    /// absent for market orders.
+    Done {
+        product_id: SmartString<LazyCompact>,
+        sequence: u64,
+        order_id: Uuid,
+        side: Side,
+        reason: DoneReason,
+        price: Option<Decimal>,
+        remaining_size: Option<Decimal>,
+        #[serde(with = "time::serde::iso8601")]
+        time: OffsetDateTime,
+    },
+    /// A trade, where `side` is the side of the maker order.
+    Match {
+        product_id: SmartString<LazyCompact>,
+        sequence: u64,
+        trade_id: u64,
+        maker_order_id: Uuid,
+        taker_order_id: Uuid,
+        side: Side,
+        price: Decimal,
+        size: Decimal,
+        #[serde(with = "time::serde::iso8601")]
+        time: OffsetDateTime,
+    },
+    /// An order was changed in place: a resting order's size or price, or a
+    /// market order's funds.
+    Change {
+        product_id: SmartString<LazyCompact>,
+        sequence: u64,
+        order_id: Uuid,
+        side: Side,
+        reason: Option<SmartString<LazyCompact>>,
+        price: Option<Decimal>,
+        old_price: Option<Decimal>,
+        new_price: Option<Decimal>,
+        old_size: Option<Decimal>,
+        new_size: Option<Decimal>,
+        old_funds: Option<Decimal>,
+        new_funds: Option<Decimal>,
+        #[serde(with = "time::serde::iso8601")]
+        time: OffsetDateTime,
+    },
+    /// A stop order was triggered. These are unsequenced.
+    Activate {
+        product_id: SmartString<LazyCompact>,
+        order_id: Uuid,
+        side: Side,
+        stop_type: StopType,
+        stop_price: Decimal,
+        size: Option<Decimal>,
+        funds: Option<Decimal>,
+        #[serde(deserialize_with = "deserialize_timestamp")]
+        timestamp: OffsetDateTime,
+    },
+}
+
+impl Message {
+    pub fn product_id(&self) -> &str {
+        match self {
+            Self::Received { product_id, .. } => product_id,
+            Self::Open { product_id, .. } => product_id,
+            Self::Done { product_id, .. } => product_id,
+            Self::Match { product_id, .. } => product_id,
+            Self::Change { product_id, .. } => product_id,
+            Self::Activate { product_id, .. } => product_id,
+        }
+    }
+
+    pub fn sequence(&self) -> Option<u64> {
+        match self {
+            Self::Received { sequence, .. } => Some(*sequence),
+            Self::Open { sequence, .. } => Some(*sequence),
+            Self::Done { sequence, .. } => Some(*sequence),
+            Self::Match { sequence, .. } => Some(*sequence),
+            Self::Change { sequence, .. } => Some(*sequence),
+            Self::Activate { .. } => None,
+        }
+    }
+
+    pub fn time(&self) -> OffsetDateTime {
+        match self {
+            Self::Received { time, .. } => *time,
+            Self::Open { time, .. } => *time,
+            Self::Done { time, .. } => *time,
+            Self::Match { time, .. } => *time,
+            Self::Change { time, .. } => *time,
+            Self::Activate { timestamp, .. } => *timestamp,
+        }
+    }
+
+    /// The level3 message with the same effect on the book, if the message
+    /// is sequenced. Received messages and funds changes don't touch the book
+    /// and become noops.
+    pub fn to_level_three(&self) -> Option<LevelThreeMessage> {
+        let product_id = self.product_id().into();
+        let sequence = self.sequence()?;
+        let time = self.time();
+        let message = match self {
+            Self::Open {
+                order_id,
+                side,
+                price,
+                remaining_size,
+                ..
+            } => LevelThreeMessage::Open {
+                product_id,
+                sequence,
+                order_id: *order_id,
+                side: *side,
+                price: *price,
+                size: *remaining_size,
+                time,
+            },
+            Self::Done { order_id, .. } => LevelThreeMessage::Done {
+                product_id,
+                sequence,
+                order_id: *order_id,
+                time,
+            },
+            Self::Match {
+                maker_order_id,
+                taker_order_id,
+                price,
+                size,
+                ..
+            } => LevelThreeMessage::Match {
+                product_id,
+                sequence,
+                maker_order_id: *maker_order_id,
+                taker_order_id: *taker_order_id,
+                price: *price,
+                size: *size,
+                time,
+            },
+            Self::Change {
+                order_id,
+                price,
+                new_price,
+                new_size: Some(size),
+                ..
+            } if new_price.or(*price).is_some() => LevelThreeMessage::Change {
+                product_id,
+                sequence,
+                order_id: *order_id,
+                price: new_price.or(*price)?,
+                size: *size,
+                time,
+            },
+            Self::Received { .. } | Self::Change { .. } | Self::Activate { .. } => {
+                LevelThreeMessage::Noop {
+                    product_id,
+                    sequence,
+                    time,
+                }
+            }
+        };
+
+        Some(message)
+    }
+}
+
+impl ChannelType for Message {
+    type Parser = ();
+
+    fn channel_type() -> &'static str {
+        "full"
+    }
+
+    fn parse_schema() -> bool {
+        false
+    }
+
+    fn parse(_: &mut (), payload: &[u8]) -> Result<Option<Self>, Error> {
+        parse_tagged(payload)
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Received {
+                product_id,
+                sequence,
+                order_id,
+                side,
+                order_type,
+                size,
+                price,
+                funds,
+                time,
+                ..
+            } => write!(
+                f,
+                "[RECEIVED] product_id: {product_id}, sequence: {sequence}, order_id: {order_id}, side: {side}, order_type: {order_type:?}, size: {size:?}, price: {price:?}, funds: {funds:?}, time: {time}"
+            ),
+            Self::Open {
+                product_id,
+                sequence,
+                order_id,
+                side,
+                price,
+                remaining_size,
+                time,
+            } => write!(
+                f,
+                "[OPEN] product_id: {product_id}, sequence: {sequence}, order_id: {order_id}, side: {side}, price: {price}, remaining_size: {remaining_size}, time: {time}"
+            ),
+            Self::Done {
+                product_id,
+                sequence,
+                order_id,
+                side,
+                reason,
+                price,
+                remaining_size,
+                time,
+            } => write!(
+                f,
+                "[DONE] product_id: {product_id}, sequence: {sequence}, order_id: {order_id}, side: {side}, reason: {reason:?}, price: {price:?}, remaining_size: {remaining_size:?}, time: {time}"
+            ),
+            Self::Match {
+                product_id,
+                sequence,
+                trade_id,
+                maker_order_id,
+                taker_order_id,
+                side,
+                price,
+                size,
+                time,
+            } => write!(
+                f,
+                "[MATCH] product_id: {product_id}, sequence: {sequence}, trade_id: {trade_id}, maker_order_id: {maker_order_id}, taker_order_id: {taker_order_id}, side: {side}, price: {price}, size: {size}, time: {time}"
+            ),
+            Self::Change {
+                product_id,
+                sequence,
+                order_id,
+                side,
+                price,
+                new_price,
+                new_size,
+                new_funds,
+                time,
+                ..
+            } => write!(
+                f,
+                "[CHANGE] product_id: {product_id}, sequence: {sequence}, order_id: {order_id}, side: {side}, price: {price:?}, new_price: {new_price:?}, new_size: {new_size:?}, new_funds: {new_funds:?}, time: {time}"
+            ),
+            Self::Activate {
+                product_id,
+                order_id,
+                side,
+                stop_type,
+                stop_price,
+                timestamp,
+                ..
+            } => write!(
+                f,
+                "[ACTIVATE] product_id: {product_id}, order_id: {order_id}, side: {side}, stop_type: {stop_type:?}, stop_price: {stop_price}, timestamp: {timestamp}"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(payload: &str) -> Option<Message> {
+        Message::parse(&mut (), payload.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn can_deserialize_received_messages() {
+        let limit = parse(
+            r#"{"type":"received","time":"2014-11-07T08:19:27.028459Z","product_id":"BTC-USD","sequence":10,"order_id":"d50ec984-77a8-460a-b958-66f114b0de9b","size":"1.34","price":"502.1","side":"buy","order_type":"limit","client_oid":"d50ec974-76a2-454b-66f135b1ea8c"}"#,
+        );
+        let market = parse(
+            r#"{"type":"received","time":"2014-11-09T08:19:27.028459Z","product_id":"BTC-USD","sequence":12,"order_id":"dddec984-77a8-460a-b958-66f114b0de9b","funds":"3000.234","side":"buy","order_type":"market"}"#,
+        );
+
+        assert!(matches!(
+            limit,
+            Some(Message::Received {
+                order_type: OrderType::Limit,
+                price: Some(_),
+                funds: None,
+                client_oid: Some(_),
+                ..
+            })
+        ));
+        assert!(matches!(
+            market,
+            Some(Message::Received {
+                order_type: OrderType::Market,
+                size: None,
+                funds: Some(_),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn can_deserialize_done_messages_with_reasons() {
+        let filled = parse(
+            r#"{"type":"done","time":"2014-11-07T08:19:27.028459Z","product_id":"BTC-USD","sequence":10,"price":"200.2","order_id":"d50ec984-77a8-460a-b958-66f114b0de9b","reason":"filled","side":"sell","remaining_size":"0"}"#,
+        )
+        .unwrap();
+        let canceled = parse(
+            r#"{"type":"done","time":"2014-11-07T08:19:27.028459Z","product_id":"BTC-USD","sequence":11,"order_id":"d50ec984-77a8-460a-b958-66f114b0de9b","reason":"canceled","side":"buy"}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            filled,
+            Message::Done {
+                reason: DoneReason::Filled,
+                remaining_size: Some(size),
+                ..
+            } if size.is_zero()
+        ));
+        assert!(matches!(
+            canceled,
+            Message::Done {
+                reason: DoneReason::Canceled,
+                price: None,
+                remaining_size: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn can_deserialize_changes_and_activations() {
+        let size = parse(
+            r#"{"type":"change","reason":"STP","time":"2014-11-07T08:19:27.028459Z","sequence":80,"order_id":"ac928c66-ca53-498f-9c13-a110027a60e8","side":"sell","product_id":"BTC-USD","new_size":"5.23512","old_size":"12.234412","price":"400.23"}"#,
+        )
+        .unwrap();
+        let funds = parse(
+            r#"{"type":"change","time":"2014-11-07T08:19:27.028459Z","sequence":80,"order_id":"ac928c66-ca53-498f-9c13-a110027a60e8","side":"buy","product_id":"BTC-USD","new_funds":"5.23512","old_funds":"12.234412","price":null}"#,
+        )
+        .unwrap();
+        let activate = parse(
+            r#"{"type":"activate","product_id":"BTC-USD","timestamp":"1483736448.299000","user_id":"12","profile_id":"30000727-d308-cf50-7b1c-c06deb1934fc","order_id":"7b52009b-64fd-0a2a-49e6-d8a939753077","stop_type":"entry","side":"buy","stop_price":"80","size":"2","funds":"50","private":true}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            size.to_level_three(),
+            Some(LevelThreeMessage::Change { sequence: 80, .. })
+        ));
+        assert!(matches!(
+            funds.to_level_three(),
+            Some(LevelThreeMessage::Noop { sequence: 80, .. })
+        ));
+        assert_eq!(activate.sequence(), None);
+        assert_eq!(activate.to_level_three(), None);
+        assert_eq!(
+            activate.time(),
+            OffsetDateTime::from_unix_timestamp_nanos(1_483_736_448_299_000_000).unwrap()
+        );
+    }
+
+    #[test]
+    fn skips_heartbeats_and_rejects_unknown_messages() {
+        let heartbeat = r#"{"type":"heartbeat","sequence":90,"last_trade_id":20,"product_id":"BTC-USD","time":"2014-11-07T08:19:28.464459Z"}"#;
+
+        assert_eq!(parse(heartbeat), None);
+        assert!(Message::parse(&mut (), br#"{"type":"unknown"}"#).is_err());
+        assert!(Message::parse(&mut (), b"[]").is_err());
+    }
+}
@@ -1,4 +1,7 @@
-use crate::exchange::websocket::channels::ChannelType;
+use crate::exchange::{
+    common::Error,
+    websocket::channels::{ChannelType, Heartbeat},
+};
 use rust_decimal::Decimal;
 use serde::{
     Deserialize, Serialize,
@@ -6,13 +9,18 @@ use serde::{
 };
 use smartstring::{LazyCompact, SmartString};
 use std::{
+    collections::HashMap,
     fmt::{Display, Formatter, Result as FmtResult},
     marker::PhantomData,
+    str::FromStr,
+};
+use time::{
+    Date, Month, OffsetDateTime, PrimitiveDateTime, Time, format_description::well_known::Iso8601,
 };
-use time::OffsetDateTime;
+use tracing::{debug, error, warn};
 use uuid::Uuid;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Message {
     Open {
         product_id: SmartString<LazyCompact>,
@@ -54,6 +62,16 @@ pub enum Message {
 }
 
 impl Message {
+    pub fn product_id(&self) -> &str {
+        match self {
+            Self::Open { product_id, .. } => product_id,
+            Self::Change { product_id, .. } => product_id,
+            Self::Match { product_id, .. } => product_id,
+            Self::Noop { product_id, .. } => product_id,
+            Self::Done { product_id, .. } => product_id,
+        }
+    }
+
     pub fn sequence(&self) -> u64 {
         match self {
             Self::Open { sequence, .. } => *sequence,
@@ -63,9 +81,97 @@ impl Message {
             Self::Done { sequence, .. } => *sequence,
         }
     }
+
+    pub fn time(&self) -> OffsetDateTime {
+        match self {
+            Self::Open { time, .. } => *time,
+            Self::Change { time, .. } => *time,
+            Self::Match { time, .. } => *time,
+            Self::Noop { time, .. } => *time,
+            Self::Done { time, .. } => *time,
+        }
+    }
+}
+
+/// A level3 message with its price and size left as the feed's strings, so
+/// a book can parse them into its own number type without going through
+/// `Decimal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawMessage<'a> {
+    Open {
+        product_id: &'a str,
+        sequence: u64,
+        order_id: Uuid,
+        side: Side,
+        price: &'a str,
+        size: &'a str,
+        time: OffsetDateTime,
+    },
+    Change {
+        product_id: &'a str,
+        sequence: u64,
+        order_id: Uuid,
+        price: &'a str,
+        size: &'a str,
+        time: OffsetDateTime,
+    },
+    Match {
+        product_id: &'a str,
+        sequence: u64,
+        maker_order_id: Uuid,
+        taker_order_id: Uuid,
+        price: &'a str,
+        size: &'a str,
+        time: OffsetDateTime,
+    },
+    Noop {
+        product_id: &'a str,
+        sequence: u64,
+        time: OffsetDateTime,
+    },
+    Done {
+        product_id: &'a str,
+        sequence: u64,
+        order_id: Uuid,
+        time: OffsetDateTime,
+    },
+}
+
+impl RawMessage<'_> {
+    pub fn product_id(&self) -> &str {
+        match self {
+            Self::Open { product_id, .. } => product_id,
+            Self::Change { product_id, .. } => product_id,
+            Self::Match { product_id, .. } => product_id,
+            Self::Noop { product_id, .. } => product_id,
+            Self::Done { product_id, .. } => product_id,
+        }
+    }
+
+    pub fn sequence(&self) -> u64 {
+        match self {
+            Self::Open { sequence, .. } => *sequence,
+            Self::Change { sequence, .. } => *sequence,
+            Self::Match { sequence, .. } => *sequence,
+            Self::Noop { sequence, .. } => *sequence,
+            Self::Done { sequence, .. } => *sequence,
+        }
+    }
+
+    pub fn time(&self) -> OffsetDateTime {
+        match self {
+            Self::Open { time, .. } => *time,
+            Self::Change { time, .. } => *time,
+            Self::Match { time, .. } => *time,
+            Self::Noop { time, .. } => *time,
+            Self::Done { time, .. } => *time,
+        }
+    }
 }
 
 impl ChannelType for Message {
+    type Parser = Parser;
+
     fn channel_type() -> &'static str {
         "level3"
     }
@@ -73,6 +179,579 @@ impl ChannelType for Message {
     fn parse_schema() -> bool {
         true
     }
+
+    fn load_schema(parser: &mut Parser, payload: &[u8]) -> Result<(), Error> {
+        parser.load_schema(payload)
+    }
+
+    fn parse(parser: &mut Parser, payload: &[u8]) -> Result<Option<Self>, Error> {
+        parser.parse(payload)
+    }
+}
+
+/// The string fields of a compact level3 frame, a flat JSON array of strings,
+/// read in place.
+struct Fields<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    done: bool,
+}
+
+impl<'a> Fields<'a> {
+    fn new(bytes: &'a [u8], position: usize) -> Self {
+        Self {
+            bytes,
+            position,
+            done: false,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.position += 1;
+        }
+    }
+
+    fn next(&mut self) -> Result<&'a str, Error> {
+        if self.done {
+            return Err(Error::invalid("missing level3 field"));
+        }
+
+        self.skip_whitespace();
+
+        if self.bytes.get(self.position) != Some(&b'"') {
+            return Err(Error::invalid("level3 field is not a string"));
+        }
+
+        let start = self.position + 1;
+        let len = self.bytes[start..]
+            .iter()
+            .position(|byte| *byte == b'"')
+            .ok_or_else(|| Error::invalid("unterminated level3 field"))?;
+        let field = &self.bytes[start..start + len];
+
+        if field.contains(&b'\\') {
+            return Err(Error::invalid("escaped level3 field"));
+        }
+
+        self.position = start + len + 1;
+        self.skip_whitespace();
+
+        match self.bytes.get(self.position) {
+            Some(b',') => {}
+            Some(b']') => self.done = true,
+            _ => return Err(Error::invalid("malformed level3 frame")),
+        }
+
+        self.position += 1;
+
+        std::str::from_utf8(field).map_err(|_| Error::invalid("level3 field is not utf-8"))
+    }
+
+    fn finish(mut self) -> Result<(), Error> {
+        self.skip_whitespace();
+
+        match self.done && self.position == self.bytes.len() {
+            true => Ok(()),
+            false => Err(Error::invalid("unexpected level3 field")),
+        }
+    }
+}
+
+fn parse_u64(field: &str) -> Result<u64, Error> {
+    field.parse().map_err(|_| Error::invalid("level3 sequence"))
+}
+
+fn parse_uuid(field: &str) -> Result<Uuid, Error> {
+    Uuid::try_parse(field).map_err(|_| Error::invalid("level3 order id"))
+}
+
+fn parse_decimal(field: &str) -> Result<Decimal, Error> {
+    Decimal::from_str(field).map_err(|_| Error::invalid("level3 decimal"))
+}
+
+fn parse_side(field: &str) -> Result<Side, Error> {
+    match field {
+        "buy" => Ok(Side::Buy),
+        "sell" => Ok(Side::Sell),
+        _ => Err(Error::invalid("level3 side")),
+    }
+}
+
+fn digits(bytes: &[u8]) -> Option<u32> {
+    bytes.iter().try_fold(0u32, |value, byte| {
+        byte.is_ascii_digit()
+            .then(|| value * 10 + (byte - b'0') as u32)
+    })
+}
+
+/// Parse the feed's `YYYY-MM-DDTHH:MM:SS.ffffffZ` timestamps directly.
+fn parse_utc(bytes: &[u8]) -> Option<OffsetDateTime> {
+    let (bytes, b'Z') = bytes.split_last().map(|(last, rest)| (rest, *last))? else {
+        return None;
+    };
+
+    if bytes.len() < 19
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || bytes[10] != b'T'
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+
+    let nanosecond = match &bytes[19..] {
+        [] => 0,
+        [b'.', fraction @ ..] if (1..=9).contains(&fraction.len()) => {
+            digits(fraction)? * 10u32.pow(9 - fraction.len() as u32)
+        }
+        _ => return None,
+    };
+    let date = Date::from_calendar_date(
+        digits(&bytes[0..4])? as i32,
+        Month::try_from(digits(&bytes[5..7])? as u8).ok()?,
+        digits(&bytes[8..10])? as u8,
+    )
+    .ok()?;
+    let time = Time::from_hms_nano(
+        digits(&bytes[11..13])? as u8,
+        digits(&bytes[14..16])? as u8,
+        digits(&bytes[17..19])? as u8,
+        nanosecond,
+    )
+    .ok()?;
+
+    Some(PrimitiveDateTime::new(date, time).assume_utc())
+}
+
+fn parse_time(field: &str) -> Result<OffsetDateTime, Error> {
+    match parse_utc(field.as_bytes()) {
+        Some(time) => Ok(time),
+        None => OffsetDateTime::parse(field, &Iso8601::DEFAULT)
+            .map_err(|_| Error::invalid("level3 time")),
+    }
+}
+
+const MAX_FIELDS: usize = 16;
+
+/// The fields level3 messages are decoded from, in `FIELD_NAMES` order.
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Type,
+    ProductId,
+    Sequence,
+    OrderId,
+    MakerOrderId,
+    TakerOrderId,
+    Side,
+    Price,
+    Size,
+    Time,
+}
+
+const FIELD_NAMES: [&str; 10] = [
+    "type",
+    "product_id",
+    "sequence",
+    "order_id",
+    "maker_order_id",
+    "taker_order_id",
+    "side",
+    "price",
+    "size",
+    "time",
+];
+
+const MESSAGE_TYPES: [&str; 5] = ["open", "change", "match", "noop", "done"];
+
+/// The schema the feed has always sent, used until a schema frame is loaded.
+const DEFAULT_SCHEMA: [&[&str]; 5] = [
+    &[
+        "type",
+        "product_id",
+        "sequence",
+        "order_id",
+        "side",
+        "price",
+        "size",
+        "time",
+    ],
+    &[
+        "type",
+        "product_id",
+        "sequence",
+        "order_id",
+        "price",
+        "size",
+        "time",
+    ],
+    &[
+        "type",
+        "product_id",
+        "sequence",
+        "maker_order_id",
+        "taker_order_id",
+        "price",
+        "size",
+        "time",
+    ],
+    &["type", "product_id", "sequence", "time"],
+    &["type", "product_id", "sequence", "order_id", "time"],
+];
+
+/// The fields each message type needs, in `MESSAGE_TYPES` order.
+const REQUIRED_FIELDS: [&[Field]; 5] = [
+    &[
+        Field::Type,
+        Field::ProductId,
+        Field::Sequence,
+        Field::OrderId,
+        Field::Side,
+        Field::Price,
+        Field::Size,
+        Field::Time,
+    ],
+    &[
+        Field::Type,
+        Field::ProductId,
+        Field::Sequence,
+        Field::OrderId,
+        Field::Price,
+        Field::Size,
+        Field::Time,
+    ],
+    &[
+        Field::Type,
+        Field::ProductId,
+        Field::Sequence,
+        Field::MakerOrderId,
+        Field::TakerOrderId,
+        Field::Price,
+        Field::Size,
+        Field::Time,
+    ],
+    &[Field::Type, Field::ProductId, Field::Sequence, Field::Time],
+    &[
+        Field::Type,
+        Field::ProductId,
+        Field::Sequence,
+        Field::OrderId,
+        Field::Time,
+    ],
+];
+
+/// Where each field sits in the frames of one message type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    len: usize,
+    positions: [Option<usize>; FIELD_NAMES.len()],
+}
+
+impl Layout {
+    /// Map field names to positions. Names this parser doesn't know are
+    /// skipped over when decoding.
+    fn new<S: AsRef<str>>(names: &[S]) -> Self {
+        let mut positions = [None; FIELD_NAMES.len()];
+
+        for (position, name) in names.iter().enumerate() {
+            if let Some(field) = FIELD_NAMES.iter().position(|field| *field == name.as_ref()) {
+                positions[field].get_or_insert(position);
+            }
+        }
+
+        Self {
+            len: names.len(),
+            positions,
+        }
+    }
+
+    fn position(&self, field: Field) -> Option<usize> {
+        self.positions[field as usize]
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SchemaFrame {
+    #[serde(rename = "type")]
+    channel: SmartString<LazyCompact>,
+    schema: HashMap<SmartString<LazyCompact>, Vec<SmartString<LazyCompact>>>,
+}
+
+/// Parses level3 frames without going through serde, keeping the last
+/// product id so frames for the same product reuse it. Fields are found by
+/// the positions in the channel's schema frame.
+#[derive(Debug)]
+pub struct Parser {
+    product_id: SmartString<LazyCompact>,
+    type_position: usize,
+    layouts: [Option<Layout>; MESSAGE_TYPES.len()],
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self {
+            product_id: SmartString::new(),
+            type_position: 0,
+            layouts: DEFAULT_SCHEMA.map(|names| Some(Layout::new(names))),
+        }
+    }
+}
+
+impl Parser {
+    /// Replace the field positions with those in a level3 schema frame.
+    /// Fails if a message type lacks a field it needs. Message types missing
+    /// from the schema are rejected when they arrive.
+    pub fn load_schema(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let frame = serde_json::from_slice::<SchemaFrame>(frame)?;
+
+        if frame.channel != Message::channel_type() {
+            return Err(Error::invalid("not a level3 schema"));
+        }
+
+        let mut type_position = None;
+        let mut layouts = [None; MESSAGE_TYPES.len()];
+
+        for (index, message_type) in MESSAGE_TYPES.iter().enumerate() {
+            let Some(names) = frame.schema.get(*message_type) else {
+                warn!(message_type, "Level3 schema has no layout for message type");
+
+                continue;
+            };
+            let layout = Layout::new(names);
+
+            if layout.len > MAX_FIELDS {
+                error!(
+                    message_type,
+                    fields = layout.len,
+                    "Level3 schema has too many fields"
+                );
+
+                return Err(Error::invalid("level3 schema mismatch"));
+            }
+
+            for field in REQUIRED_FIELDS[index] {
+                if layout.position(*field).is_none() {
+                    error!(
+                        message_type,
+                        field = FIELD_NAMES[*field as usize],
+                        "Level3 schema is missing a field"
+                    );
+
+                    return Err(Error::invalid("level3 schema mismatch"));
+                }
+            }
+
+            // The type must be found before the layout is known, so it has
+            // to be in the same place for every message type.
+            let position = layout.position(Field::Type);
+
+            if *type_position.get_or_insert(position) != position {
+                error!(message_type, "Level3 schema moves the type field");
+
+                return Err(Error::invalid("level3 schema mismatch"));
+            }
+
+            layouts[index] = Some(layout);
+        }
+
+        self.type_position = type_position
+            .flatten()
+            .ok_or_else(|| Error::invalid("level3 schema mismatch"))?;
+        self.layouts = layouts;
+
+        debug!(schema = ?frame.schema, "Loaded level3 schema");
+
+        Ok(())
+    }
+
+    fn product_id(&mut self, field: &str) -> SmartString<LazyCompact> {
+        if self.product_id != field {
+            self.product_id = field.into();
+        }
+
+        self.product_id.clone()
+    }
+
+    /// Parse a frame into a message, or `None` if it's a heartbeat.
+    pub fn parse(&mut self, frame: &[u8]) -> Result<Option<Message>, Error> {
+        let Some(raw) = self.parse_raw(frame)? else {
+            return Ok(None);
+        };
+        let product_id = self.product_id(raw.product_id());
+        let message = match raw {
+            RawMessage::Open {
+                sequence,
+                order_id,
+                side,
+                price,
+                size,
+                time,
+                ..
+            } => Message::Open {
+                product_id,
+                sequence,
+                order_id,
+                side,
+                price: parse_decimal(price)?,
+                size: parse_decimal(size)?,
+                time,
+            },
+            RawMessage::Change {
+                sequence,
+                order_id,
+                price,
+                size,
+                time,
+                ..
+            } => Message::Change {
+                product_id,
+                sequence,
+                order_id,
+                price: parse_decimal(price)?,
+                size: parse_decimal(size)?,
+                time,
+            },
+            RawMessage::Match {
+                sequence,
+                maker_order_id,
+                taker_order_id,
+                price,
+                size,
+                time,
+                ..
+            } => Message::Match {
+                product_id,
+                sequence,
+                maker_order_id,
+                taker_order_id,
+                price: parse_decimal(price)?,
+                size: parse_decimal(size)?,
+                time,
+            },
+            RawMessage::Noop { sequence, time, .. } => Message::Noop {
+                product_id,
+                sequence,
+                time,
+            },
+            RawMessage::Done {
+                sequence,
+                order_id,
+                time,
+                ..
+            } => Message::Done {
+                product_id,
+                sequence,
+                order_id,
+                time,
+            },
+        };
+
+        Ok(Some(message))
+    }
+
+    /// Parse a frame into a message that borrows its price and size from the
+    /// frame, or `None` if it's a heartbeat.
+    pub fn parse_raw<'a>(&self, frame: &'a [u8]) -> Result<Option<RawMessage<'a>>, Error> {
+        let start = frame
+            .iter()
+            .position(|byte| !byte.is_ascii_whitespace())
+            .ok_or_else(|| Error::invalid("empty frame"))?;
+
+        match frame[start] {
+            b'[' => {}
+            b'{' => {
+                serde_json::from_slice::<Heartbeat>(frame)?;
+
+                return Ok(None);
+            }
+            _ => return Err(Error::invalid("malformed level3 frame")),
+        }
+
+        let mut fields = Fields::new(frame, start + 1);
+        let mut values = [""; MAX_FIELDS];
+        let mut len = 0;
+
+        while !fields.done {
+            *values
+                .get_mut(len)
+                .ok_or_else(|| Error::invalid("level3 frame does not match the schema"))? =
+                fields.next()?;
+            len += 1;
+        }
+
+        fields.finish()?;
+
+        let message_type = values[..len]
+            .get(self.type_position)
+            .ok_or_else(|| Error::invalid("level3 frame does not match the schema"))?;
+        let index = MESSAGE_TYPES
+            .iter()
+            .position(|name| name == message_type)
+            .ok_or_else(|| Error::invalid("unknown level3 message type"))?;
+        let layout = self.layouts[index]
+            .ok_or_else(|| Error::invalid("level3 message type is not in the schema"))?;
+
+        if layout.len != len {
+            return Err(Error::invalid("level3 frame does not match the schema"));
+        }
+
+        // Every required field was checked when the layout was loaded.
+        let get = |field| {
+            layout
+                .position(field)
+                .map(|position| values[position])
+                .ok_or_else(|| Error::Impossible)
+        };
+        let product_id = get(Field::ProductId)?;
+        let sequence = parse_u64(get(Field::Sequence)?)?;
+        let time = parse_time(get(Field::Time)?)?;
+        let message = match index {
+            0 => RawMessage::Open {
+                product_id,
+                sequence,
+                order_id: parse_uuid(get(Field::OrderId)?)?,
+                side: parse_side(get(Field::Side)?)?,
+                price: get(Field::Price)?,
+                size: get(Field::Size)?,
+                time,
+            },
+            1 => RawMessage::Change {
+                product_id,
+                sequence,
+                order_id: parse_uuid(get(Field::OrderId)?)?,
+                price: get(Field::Price)?,
+                size: get(Field::Size)?,
+                time,
+            },
+            2 => RawMessage::Match {
+                product_id,
+                sequence,
+                maker_order_id: parse_uuid(get(Field::MakerOrderId)?)?,
+                taker_order_id: parse_uuid(get(Field::TakerOrderId)?)?,
+                price: get(Field::Price)?,
+                size: get(Field::Size)?,
+                time,
+            },
+            3 => RawMessage::Noop {
+                product_id,
+                sequence,
+                time,
+            },
+            _ => RawMessage::Done {
+                product_id,
+                sequence,
+                order_id: parse_uuid(get(Field::OrderId)?)?,
+                time,
+            },
+        };
+
+        Ok(Some(message))
+    }
 }
 
 impl Display for Message {
@@ -327,7 +1006,7 @@ impl<'de> Deserialize<'de> for Message {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum Side {
     Buy,
@@ -397,6 +1076,158 @@ mod test {
         Ok(())
     }
 
+    const FRAMES: [&str; 5] = [
+        r#"["change","KSM-USD","1085439001","5ca12898-a4e0-4da5-83e7-58f6c8b23a08","47.39","466.02","2024-12-07T03:05:26.853178Z"]"#,
+        r#"["done","KSM-USD","1085439002","c61973b4-64c6-42f5-92ad-0122b6835346","2024-12-07T03:05:26.858722Z"]"#,
+        r#"["match","KSM-USD","1085550786","f38ca06b-a427-4072-94db-1489294d990b","1b03667a-ada9-45b6-b6bd-7ef8b153c3b5","46.5","4.6203","2024-12-07T03:45:03.660871Z"]"#,
+        r#"["noop","KSM-USD","1085550970","2024-12-07T03:45:06.664022Z"]"#,
+        r#"["open","KSM-USD","1085550965","757aaa18-41e6-4374-9341-769bf32d2c72","sell","46.84","222.7125","2024-12-07T03:45:06.586641Z"]"#,
+    ];
+
+    #[test]
+    fn parser_matches_deserialize() {
+        let mut parser = Parser::default();
+
+        for frame in FRAMES {
+            let expected: Message = serde_json::from_str(frame).unwrap();
+
+            assert_eq!(
+                parser.parse(frame.as_bytes()).unwrap(),
+                Some(expected.clone())
+            );
+
+            let spaced = frame.replace(',', " , ").replace('[', " [ ");
+
+            assert_eq!(parser.parse(spaced.as_bytes()).unwrap(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn raw_messages_keep_the_feed_strings() {
+        let frame = r#"["change","KSM-USD","1085439001","5ca12898-a4e0-4da5-83e7-58f6c8b23a08","47.390","466.02","2024-12-07T03:05:26.853178Z"]"#;
+
+        assert!(matches!(
+            Parser::default().parse_raw(frame.as_bytes()).unwrap(),
+            Some(RawMessage::Change {
+                product_id: "KSM-USD",
+                sequence: 1085439001,
+                price: "47.390",
+                size: "466.02",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parser_skips_heartbeats_and_rejects_malformed_frames() {
+        let mut parser = Parser::default();
+        let heartbeat = r#"{"type":"heartbeat","sequence":1085550970,"last_trade_id":1,"product_id":"KSM-USD","time":"2024-12-07T03:45:06.664022Z"}"#;
+
+        assert_eq!(parser.parse(heartbeat.as_bytes()).unwrap(), None);
+
+        for frame in [
+            "",
+            "[]",
+            r#"{"type":"error"}"#,
+            r#"["noop","KSM-USD","1085550970"]"#,
+            r#"["noop","KSM-USD","1085550970","2024-12-07T03:45:06.664022Z","extra"]"#,
+            r#"["noop","KSM-USD","1085550970","2024-12-07T03:45:06.664022Z"] x"#,
+            r#"["noop","KSM-\"USD","1085550970","2024-12-07T03:45:06.664022Z"]"#,
+            r#"["noop","KSM-USD",1085550970,"2024-12-07T03:45:06.664022Z"]"#,
+            r#"["open","KSM-USD","1","757aaa18-41e6-4374-9341-769bf32d2c72","up","1","1","2024-12-07T03:45:06Z"]"#,
+            r#"["fill","KSM-USD","1085550970","2024-12-07T03:45:06.664022Z"]"#,
+        ] {
+            assert!(parser.parse(frame.as_bytes()).is_err(), "{frame}");
+        }
+    }
+
+    const SCHEMA: &str = r#"{"type":"level3","schema":{"change":["type","product_id","sequence","order_id","price","size","time"],"done":["type","product_id","sequence","order_id","time"],"match":["type","product_id","sequence","maker_order_id","taker_order_id","price","size","time"],"noop":["type","product_id","sequence","time"],"open":["type","product_id","sequence","order_id","side","price","size","time"]}}"#;
+
+    #[test]
+    fn loads_the_current_schema() {
+        let mut parser = Parser::default();
+
+        parser.load_schema(SCHEMA.as_bytes()).unwrap();
+
+        assert_eq!(parser.layouts, Parser::default().layouts);
+
+        for frame in FRAMES {
+            assert!(parser.parse(frame.as_bytes()).unwrap().is_some());
+        }
+    }
+
+    #[test]
+    fn decodes_reordered_and_extended_schemas() {
+        let mut parser = Parser::default();
+        let schema = SCHEMA.replace(
+            r#""open":["type","product_id","sequence","order_id","side","price","size","time"]"#,
+            r#""open":["type","time","product_id","sequence","client_oid","order_id","size","price","side"]"#,
+        );
+        let frame = r#"["open","2024-12-07T03:45:06.586641Z","KSM-USD","1085550965","","757aaa18-41e6-4374-9341-769bf32d2c72","222.7125","46.84","sell"]"#;
+
+        parser.load_schema(schema.as_bytes()).unwrap();
+
+        assert_eq!(
+            parser.parse(frame.as_bytes()).unwrap(),
+            Some(serde_json::from_str(FRAMES[4]).unwrap())
+        );
+
+        // Frames in the old layout no longer fit.
+        assert!(matches!(
+            parser.parse(FRAMES[4].as_bytes()),
+            Err(Error::Invalid("level3 frame does not match the schema"))
+        ));
+    }
+
+    #[test]
+    fn rejects_mismatched_schemas() {
+        let mut parser = Parser::default();
+
+        for schema in [
+            SCHEMA.replace(r#""maker_order_id","#, ""),
+            SCHEMA.replace(
+                r#""noop":["type","product_id","sequence","time"]"#,
+                r#""noop":["product_id","type","sequence","time"]"#,
+            ),
+            SCHEMA.replace(r#""type":"level3""#, r#""type":"level2""#),
+        ] {
+            assert!(matches!(
+                parser.load_schema(schema.as_bytes()),
+                Err(Error::Invalid(_))
+            ));
+        }
+
+        // A message type missing from the schema is rejected when it arrives.
+        let schema = SCHEMA.replace(r#""noop":["type","product_id","sequence","time"],"#, "");
+
+        parser.load_schema(schema.as_bytes()).unwrap();
+
+        assert!(matches!(
+            parser.parse(FRAMES[3].as_bytes()),
+            Err(Error::Invalid("level3 message type is not in the schema"))
+        ));
+        assert!(parser.parse(FRAMES[0].as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn fast_time_parsing_matches_iso8601() {
+        for time in [
+            "2024-12-07T03:45:06Z",
+            "2024-12-07T03:45:06.6Z",
+            "2024-12-07T03:45:06.664022Z",
+            "2024-02-29T23:59:59.123456789Z",
+            "2024-12-07T03:45:06.664022+01:00",
+        ] {
+            assert_eq!(
+                parse_time(time).unwrap(),
+                OffsetDateTime::parse(time, &Iso8601::DEFAULT).unwrap()
+            );
+        }
+
+        assert!(parse_time("2023-02-29T00:00:00Z").is_err());
+        assert!(parse_time("2024-12-07 03:45:06Z").is_err());
+    }
+
     #[tokio::test]
     async fn can_deserialize_open_message() -> test::Result<()> {
         test::setup()?;
@@ -0,0 +1,151 @@
+use crate::exchange::{
+    common::Error,
+    websocket::channels::{ChannelType, level_three::Side, parse_tagged},
+};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use smartstring::{LazyCompact, SmartString};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use time::OffsetDateTime;
+
+/// A new aggregate size at a price level. A zero size removes the level.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+pub struct Change {
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
+/// A message from the `level2` channel: a snapshot of every price level,
+/// then updates to individual levels.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Message {
+    Snapshot {
+        product_id: SmartString<LazyCompact>,
+        bids: Vec<(Decimal, Decimal)>,
+        asks: Vec<(Decimal, Decimal)>,
+        #[serde(default, with = "time::serde::iso8601::option")]
+        time: Option<OffsetDateTime>,
+    },
+    #[serde(rename = "l2update")]
+    Update {
+        product_id: SmartString<LazyCompact>,
+        changes: Vec<Change>,
+        #[serde(with = "time::serde::iso8601")]
+        time: OffsetDateTime,
+    },
+}
+
+impl Message {
+    pub fn product_id(&self) -> &str {
+        match self {
+            Self::Snapshot { product_id, .. } => product_id,
+            Self::Update { product_id, .. } => product_id,
+        }
+    }
+}
+
+impl ChannelType for Message {
+    type Parser = ();
+
+    fn channel_type() -> &'static str {
+        "level2"
+    }
+
+    fn parse_schema() -> bool {
+        false
+    }
+
+    fn parse(_: &mut (), payload: &[u8]) -> Result<Option<Self>, Error> {
+        parse_tagged(payload)
+    }
+}
+
+/// A message from the `level2_batch` channel, which carries the same messages
+/// as `level2` with updates batched every 50 milliseconds.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct Batch(pub Message);
+
+impl ChannelType for Batch {
+    type Parser = ();
+
+    fn channel_type() -> &'static str {
+        "level2_batch"
+    }
+
+    fn parse_schema() -> bool {
+        false
+    }
+
+    fn parse(_: &mut (), payload: &[u8]) -> Result<Option<Self>, Error> {
+        parse_tagged(payload)
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Snapshot {
+                product_id,
+                bids,
+                asks,
+                ..
+            } => write!(
+                f,
+                "[SNAPSHOT] product_id: {product_id}, bids: {}, asks: {}",
+                bids.len(),
+                asks.len()
+            ),
+            Self::Update {
+                product_id,
+                changes,
+                time,
+            } => write!(
+                f,
+                "[L2UPDATE] product_id: {product_id}, changes: {}, time: {time}",
+                changes.len()
+            ),
+        }
+    }
+}
+
+impl Display for Batch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn can_parse_snapshot_and_update_messages() {
+        let snapshot = r#"{"type":"snapshot","product_id":"BTC-USD","bids":[["10101.10","0.45054140"]],"asks":[["10102.55","0.57753524"],["10102.56","1"]]}"#;
+        let update = r#"{"type":"l2update","product_id":"BTC-USD","time":"2019-08-14T20:42:27.265Z","changes":[["buy","10101.80000000","0.162567"],["sell","10102.55","0"]]}"#;
+
+        assert!(matches!(
+            Message::parse(&mut (), snapshot.as_bytes()).unwrap(),
+            Some(Message::Snapshot { bids, asks, time: None, .. })
+                if bids.len() == 1 && asks.len() == 2
+        ));
+        assert!(matches!(
+            Batch::parse(&mut (), update.as_bytes()).unwrap(),
+            Some(Batch(Message::Update { changes, .. })) if changes[1] == Change {
+                side: Side::Sell,
+                price: Decimal::new(1010255, 2),
+                size: Decimal::ZERO,
+            }
+        ));
+    }
+
+    #[test]
+    fn skips_heartbeats() {
+        let heartbeat = r#"{"type":"heartbeat","sequence":90,"last_trade_id":20,"product_id":"BTC-USD","time":"2014-11-07T08:19:28.464459Z"}"#;
+
+        assert_eq!(Message::parse(&mut (), heartbeat.as_bytes()).unwrap(), None);
+        assert!(Message::parse(&mut (), br#"{"type":"l2update"}"#).is_err());
+    }
+}
@@ -13,6 +13,7 @@ use serde::{Deserialize, de::DeserializeOwned};
 use serde_json::Value;
 use smartstring::{LazyCompact, SmartString};
 use std::{
+    borrow::Cow,
     future::Future,
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
@@ -29,27 +30,57 @@ use tokio_rustls::{
     TlsConnector,
     rustls::{ClientConfig, RootCertStore},
 };
-use tracing::{debug, error};
+use tracing::{debug, error, trace};
 
+pub mod full;
 pub mod level_three;
+pub mod level_two;
+pub mod ticker;
+
+/// The authenticated feed, which carries every channel.
+pub const DIRECT_FEED_DOMAIN: &str = "ws-direct.exchange.coinbase.com";
+
+/// The public feed, for channels that don't need authentication.
+pub const PUBLIC_FEED_DOMAIN: &str = "ws-feed.exchange.coinbase.com";
+
+pub const FEED_PORT: u16 = 443;
+
+pub trait ChannelType: Sized {
+    /// State kept between frames while parsing.
+    type Parser: Default + Send;
 
-pub trait ChannelType {
     fn channel_type() -> &'static str;
     fn parse_schema() -> bool;
+
+    /// Whether subscribing needs credentials. Public channels connect without
+    /// them.
+    fn requires_authentication() -> bool {
+        true
+    }
+
+    /// Load the schema frame sent after subscribing, if `parse_schema` is
+    /// true.
+    fn load_schema(_parser: &mut Self::Parser, _payload: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Parse a frame into a message, or `None` if it's a heartbeat.
+    fn parse(parser: &mut Self::Parser, payload: &[u8]) -> Result<Option<Self>, Error>;
 }
 
 pub struct Channel<T>
 where
-    T: 'static + DeserializeOwned + Send + Clone,
+    T: 'static + ChannelType + DeserializeOwned + Send + Clone,
 {
     ws: WebSocket<TokioIo<Upgraded>>,
     cache: Option<Vec<T>>,
     token_bucket: TokenBucket,
+    parser: T::Parser,
 }
 
 impl<T> Channel<T>
 where
-    T: 'static + DeserializeOwned + Send + Clone,
+    T: 'static + ChannelType + DeserializeOwned + Send + Clone,
 {
     /// Send a frame (message) to the host, respecting rate limits.
     async fn write_frame<'f>(&mut self, frame: Frame<'f>) -> Result<(), Error> {
@@ -88,26 +119,22 @@ where
     /// heartbeat channel. Heartbeat messages arrive every second regardless of
     /// market activity, ensuring the timeout only triggers on actual connection
     /// failures rather than quiet markets. Heartbeat messages are silently filtered
-    /// out; only `T` messages are returned to the caller.
+    /// out; only `T` messages are returned to the caller. Each frame is parsed
+    /// once by `T::parse`.
     pub async fn next(&mut self) -> Result<T, Error> {
-        'message: loop {
+        loop {
             match tokio::time::timeout(Duration::from_secs(10), self.ws.read_frame()).await {
-                Ok(Ok(frame)) => match serde_json::from_slice::<T>(frame.payload.as_ref()) {
-                    Ok(message) => return Ok(message),
-                    Err(message_error) => {
-                        match serde_json::from_slice::<Heartbeat>(frame.payload.as_ref()) {
-                            Ok(_) => continue 'message,
-                            Err(heartbeat_error) => {
-                                error!("Failed to deserialize message => {message_error}");
-                                error!("Failed to deserialize heartbeat => {heartbeat_error}");
-
-                                if let Ok(payload) = std::str::from_utf8(frame.payload.as_ref()) {
-                                    error!("Unknown message payload => {payload}");
-                                }
-
-                                return Err(Error::from(heartbeat_error));
-                            }
+                Ok(Ok(frame)) => match T::parse(&mut self.parser, frame.payload.as_ref()) {
+                    Ok(Some(message)) => return Ok(message),
+                    Ok(None) => continue,
+                    Err(error) => {
+                        error!("Failed to parse message => {error}");
+
+                        if let Ok(payload) = std::str::from_utf8(frame.payload.as_ref()) {
+                            error!("Unknown message payload => {payload}");
                         }
+
+                        return Err(error);
                     }
                 },
                 Ok(Err(error)) => {
@@ -168,7 +195,7 @@ where
 
 pub struct CachingChannel<T>
 where
-    T: 'static + DeserializeOwned + Send + Clone,
+    T: 'static + ChannelType + DeserializeOwned + Send + Clone,
 {
     tx: Sender<()>,
     join_handle: JoinHandle<Result<Channel<T>, Error>>,
@@ -176,7 +203,7 @@ where
 
 impl<T> CachingChannel<T>
 where
-    T: 'static + DeserializeOwned + Send + Clone,
+    T: 'static + ChannelType + DeserializeOwned + Send + Clone,
 {
     pub async fn join(self) -> Result<Channel<T>, Error> {
         // Signal the caching task to stop caching.
@@ -237,6 +264,9 @@ impl ChannelBuilder {
         self
     }
 
+    /// Connect somewhere other than the default endpoint. By default, a channel
+    /// that doesn't need authentication connects to `PUBLIC_FEED_DOMAIN` when
+    /// no credentials are given, and everything else to `DIRECT_FEED_DOMAIN`.
     pub fn with_endpoint(mut self, domain: impl Into<String>, port: u16) -> Self {
         self.domain = Some(domain.into());
         self.port = Some(port);
@@ -256,14 +286,35 @@ impl ChannelBuilder {
         self
     }
 
-    /// Connect to the endpoint and stream order book data.
-    pub async fn connect<T>(self) -> Result<Channel<T>, Error>
-    where
-        T: 'static + ChannelType + DeserializeOwned + Send + Clone,
-    {
+    /// Whether to subscribe without credentials, which public channels can.
+    fn is_anonymous<T: ChannelType>(&self) -> bool {
+        !T::requires_authentication()
+            && self.key.is_none()
+            && self.signer.is_none()
+            && self.passphrase.is_none()
+    }
+
+    /// Build the subscription message, signed unless it's anonymous.
+    fn subscription<T: ChannelType>(&self) -> Result<Value, Error> {
+        debug!("Creating subscription message");
+        let mut subscription = serde_json::json!({
+            "type": "subscribe",
+            "channels": [
+                { "name": T::channel_type(), "product_ids": self.product_ids },
+                { "name": "heartbeat", "product_ids": self.product_ids },
+            ],
+        });
+
+        if self.is_anonymous::<T>() {
+            debug!("Subscribing without authentication");
+
+            return Ok(subscription);
+        }
+
         debug!("Creating signing key");
         let key = self
             .key
+            .clone()
             .ok_or_else(|| Error::unavailable("authentication key"))?;
 
         debug!("Generating signature");
@@ -273,36 +324,45 @@ impl ChannelBuilder {
             .to_string();
         let signature = self
             .signer
+            .as_ref()
             .ok_or_else(|| Error::unavailable("authentication secret"))?
             .get_cb_access_sign(timestamp.as_str(), "/users/self/verify", "", "GET")?;
 
         debug!("Fetching passphrase");
         let passphrase = self
             .passphrase
+            .clone()
             .ok_or_else(|| Error::unavailable("authentication passphrase"))?;
 
-        debug!("Creating subscription message");
-        let subscription_message = serde_json::to_string(&serde_json::json!({
-            "type": "subscribe",
-            "channels": [
-                { "name": T::channel_type(), "product_ids": self.product_ids },
-                { "name": "heartbeat", "product_ids": self.product_ids },
-            ],
-            "signature": signature,
-            "key": key,
-            "passphrase": passphrase,
-            "timestamp": timestamp,
-        }))?;
-
-        debug!("Fetching endpoint domain");
-        let domain = self
-            .domain
-            .ok_or_else(|| Error::unavailable("endpoint domain"))?;
-
-        debug!("Fetching endpoint port");
-        let port = self
-            .port
-            .ok_or_else(|| Error::unavailable("endpoint port"))?;
+        subscription["signature"] = signature.into();
+        subscription["key"] = key.into();
+        subscription["passphrase"] = passphrase.into();
+        subscription["timestamp"] = timestamp.into();
+
+        Ok(subscription)
+    }
+
+    /// Get the endpoint to connect to, defaulting to the feed the subscription
+    /// needs.
+    fn endpoint<T: ChannelType>(&self) -> (String, u16) {
+        let domain = self.domain.clone().unwrap_or_else(|| {
+            match self.is_anonymous::<T>() {
+                true => PUBLIC_FEED_DOMAIN,
+                false => DIRECT_FEED_DOMAIN,
+            }
+            .to_string()
+        });
+
+        (domain, self.port.unwrap_or(FEED_PORT))
+    }
+
+    /// Connect to the endpoint and stream order book data.
+    pub async fn connect<T>(self) -> Result<Channel<T>, Error>
+    where
+        T: 'static + ChannelType + DeserializeOwned + Send + Clone,
+    {
+        let subscription_message = serde_json::to_string(&self.subscription::<T>()?)?;
+        let (domain, port) = self.endpoint::<T>();
 
         debug!("Establishing TCP stream");
         let tcp_stream = TcpStream::connect(format!("{domain}:{port}")).await?;
@@ -350,6 +410,7 @@ impl ChannelBuilder {
         let mut channel = Channel {
             ws,
             cache: None,
+            parser: T::Parser::default(),
             token_bucket: self
                 .token_bucket
                 .ok_or_else(|| Error::unavailable("token bucket"))?,
@@ -366,8 +427,10 @@ impl ChannelBuilder {
         let _ = serde_json::from_slice::<Value>(channel.read_frame().await?.payload.as_ref())?;
 
         if T::parse_schema() {
-            debug!("Deserializing schema response");
-            let _ = serde_json::from_slice::<Value>(channel.read_frame().await?.payload.as_ref())?;
+            debug!("Loading schema response");
+            let payload = channel.read_frame().await?.payload.to_vec();
+
+            T::load_schema(&mut channel.parser, &payload)?;
         }
 
         Ok(channel)
@@ -386,6 +449,30 @@ where
     }
 }
 
+/// The `type` of a frame on a channel of JSON objects, read without
+/// deserializing the rest.
+#[derive(Deserialize)]
+struct Tag<'a> {
+    #[serde(rename = "type", borrow)]
+    kind: Cow<'a, str>,
+}
+
+/// Parse a frame from a channel of JSON objects tagged by `type`, returning
+/// `None` for heartbeats. The tag picks the one type the frame is decoded as,
+/// so a malformed message fails with its own error.
+pub(crate) fn parse_tagged<T: DeserializeOwned>(payload: &[u8]) -> Result<Option<T>, Error> {
+    match serde_json::from_slice::<Tag>(payload)?.kind.as_ref() {
+        "heartbeat" => {
+            let heartbeat = serde_json::from_slice::<Heartbeat>(payload)?;
+
+            trace!(?heartbeat, "Skipping heartbeat");
+
+            Ok(None)
+        }
+        _ => Ok(Some(serde_json::from_slice(payload)?)),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
 pub enum Heartbeat {
@@ -402,7 +489,10 @@ pub enum Heartbeat {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{exchange::websocket::channels::level_three::Message, test};
+    use crate::{
+        exchange::websocket::channels::{level_three::Message, ticker::Ticker},
+        test,
+    };
     use tokio::time::{Duration, sleep};
 
     #[tokio::test]
@@ -417,7 +507,7 @@ mod test {
         // Set up the channel.
         let mut channel = ChannelBuilder::default()
             .with_authentication(key, secret, passphrase)?
-            .with_endpoint("ws-direct.exchange.coinbase.com", 443)
+            .with_endpoint(DIRECT_FEED_DOMAIN, FEED_PORT)
             .with_product_id("BTC-USD")
             .with_token_bucket(TokenBucket::new(1_000, Duration::from_millis(100)))
             .connect::<Message>()
@@ -443,7 +533,7 @@ mod test {
         // Set up the channel.
         let channel = ChannelBuilder::default()
             .with_authentication(key, secret, passphrase)?
-            .with_endpoint("ws-direct.exchange.coinbase.com", 443)
+            .with_endpoint(DIRECT_FEED_DOMAIN, FEED_PORT)
             .with_product_id("BTC-USD")
             .with_token_bucket(TokenBucket::new(1_000, Duration::from_millis(100)))
             .connect::<Message>()
@@ -480,4 +570,37 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn public_channels_subscribe_to_the_public_feed_without_a_signature() {
+        let builder = ChannelBuilder::default().with_product_id("BTC-USD");
+        let subscription = builder.subscription::<Ticker>().unwrap();
+
+        for field in ["signature", "key", "passphrase", "timestamp"] {
+            assert!(subscription.get(field).is_none());
+        }
+
+        assert_eq!(subscription["channels"][0]["name"], "ticker");
+        assert_eq!(
+            builder.endpoint::<Ticker>(),
+            (PUBLIC_FEED_DOMAIN.to_string(), FEED_PORT)
+        );
+
+        // Channels that need credentials can't subscribe without them.
+        assert!(matches!(
+            builder.subscription::<Message>(),
+            Err(Error::Unavailable(_))
+        ));
+        assert_eq!(builder.endpoint::<Message>().0, DIRECT_FEED_DOMAIN);
+
+        // Given credentials, a public channel is signed and uses the direct feed.
+        let builder = builder
+            .with_key("key")
+            .with_signer(Signer::try_from("c2VjcmV0").unwrap())
+            .with_passphrase("passphrase".into());
+        let subscription = builder.subscription::<Ticker>().unwrap();
+
+        assert!(subscription["signature"].is_string());
+        assert_eq!(builder.endpoint::<Ticker>().0, DIRECT_FEED_DOMAIN);
+    }
 }
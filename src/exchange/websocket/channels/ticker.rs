@@ -0,0 +1,159 @@
+use crate::exchange::{
+    common::Error,
+    websocket::channels::{ChannelType, level_three::Side, parse_tagged},
+};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use smartstring::{LazyCompact, SmartString};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use time::OffsetDateTime;
+
+/// The latest trade and top of book for a product. The first ticker after
+/// subscribing may not carry the trade fields.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Ticker {
+    pub sequence: u64,
+    pub product_id: SmartString<LazyCompact>,
+    pub price: Decimal,
+    pub best_bid: Decimal,
+    pub best_bid_size: Decimal,
+    pub best_ask: Decimal,
+    pub best_ask_size: Decimal,
+    pub open_24h: Decimal,
+    pub high_24h: Decimal,
+    pub low_24h: Decimal,
+    pub volume_24h: Decimal,
+    pub volume_30d: Decimal,
+    #[serde(default)]
+    pub last_size: Option<Decimal>,
+    #[serde(default)]
+    pub side: Option<Side>,
+    #[serde(default)]
+    pub trade_id: Option<u64>,
+    #[serde(default, with = "time::serde::iso8601::option")]
+    pub time: Option<OffsetDateTime>,
+}
+
+/// Checks the `type` tag, which `Ticker` itself doesn't keep.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Frame {
+    Ticker(Ticker),
+}
+
+fn parse(payload: &[u8]) -> Result<Option<Ticker>, Error> {
+    Ok(parse_tagged::<Frame>(payload)?.map(|Frame::Ticker(ticker)| ticker))
+}
+
+impl ChannelType for Ticker {
+    type Parser = ();
+
+    fn channel_type() -> &'static str {
+        "ticker"
+    }
+
+    fn parse_schema() -> bool {
+        false
+    }
+
+    fn requires_authentication() -> bool {
+        false
+    }
+
+    fn parse(_: &mut (), payload: &[u8]) -> Result<Option<Self>, Error> {
+        parse(payload)
+    }
+}
+
+/// A ticker from the `ticker_batch` channel, which sends the same messages as
+/// `ticker` at most every 5 seconds.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Batch(pub Ticker);
+
+impl ChannelType for Batch {
+    type Parser = ();
+
+    fn channel_type() -> &'static str {
+        "ticker_batch"
+    }
+
+    fn parse_schema() -> bool {
+        false
+    }
+
+    fn requires_authentication() -> bool {
+        false
+    }
+
+    fn parse(_: &mut (), payload: &[u8]) -> Result<Option<Self>, Error> {
+        Ok(parse(payload)?.map(Batch))
+    }
+}
+
+impl Display for Ticker {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "[TICKER] sequence: {}, product_id: {}, price: {}, best_bid: {}, best_ask: {}",
+            self.sequence, self.product_id, self.price, self.best_bid, self.best_ask
+        )
+    }
+}
+
+impl Display for Batch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn can_parse_ticker_messages() {
+        let ticker = r#"{"type":"ticker","sequence":37475248783,"product_id":"ETH-USD","price":"1285.22","open_24h":"1310.79","volume_24h":"245532.79269678","low_24h":"1280.52","high_24h":"1313.8","volume_30d":"9788783.60117027","best_bid":"1285.04","best_bid_size":"0.46688654","best_ask":"1285.27","best_ask_size":"1.56637040","side":"buy","time":"2022-10-19T23:28:22.061769Z","trade_id":370843401,"last_size":"11.4396987"}"#;
+        let ticker = Ticker::parse(&mut (), ticker.as_bytes()).unwrap().unwrap();
+
+        assert_eq!(ticker.sequence, 37475248783);
+        assert_eq!(ticker.price, Decimal::new(128522, 2));
+        assert_eq!(ticker.best_ask_size, Decimal::new(156637040, 8));
+        assert_eq!(ticker.side, Some(Side::Buy));
+        assert_eq!(ticker.trade_id, Some(370843401));
+        assert!(ticker.time.is_some());
+    }
+
+    #[test]
+    fn can_parse_tickers_without_trades() {
+        let ticker = r#"{"type":"ticker","sequence":1,"product_id":"ETH-USD","price":"1285.22","open_24h":"1310.79","volume_24h":"245532.79269678","low_24h":"1280.52","high_24h":"1313.8","volume_30d":"9788783.60117027","best_bid":"1285.04","best_bid_size":"0.46688654","best_ask":"1285.27","best_ask_size":"1.56637040"}"#;
+
+        assert!(matches!(
+            Batch::parse(&mut (), ticker.as_bytes()).unwrap(),
+            Some(Batch(Ticker {
+                side: None,
+                trade_id: None,
+                last_size: None,
+                time: None,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn skips_heartbeats_and_rejects_other_types() {
+        let heartbeat = r#"{"type":"heartbeat","sequence":90,"last_trade_id":20,"product_id":"BTC-USD","time":"2014-11-07T08:19:28.464459Z"}"#;
+        let snapshot = r#"{"type":"snapshot","product_id":"BTC-USD","bids":[],"asks":[]}"#;
+
+        assert_eq!(Ticker::parse(&mut (), heartbeat.as_bytes()).unwrap(), None);
+        assert!(Ticker::parse(&mut (), snapshot.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn malformed_tickers_report_their_own_error() {
+        let ticker = r#"{"type":"ticker","sequence":1,"product_id":"ETH-USD"}"#;
+        let error = Ticker::parse(&mut (), ticker.as_bytes()).unwrap_err();
+
+        assert!(error.to_string().contains("missing field `price`"));
+    }
+}
@@ -0,0 +1,537 @@
+use crate::{
+    CompactOrder, CompactOrderBook, CompactOrders, Handle, Level, LevelStorage, Order, OrderBook,
+    Orders, Update,
+    exchange::{
+        common::{Error, types::Scale},
+        rest::products::Product,
+        websocket::channels::level_three::{RawMessage, Side},
+    },
+};
+use std::collections::{BTreeMap, HashMap, hash_map::Entry};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// A price level in increments: `price` in quote increments and `size` in
+/// base increments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedLevel {
+    pub price: u64,
+    pub size: u64,
+    pub order_count: usize,
+}
+
+impl FixedLevel {
+    fn new(price: u64, orders: &Orders<u64>) -> Self {
+        Self {
+            price,
+            size: orders.total_size,
+            order_count: orders.len(),
+        }
+    }
+}
+
+/// An order book that keeps prices and sizes as integer counts of the
+/// product's increments, so updates and queries avoid decimal arithmetic.
+/// It applies messages exactly as `OrderBook` does.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FixedOrderBook {
+    pub product: Product,
+    price_scale: Scale,
+    size_scale: Scale,
+    asks: BTreeMap<u64, Orders<u64>>,
+    bids: BTreeMap<u64, Orders<u64>>,
+    sequence: u64,
+    updated_at: OffsetDateTime,
+    index: HashMap<Uuid, (Side, u64, Handle)>,
+}
+
+impl FixedOrderBook {
+    /// Convert a book, failing if any price or size isn't a whole number of
+    /// the product's increments.
+    pub fn from_order_book(order_book: &OrderBook) -> Result<Self, Error> {
+        let mut fixed = Self {
+            product: order_book.product.clone(),
+            price_scale: Scale::new(order_book.product.quote_increment)?,
+            size_scale: Scale::new(order_book.product.base_increment)?,
+            asks: BTreeMap::new(),
+            bids: BTreeMap::new(),
+            sequence: order_book.sequence,
+            updated_at: order_book.updated_at,
+            index: HashMap::with_capacity(order_book.index.len()),
+        };
+
+        for (side, halfbook) in [
+            (Side::Buy, &order_book.bids),
+            (Side::Sell, &order_book.asks),
+        ] {
+            for (price, orders) in halfbook.iter() {
+                let price = fixed.price_scale.units(*price)?;
+
+                for order in orders.iter() {
+                    let size = fixed.size_scale.units(order.size)?;
+
+                    fixed.insert(side, price, Order::new(order.id, size))?;
+                }
+            }
+        }
+
+        Ok(fixed)
+    }
+
+    fn halfbook_mut(&mut self, side: Side) -> &mut BTreeMap<u64, Orders<u64>> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    fn halfbook(&self, side: Side) -> &BTreeMap<u64, Orders<u64>> {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
+    }
+
+    /// Apply a message with the same semantics as `OrderBook::update_with`,
+    /// parsing its price and size straight into increments.
+    pub fn update_with(&mut self, raw_message: &RawMessage<'_>) -> Result<(), Error> {
+        let update = match *raw_message {
+            RawMessage::Open {
+                order_id,
+                side,
+                price,
+                size,
+                ..
+            } => Update::Open {
+                order_id,
+                side,
+                price: self.price_scale.parse(price)?,
+                size: self.size_scale.parse(size)?,
+            },
+            RawMessage::Change {
+                order_id,
+                price,
+                size,
+                ..
+            } => Update::Change {
+                order_id,
+                price: self.price_scale.parse(price)?,
+                size: self.size_scale.parse(size)?,
+            },
+            RawMessage::Match {
+                maker_order_id,
+                price,
+                size,
+                ..
+            } => Update::Match {
+                maker_order_id,
+                price: self.price_scale.parse(price)?,
+                size: self.size_scale.parse(size)?,
+            },
+            RawMessage::Noop { .. } => Update::Noop,
+            RawMessage::Done { order_id, .. } => Update::Done { order_id },
+        };
+
+        self.apply_update(raw_message.sequence(), raw_message.time(), update)?;
+
+        Ok(())
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn updated_at(&self) -> OffsetDateTime {
+        self.updated_at
+    }
+
+    pub fn price_scale(&self) -> Scale {
+        self.price_scale
+    }
+
+    pub fn size_scale(&self) -> Scale {
+        self.size_scale
+    }
+
+    pub fn best_bid(&self) -> Option<FixedLevel> {
+        self.bids
+            .last_key_value()
+            .map(|(price, orders)| FixedLevel::new(*price, orders))
+    }
+
+    pub fn best_ask(&self) -> Option<FixedLevel> {
+        self.asks
+            .first_key_value()
+            .map(|(price, orders)| FixedLevel::new(*price, orders))
+    }
+
+    /// The spread in quote increments.
+    pub fn spread(&self) -> Option<u64> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+
+        ask.price.checked_sub(bid.price)
+    }
+
+    /// Iterate over the price levels on one side of the book, best price first.
+    pub fn levels(&self, side: Side) -> Box<dyn Iterator<Item = FixedLevel> + '_> {
+        let levels = self
+            .halfbook(side)
+            .iter()
+            .map(|(price, orders)| FixedLevel::new(*price, orders));
+
+        match side {
+            Side::Buy => Box::new(levels.rev()),
+            Side::Sell => Box::new(levels),
+        }
+    }
+
+    pub fn top_levels(&self, side: Side, n: usize) -> Vec<FixedLevel> {
+        self.levels(side).take(n).collect()
+    }
+
+    /// Convert a level back to decimals.
+    pub fn to_level(&self, level: FixedLevel) -> Result<Level, Error> {
+        Ok(Level {
+            price: self.price_scale.to_decimal(level.price)?,
+            size: self.size_scale.to_decimal(level.size)?,
+            order_count: level.order_count,
+        })
+    }
+
+    /// Iterate over the orders queued at a price level in time priority.
+    pub fn orders_at(&self, side: Side, price: u64) -> impl Iterator<Item = &Order<u64>> {
+        self.halfbook(side)
+            .get(&price)
+            .into_iter()
+            .flat_map(|orders| orders.iter())
+    }
+
+    pub fn order(&self, order_id: Uuid) -> Option<(Side, u64, &Order<u64>)> {
+        let (side, price, handle) = *self.index.get(&order_id)?;
+        let order = self.halfbook(side).get(&price)?.get(handle)?;
+
+        Some((side, price, order))
+    }
+
+    pub fn order_count(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn to_compact(&self) -> Result<CompactOrderBook, Error> {
+        let side = |halfbook: &BTreeMap<u64, Orders<u64>>| {
+            halfbook
+                .iter()
+                .map(|(price, orders)| {
+                    let orders = orders
+                        .iter()
+                        .map(|order| {
+                            let size = self.size_scale.to_decimal(order.size)?;
+
+                            Ok(CompactOrder((order.id, size.into())))
+                        })
+                        .collect::<Result<_, Error>>()?;
+
+                    Ok((
+                        self.price_scale.to_decimal(*price)?.into(),
+                        CompactOrders(orders),
+                    ))
+                })
+                .collect::<Result<Vec<_>, Error>>()
+        };
+
+        Ok(CompactOrderBook {
+            product: self.product.clone(),
+            asks: side(&self.asks)?,
+            bids: side(&self.bids)?,
+            sequence: self.sequence,
+            updated_at: self.updated_at,
+        })
+    }
+
+    pub fn to_order_book(&self) -> Result<OrderBook, Error> {
+        OrderBook::try_from(self.to_compact()?)
+    }
+}
+
+impl LevelStorage for FixedOrderBook {
+    type Price = u64;
+    type Size = u64;
+
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    fn advance(&mut self, time: OffsetDateTime) {
+        self.sequence += 1;
+        self.updated_at = time;
+    }
+
+    fn index(&self) -> &HashMap<Uuid, (Side, u64, Handle)> {
+        &self.index
+    }
+
+    fn index_mut(&mut self) -> &mut HashMap<Uuid, (Side, u64, Handle)> {
+        &mut self.index
+    }
+
+    fn orders_mut(&mut self, side: Side, price: u64) -> Option<&mut Orders<u64>> {
+        self.halfbook_mut(side).get_mut(&price)
+    }
+
+    fn insert(&mut self, side: Side, price: u64, order: Order<u64>) -> Result<(), Error> {
+        let Entry::Vacant(entry) = self.index.entry(order.id) else {
+            return Err(Error::OrderAlreadyExists);
+        };
+        let halfbook = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+
+        entry.insert((
+            side,
+            price,
+            halfbook.entry(price).or_default().insert(order)?,
+        ));
+
+        Ok(())
+    }
+
+    fn remove_level(&mut self, side: Side, price: u64) {
+        self.halfbook_mut(side).remove(&price);
+    }
+}
+
+impl TryFrom<CompactOrderBook> for FixedOrderBook {
+    type Error = Error;
+
+    fn try_from(compact_book: CompactOrderBook) -> Result<Self, Self::Error> {
+        Self::from_order_book(&OrderBook::try_from(compact_book)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        exchange::websocket::channels::level_three::{Message as LevelThreeMessage, Parser},
+        test_util::make_order_book_with_orders,
+    };
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+    use rust_decimal::Decimal;
+    use time::format_description::well_known::Rfc3339;
+
+    /// Write a message as a level3 frame in the default schema.
+    fn to_frame(message: &LevelThreeMessage) -> Vec<u8> {
+        let time = message.time().format(&Rfc3339).unwrap();
+        let side = |side: &Side| match side {
+            Side::Buy => "buy".to_string(),
+            Side::Sell => "sell".to_string(),
+        };
+        let fields = match message {
+            LevelThreeMessage::Open {
+                product_id,
+                sequence,
+                order_id,
+                side: order_side,
+                price,
+                size,
+                ..
+            } => vec![
+                "open".to_string(),
+                product_id.to_string(),
+                sequence.to_string(),
+                order_id.to_string(),
+                side(order_side),
+                price.to_string(),
+                size.to_string(),
+                time,
+            ],
+            LevelThreeMessage::Change {
+                product_id,
+                sequence,
+                order_id,
+                price,
+                size,
+                ..
+            } => vec![
+                "change".to_string(),
+                product_id.to_string(),
+                sequence.to_string(),
+                order_id.to_string(),
+                price.to_string(),
+                size.to_string(),
+                time,
+            ],
+            LevelThreeMessage::Match {
+                product_id,
+                sequence,
+                maker_order_id,
+                taker_order_id,
+                price,
+                size,
+                ..
+            } => vec![
+                "match".to_string(),
+                product_id.to_string(),
+                sequence.to_string(),
+                maker_order_id.to_string(),
+                taker_order_id.to_string(),
+                price.to_string(),
+                size.to_string(),
+                time,
+            ],
+            LevelThreeMessage::Noop {
+                product_id,
+                sequence,
+                ..
+            } => vec![
+                "noop".to_string(),
+                product_id.to_string(),
+                sequence.to_string(),
+                time,
+            ],
+            LevelThreeMessage::Done {
+                product_id,
+                sequence,
+                order_id,
+                ..
+            } => vec![
+                "done".to_string(),
+                product_id.to_string(),
+                sequence.to_string(),
+                order_id.to_string(),
+                time,
+            ],
+        };
+
+        serde_json::to_vec(&fields).unwrap()
+    }
+
+    fn random_message(
+        rng: &mut StdRng,
+        sequence: u64,
+        ids: &[Uuid],
+        order_book: &OrderBook,
+    ) -> LevelThreeMessage {
+        let product_id = "BTC-USD".into();
+        let time = OffsetDateTime::now_utc();
+        let price = Decimal::new(rng.random_range(9900..10100), 2);
+        let size = Decimal::new(rng.random_range(1..1000), 3);
+        let order_id = match ids.is_empty() || rng.random_bool(0.3) {
+            true => Uuid::new_v4(),
+            false => ids[rng.random_range(0..ids.len())],
+        };
+
+        match rng.random_range(0..4) {
+            0 => LevelThreeMessage::Open {
+                product_id,
+                sequence,
+                order_id,
+                side: match rng.random_bool(0.5) {
+                    true => Side::Buy,
+                    false => Side::Sell,
+                },
+                price,
+                size,
+                time,
+            },
+            1 => LevelThreeMessage::Change {
+                product_id,
+                sequence,
+                order_id,
+                price,
+                size,
+                time,
+            },
+            2 => {
+                // Fill at most the maker's remaining size at its own price.
+                let (price, size) = match order_book.order(order_id) {
+                    Some((_, price, order)) => (price, size.min(order.size)),
+                    None => (price, size),
+                };
+
+                LevelThreeMessage::Match {
+                    product_id,
+                    sequence,
+                    maker_order_id: order_id,
+                    taker_order_id: Uuid::new_v4(),
+                    price,
+                    size,
+                    time,
+                }
+            }
+            _ => LevelThreeMessage::Done {
+                product_id,
+                sequence,
+                order_id,
+                time,
+            },
+        }
+    }
+
+    #[test]
+    fn matches_order_book_on_random_messages() {
+        let mut rng = StdRng::seed_from_u64(20);
+        let mut order_book = make_order_book_with_orders(1000, vec![], vec![]);
+
+        order_book.product.base_increment = Decimal::new(1, 3);
+
+        let mut fixed = FixedOrderBook::from_order_book(&order_book).unwrap();
+        let parser = Parser::default();
+        let mut ids = vec![];
+        let mut errors = 0;
+
+        for sequence in 1001..6001 {
+            let message = random_message(&mut rng, sequence, &ids, &order_book);
+
+            if let LevelThreeMessage::Open { order_id, .. } = message {
+                ids.push(order_id);
+            }
+
+            let frame = to_frame(&message);
+            let raw = parser.parse_raw(&frame).unwrap().unwrap();
+
+            // Both books apply the same steps before failing, so they still
+            // agree after an error.
+            match (order_book.update_with(&message), fixed.update_with(&raw)) {
+                (Ok(_), Ok(())) => {}
+                (Err(expected), Err(actual)) => {
+                    assert_eq!(expected.to_string(), actual.to_string());
+                    errors += 1;
+                }
+                (expected, actual) => panic!("{expected:?} != {actual:?} @ {message:?}"),
+            }
+
+            assert_eq!(fixed.sequence(), order_book.sequence());
+
+            for side in [Side::Buy, Side::Sell] {
+                let levels = fixed
+                    .levels(side)
+                    .map(|level| fixed.to_level(level).unwrap())
+                    .collect::<Vec<_>>();
+
+                assert_eq!(order_book.levels(side).collect::<Vec<_>>(), levels);
+            }
+        }
+
+        assert!(errors > 0);
+        assert_eq!(
+            fixed.to_compact().unwrap().to_bytes().unwrap(),
+            order_book.to_compact().to_bytes().unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_prices_off_the_increment() {
+        let order_book = make_order_book_with_orders(
+            1000,
+            vec![(Decimal::new(99001, 3), Uuid::new_v4(), Decimal::ONE)],
+            vec![],
+        );
+
+        assert!(matches!(
+            FixedOrderBook::from_order_book(&order_book),
+            Err(Error::Math { .. })
+        ));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{OrderBook, exchange::common::Error, exchange::websocket::channels::level_three::Side};
+use rust_decimal::Decimal;
+
+/// The amount of a simulated market order, either in the base currency
+/// (`Size`) or in the quote currency (`Funds`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarketOrder {
+    Size(Decimal),
+    Funds(Decimal),
+}
+
+/// The estimated outcome of a market order walking the book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketImpact {
+    pub side: Side,
+    pub filled_size: Decimal,
+    pub filled_funds: Decimal,
+    pub average_price: Option<Decimal>,
+    pub worst_price: Option<Decimal>,
+    pub slippage_bps: Option<Decimal>,
+    pub levels_consumed: usize,
+    pub unfilled: MarketOrder,
+    /// Funds left over by a funds order that filled as far as the book allows,
+    /// too little to buy one base increment at the next price. They aren't
+    /// counted as unfilled.
+    pub residual_funds: Decimal,
+}
+
+impl MarketImpact {
+    pub fn is_fully_filled(&self) -> bool {
+        match self.unfilled {
+            MarketOrder::Size(size) => size.is_zero(),
+            MarketOrder::Funds(funds) => funds.is_zero(),
+        }
+    }
+}
+
+impl OrderBook {
+    /// Simulate a market order against the current book without modifying it.
+    ///
+    /// A buy walks the asks from the lowest price up and a sell walks the bids
+    /// from the highest price down. Fill sizes are rounded down to the product's
+    /// `base_increment`. Slippage is measured in basis points against the mid
+    /// price, where a positive value is a cost to the taker.
+    pub fn simulate_market_order(
+        &self,
+        side: Side,
+        order: MarketOrder,
+    ) -> Result<MarketImpact, Error> {
+        let increment = self.product.base_increment;
+        let levels = match side {
+            Side::Buy => self.levels(Side::Sell),
+            Side::Sell => self.levels(Side::Buy),
+        };
+        let mut remaining = match order {
+            MarketOrder::Size(size) => round_down(size, increment)?,
+            MarketOrder::Funds(funds) => funds,
+        }
+        .max(Decimal::ZERO);
+        let mut filled_size = Decimal::ZERO;
+        let mut filled_funds = Decimal::ZERO;
+        let mut worst_price = None;
+        let mut levels_consumed = 0;
+        let mut residual = Decimal::ZERO;
+
+        for level in levels {
+            if remaining.is_zero() {
+                break;
+            }
+
+            let size = match order {
+                MarketOrder::Size(_) => remaining.min(level.size),
+                MarketOrder::Funds(_) => {
+                    let affordable = remaining
+                        .checked_div(level.price)
+                        .ok_or_else(|| Error::math("funds division overflow", None))?;
+
+                    round_down(affordable, increment)?.min(level.size)
+                }
+            };
+
+            // The remaining funds can't buy a single increment at this price.
+            if size.is_zero() {
+                residual = remaining;
+                break;
+            }
+
+            let funds = size
+                .checked_mul(level.price)
+                .ok_or_else(|| Error::math("fill funds overflow", None))?;
+
+            filled_size = filled_size
+                .checked_add(size)
+                .ok_or_else(|| Error::math("filled size overflow", None))?;
+            filled_funds = filled_funds
+                .checked_add(funds)
+                .ok_or_else(|| Error::math("filled funds overflow", None))?;
+            remaining = match order {
+                MarketOrder::Size(_) => remaining.checked_sub(size),
+                MarketOrder::Funds(_) => remaining.checked_sub(funds),
+            }
+            .ok_or_else(|| Error::math("fill exceeds remaining amount", None))?;
+            worst_price = Some(level.price);
+            levels_consumed += 1;
+
+            // Whatever is left couldn't take the rest of this level, so it
+            // can't reach past it to a worse price either.
+            if size < level.size {
+                residual = remaining;
+                break;
+            }
+        }
+
+        let average_price = filled_funds.checked_div(filled_size);
+        let slippage_bps = match (average_price, self.mid()) {
+            (Some(average_price), Some(mid)) if !mid.is_zero() => {
+                let difference = match side {
+                    Side::Buy => average_price - mid,
+                    Side::Sell => mid - average_price,
+                };
+
+                difference
+                    .checked_mul(Decimal::from(10_000))
+                    .and_then(|difference| difference.checked_div(mid))
+            }
+            _ => None,
+        };
+
+        Ok(MarketImpact {
+            side,
+            filled_size,
+            filled_funds,
+            average_price,
+            worst_price,
+            slippage_bps,
+            levels_consumed,
+            // A size order only stops inside a level once nothing remains.
+            unfilled: match order {
+                MarketOrder::Size(_) => MarketOrder::Size(remaining),
+                MarketOrder::Funds(_) => MarketOrder::Funds(remaining - residual),
+            },
+            residual_funds: residual,
+        })
+    }
+}
+
+/// Round `value` down to a whole multiple of `increment`.
+pub(crate) fn round_down(value: Decimal, increment: Decimal) -> Result<Decimal, Error> {
+    if increment.is_zero() {
+        return Ok(value);
+    }
+
+    value
+        .checked_div(increment)
+        .map(|steps| steps.floor())
+        .and_then(|steps| steps.checked_mul(increment))
+        .map(|value| value.normalize())
+        .ok_or_else(|| Error::math("increment rounding overflow", None))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::make_order_book_with_orders;
+    use uuid::Uuid;
+
+    fn make_book() -> OrderBook {
+        make_order_book_with_orders(
+            1000,
+            vec![
+                (Decimal::new(9900, 2), Uuid::new_v4(), Decimal::ONE), // 99.00
+                (Decimal::new(9800, 2), Uuid::new_v4(), Decimal::TWO), // 98.00
+            ],
+            vec![
+                (Decimal::new(10100, 2), Uuid::new_v4(), Decimal::ONE), // 101.00
+                (Decimal::new(10200, 2), Uuid::new_v4(), Decimal::TWO), // 102.00
+            ],
+        )
+    }
+
+    #[test]
+    fn buy_by_size_walks_asks() {
+        let book = make_book();
+        let impact = book
+            .simulate_market_order(Side::Buy, MarketOrder::Size(Decimal::new(2, 0)))
+            .unwrap();
+
+        assert_eq!(impact.filled_size, Decimal::new(2, 0));
+        assert_eq!(impact.filled_funds, Decimal::new(20300, 2)); // 101 + 102
+        assert_eq!(impact.average_price, Some(Decimal::new(10150, 2)));
+        assert_eq!(impact.worst_price, Some(Decimal::new(10200, 2)));
+        assert_eq!(impact.slippage_bps, Some(Decimal::from(150)));
+        assert_eq!(impact.levels_consumed, 2);
+        assert!(impact.is_fully_filled());
+    }
+
+    #[test]
+    fn sell_by_size_reports_unfilled_remainder() {
+        let book = make_book();
+        let impact = book
+            .simulate_market_order(Side::Sell, MarketOrder::Size(Decimal::new(5, 0)))
+            .unwrap();
+
+        assert_eq!(impact.filled_size, Decimal::new(3, 0));
+        assert_eq!(impact.worst_price, Some(Decimal::new(9800, 2)));
+        assert_eq!(impact.unfilled, MarketOrder::Size(Decimal::TWO));
+        assert!(!impact.is_fully_filled());
+    }
+
+    #[test]
+    fn buy_by_funds_rounds_to_base_increment() {
+        let book = make_book();
+        let impact = book
+            .simulate_market_order(Side::Buy, MarketOrder::Funds(Decimal::new(5050, 2)))
+            .unwrap();
+
+        // 50.50 / 101.00 = 0.5 BTC
+        assert_eq!(impact.filled_size, Decimal::new(5, 1));
+        assert_eq!(impact.levels_consumed, 1);
+        assert!(impact.is_fully_filled());
+
+        let impact = book
+            .simulate_market_order(Side::Buy, MarketOrder::Funds(Decimal::new(100, 0)))
+            .unwrap();
+
+        // 100 / 101 = 0.99009900990... => 0.99009900 BTC
+        assert_eq!(impact.filled_size, Decimal::new(99009900, 8));
+        assert!(impact.filled_funds <= Decimal::new(100, 0));
+        assert!(impact.residual_funds < Decimal::new(101, 8));
+        assert!(impact.is_fully_filled());
+    }
+
+    #[test]
+    fn sell_by_funds_stops_at_a_partly_filled_level() {
+        let mut book = make_order_book_with_orders(
+            1000,
+            vec![
+                (Decimal::from(99), Uuid::new_v4(), Decimal::TWO),
+                (Decimal::from(98), Uuid::new_v4(), Decimal::TWO),
+            ],
+            vec![],
+        );
+
+        book.product.base_increment = Decimal::ONE;
+
+        let impact = book
+            .simulate_market_order(Side::Sell, MarketOrder::Funds(Decimal::new(1975, 1)))
+            .unwrap();
+
+        // 197.5 / 99 rounds down to 1, which leaves the 99 level partly filled.
+        assert_eq!(impact.filled_size, Decimal::ONE);
+        assert_eq!(impact.worst_price, Some(Decimal::from(99)));
+        assert_eq!(impact.levels_consumed, 1);
+        assert_eq!(impact.unfilled, MarketOrder::Funds(Decimal::ZERO));
+        assert_eq!(impact.residual_funds, Decimal::new(985, 1));
+        assert!(impact.is_fully_filled());
+    }
+
+    #[test]
+    fn buy_by_funds_beyond_the_book_is_unfilled() {
+        let book = make_book();
+        let impact = book
+            .simulate_market_order(Side::Buy, MarketOrder::Funds(Decimal::from(1000)))
+            .unwrap();
+
+        // 101 + 2 * 102 = 305 buys every ask.
+        assert_eq!(impact.filled_size, Decimal::from(3));
+        assert_eq!(impact.unfilled, MarketOrder::Funds(Decimal::from(695)));
+        assert!(impact.residual_funds.is_zero());
+        assert!(!impact.is_fully_filled());
+    }
+
+    #[test]
+    fn empty_side_fills_nothing() {
+        let book = make_order_book_with_orders(1000, vec![], vec![]);
+        let impact = book
+            .simulate_market_order(Side::Buy, MarketOrder::Size(Decimal::ONE))
+            .unwrap();
+
+        assert!(impact.filled_size.is_zero());
+        assert!(impact.average_price.is_none());
+        assert_eq!(impact.levels_consumed, 0);
+    }
+}
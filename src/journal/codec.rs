@@ -0,0 +1,230 @@
+use crate::{
+    codec::{Decoder, Errors, put_decimal, put_signed, put_varint},
+    exchange::{
+        common::Error,
+        websocket::channels::level_three::{Message as LevelThreeMessage, Side},
+    },
+};
+use time::OffsetDateTime;
+
+const OPEN: u8 = 0;
+const CHANGE: u8 = 1;
+const MATCH: u8 = 2;
+const NOOP: u8 = 3;
+const DONE: u8 = 4;
+
+/// How the journal describes its decoding errors.
+pub(super) const ERRORS: Errors = Errors {
+    truncated: "truncated journal record",
+    varint: "journal varint overflow",
+    length: "journal length",
+    sequence: "journal sequence",
+    timestamp: "journal timestamp",
+    decimal: "journal decimal",
+};
+
+fn put_header(
+    buffer: &mut Vec<u8>,
+    tag: u8,
+    product_id: &str,
+    sequence: u64,
+    time: &OffsetDateTime,
+) {
+    buffer.push(tag);
+    put_varint(buffer, product_id.len() as u128);
+    buffer.extend_from_slice(product_id.as_bytes());
+    put_varint(buffer, sequence as u128);
+    put_signed(buffer, time.unix_timestamp_nanos());
+}
+
+/// Append the binary encoding of a message to `buffer`.
+pub(super) fn encode(buffer: &mut Vec<u8>, message: &LevelThreeMessage) {
+    match message {
+        LevelThreeMessage::Open {
+            product_id,
+            sequence,
+            order_id,
+            side,
+            price,
+            size,
+            time,
+        } => {
+            put_header(buffer, OPEN, product_id, *sequence, time);
+            buffer.extend_from_slice(order_id.as_bytes());
+            buffer.push(match side {
+                Side::Buy => 0,
+                Side::Sell => 1,
+            });
+            put_decimal(buffer, price);
+            put_decimal(buffer, size);
+        }
+        LevelThreeMessage::Change {
+            product_id,
+            sequence,
+            order_id,
+            price,
+            size,
+            time,
+        } => {
+            put_header(buffer, CHANGE, product_id, *sequence, time);
+            buffer.extend_from_slice(order_id.as_bytes());
+            put_decimal(buffer, price);
+            put_decimal(buffer, size);
+        }
+        LevelThreeMessage::Match {
+            product_id,
+            sequence,
+            maker_order_id,
+            taker_order_id,
+            price,
+            size,
+            time,
+        } => {
+            put_header(buffer, MATCH, product_id, *sequence, time);
+            buffer.extend_from_slice(maker_order_id.as_bytes());
+            buffer.extend_from_slice(taker_order_id.as_bytes());
+            put_decimal(buffer, price);
+            put_decimal(buffer, size);
+        }
+        LevelThreeMessage::Noop {
+            product_id,
+            sequence,
+            time,
+        } => put_header(buffer, NOOP, product_id, *sequence, time),
+        LevelThreeMessage::Done {
+            product_id,
+            sequence,
+            order_id,
+            time,
+        } => {
+            put_header(buffer, DONE, product_id, *sequence, time);
+            buffer.extend_from_slice(order_id.as_bytes());
+        }
+    }
+}
+
+fn decode_side(decoder: &mut Decoder<'_>) -> Result<Side, Error> {
+    match decoder.byte()? {
+        0 => Ok(Side::Buy),
+        1 => Ok(Side::Sell),
+        _ => Err(Error::invalid("journal side")),
+    }
+}
+
+/// Decode the next message.
+pub(super) fn decode(decoder: &mut Decoder<'_>) -> Result<LevelThreeMessage, Error> {
+    let tag = decoder.byte()?;
+    let len = decoder.length()?;
+    let product_id = std::str::from_utf8(decoder.take(len)?)
+        .map_err(|_| Error::invalid("journal product id"))?
+        .into();
+    let sequence = decoder.sequence()?;
+    let time = decoder.time()?;
+
+    let message = match tag {
+        OPEN => LevelThreeMessage::Open {
+            product_id,
+            sequence,
+            order_id: decoder.uuid()?,
+            side: decode_side(decoder)?,
+            price: decoder.decimal()?,
+            size: decoder.decimal()?,
+            time,
+        },
+        CHANGE => LevelThreeMessage::Change {
+            product_id,
+            sequence,
+            order_id: decoder.uuid()?,
+            price: decoder.decimal()?,
+            size: decoder.decimal()?,
+            time,
+        },
+        MATCH => LevelThreeMessage::Match {
+            product_id,
+            sequence,
+            maker_order_id: decoder.uuid()?,
+            taker_order_id: decoder.uuid()?,
+            price: decoder.decimal()?,
+            size: decoder.decimal()?,
+            time,
+        },
+        NOOP => LevelThreeMessage::Noop {
+            product_id,
+            sequence,
+            time,
+        },
+        DONE => LevelThreeMessage::Done {
+            product_id,
+            sequence,
+            order_id: decoder.uuid()?,
+            time,
+        },
+        _ => return Err(Error::invalid("journal message tag")),
+    };
+
+    Ok(message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    #[test]
+    fn messages_round_trip() {
+        let time = OffsetDateTime::from_unix_timestamp_nanos(1_733_541_926_853_178_000).unwrap();
+        let messages = [
+            LevelThreeMessage::Open {
+                product_id: "BTC-USD".into(),
+                sequence: 1,
+                order_id: Uuid::new_v4(),
+                side: Side::Sell,
+                price: Decimal::new(9_999_999, 2),
+                size: Decimal::new(123_456_789, 8),
+                time,
+            },
+            LevelThreeMessage::Change {
+                product_id: "BTC-USD".into(),
+                sequence: 2,
+                order_id: Uuid::new_v4(),
+                price: Decimal::new(-5, 1),
+                size: Decimal::MAX,
+                time,
+            },
+            LevelThreeMessage::Match {
+                product_id: "KSM-USD".into(),
+                sequence: u64::MAX,
+                maker_order_id: Uuid::new_v4(),
+                taker_order_id: Uuid::new_v4(),
+                price: Decimal::new(4739, 2),
+                size: Decimal::ZERO,
+                time,
+            },
+            LevelThreeMessage::Noop {
+                product_id: "BTC-USD".into(),
+                sequence: 4,
+                time,
+            },
+            LevelThreeMessage::Done {
+                product_id: "BTC-USD".into(),
+                sequence: 5,
+                order_id: Uuid::new_v4(),
+                time,
+            },
+        ];
+        let mut buffer = vec![];
+
+        for message in messages.iter() {
+            encode(&mut buffer, message);
+        }
+
+        let mut decoder = Decoder::new(&buffer, &ERRORS);
+
+        for message in messages.iter() {
+            assert_eq!(&decode(&mut decoder).unwrap(), message);
+        }
+
+        assert!(decoder.is_empty());
+    }
+}
@@ -0,0 +1,56 @@
+use super::MAX_FRAME_LEN;
+use crate::exchange::common::Error;
+
+/// Compress a frame's messages as an LZ4 block.
+pub(crate) fn compress(input: &[u8]) -> Vec<u8> {
+    lz4_flex::block::compress(input)
+}
+
+/// Reverse `compress`, failing unless the output is exactly `len` bytes. The
+/// length comes from the frame, so it is capped at `MAX_FRAME_LEN` before
+/// anything is allocated.
+pub(crate) fn decompress(input: &[u8], len: usize) -> Result<Vec<u8>, Error> {
+    if len > MAX_FRAME_LEN as usize {
+        return Err(Error::invalid("journal decompressed length"));
+    }
+
+    let output = lz4_flex::block::decompress(input, len)
+        .map_err(|error| Error::dependency("Lz4 error", Box::new(error)))?;
+
+    match output.len() == len {
+        true => Ok(output),
+        false => Err(Error::invalid("journal decompressed length")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_shrinks_repetitive_input() {
+        let input =
+            b"BTC-USD 1234.56 BTC-USD 1234.57 BTC-USD 1234.58 aaaaaaaaaaaaaaaaaaaaaaaa".repeat(20);
+        let compressed = compress(&input);
+
+        assert!(compressed.len() < input.len() / 4);
+        assert_eq!(decompress(&compressed, input.len()).unwrap(), input);
+    }
+
+    #[test]
+    fn round_trips_short_and_empty_input() {
+        for input in [&b""[..], b"a", b"abc", b"abcd", b"abcdabcd"] {
+            assert_eq!(decompress(&compress(input), input.len()).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_lengths_and_input() {
+        let compressed = compress(b"abcdabcd");
+
+        assert!(decompress(&compressed, 7).is_err());
+        assert!(decompress(&compressed, 9).is_err());
+        assert!(decompress(&compressed, usize::MAX).is_err());
+        assert!(decompress(&[0xf0, 1, 2], 64).is_err());
+    }
+}
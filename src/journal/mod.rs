@@ -0,0 +1,930 @@
+//! An append-only binary journal of level-three messages for a single product.
+//!
+//! A journal is a directory of segments named after the first sequence they
+//! hold. Each segment starts with a header naming the product, followed by
+//! frames of `[length: u32][crc32: u32][body]`, where the body holds a batch of
+//! encoded messages, optionally compressed as an LZ4 block. Every segment has a
+//! sidecar index of `(sequence, offset)` pairs written every `index_interval`
+//! bytes, which lets readers seek without scanning whole segments.
+
+mod codec;
+mod compress;
+
+use crate::{
+    codec::{Decoder, put_varint},
+    exchange::{common::Error, websocket::channels::level_three::Message as LevelThreeMessage},
+};
+use smartstring::{LazyCompact, SmartString};
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+use time::{Duration, OffsetDateTime};
+use tracing::{debug, warn};
+
+const MAGIC: &[u8; 8] = b"CBL3JRNL";
+const VERSION: u8 = 1;
+const COMPRESSED: u8 = 1;
+const FRAME_HEADER_LEN: u64 = 8;
+const MAX_FRAME_LEN: u32 = 64 * 1024 * 1024;
+const MAX_BATCH_LEN: usize = 16 * 1024 * 1024;
+const SEGMENT_EXTENSION: &str = "seg";
+const INDEX_EXTENSION: &str = "idx";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JournalConfig {
+    /// Start a new segment once the current one reaches this many bytes.
+    pub segment_size: u64,
+    /// Start a new segment once it spans this much exchange time.
+    pub segment_duration: Option<Duration>,
+    /// Messages per frame. Unflushed messages are lost on a crash.
+    pub batch_size: usize,
+    pub compress: bool,
+    /// Bytes between sparse index entries.
+    pub index_interval: u64,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self {
+            segment_size: 256 * 1024 * 1024,
+            segment_duration: None,
+            batch_size: 64,
+            compress: false,
+            index_interval: 64 * 1024,
+        }
+    }
+}
+
+impl JournalConfig {
+    pub fn with_segment_size(mut self, segment_size: u64) -> Self {
+        self.segment_size = segment_size;
+
+        self
+    }
+
+    pub fn with_segment_duration(mut self, segment_duration: Duration) -> Self {
+        self.segment_duration = Some(segment_duration);
+
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+
+        self
+    }
+
+    pub fn with_compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+
+        self
+    }
+
+    pub fn with_index_interval(mut self, index_interval: u64) -> Self {
+        self.index_interval = index_interval;
+
+        self
+    }
+}
+
+struct Frame {
+    first_sequence: u64,
+    count: usize,
+    data: Vec<u8>,
+}
+
+impl Frame {
+    fn encode(&self, compress: bool) -> Vec<u8> {
+        let mut body = Vec::with_capacity(self.data.len() + 16);
+
+        body.push(if compress { COMPRESSED } else { 0 });
+        put_varint(&mut body, self.first_sequence as u128);
+        put_varint(&mut body, self.count as u128);
+
+        if compress {
+            put_varint(&mut body, self.data.len() as u128);
+            body.extend_from_slice(&compress::compress(&self.data));
+        } else {
+            body.extend_from_slice(&self.data);
+        }
+
+        let mut frame = Vec::with_capacity(body.len() + FRAME_HEADER_LEN as usize);
+
+        frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&crc32fast::hash(&body).to_le_bytes());
+        frame.extend_from_slice(&body);
+        frame
+    }
+
+    fn decode(body: &[u8]) -> Result<Self, Error> {
+        let mut decoder = Decoder::new(body, &codec::ERRORS);
+        let flags = decoder.take(1)?[0];
+        let first_sequence =
+            u64::try_from(decoder.varint()?).map_err(|_| Error::invalid("journal sequence"))?;
+        let count =
+            usize::try_from(decoder.varint()?).map_err(|_| Error::invalid("journal count"))?;
+        let data = match flags & COMPRESSED {
+            0 => decoder.rest().to_vec(),
+            _ => {
+                let len = usize::try_from(decoder.varint()?)
+                    .map_err(|_| Error::invalid("journal length"))?;
+
+                compress::decompress(decoder.rest(), len)?
+            }
+        };
+
+        Ok(Self {
+            first_sequence,
+            count,
+            data,
+        })
+    }
+
+    fn messages(&self) -> Result<Vec<LevelThreeMessage>, Error> {
+        let mut decoder = Decoder::new(&self.data, &codec::ERRORS);
+        let mut messages = Vec::with_capacity(self.count);
+
+        for _ in 0..self.count {
+            messages.push(codec::decode(&mut decoder)?);
+        }
+
+        Ok(messages)
+    }
+}
+
+enum ReadFrame {
+    Frame {
+        frame: Frame,
+        len: u64,
+    },
+    End,
+    /// An incomplete or corrupt frame, as left by a crash mid-write.
+    Torn,
+}
+
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize, Error> {
+    let mut read = 0;
+
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    Ok(read)
+}
+
+fn read_frame(reader: &mut impl Read) -> Result<ReadFrame, Error> {
+    let mut header = [0u8; FRAME_HEADER_LEN as usize];
+
+    match read_full(reader, &mut header)? {
+        0 => return Ok(ReadFrame::End),
+        n if n < header.len() => return Ok(ReadFrame::Torn),
+        _ => {}
+    }
+
+    let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+    if len > MAX_FRAME_LEN {
+        return Ok(ReadFrame::Torn);
+    }
+
+    let mut body = vec![0u8; len as usize];
+
+    if read_full(reader, &mut body)? < body.len() || crc32fast::hash(&body) != crc {
+        return Ok(ReadFrame::Torn);
+    }
+
+    Ok(ReadFrame::Frame {
+        frame: Frame::decode(&body)?,
+        len: FRAME_HEADER_LEN + len as u64,
+    })
+}
+
+fn write_header(writer: &mut impl Write, product_id: &str) -> Result<u64, Error> {
+    let len = u8::try_from(product_id.len()).map_err(|_| Error::invalid("journal product id"))?;
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION, len])?;
+    writer.write_all(product_id.as_bytes())?;
+
+    Ok((MAGIC.len() + 2 + product_id.len()) as u64)
+}
+
+/// Read a segment header, returning `None` if it is incomplete, as left by a
+/// crash while creating the segment.
+fn read_header(reader: &mut impl Read) -> Result<Option<(SmartString<LazyCompact>, u64)>, Error> {
+    let mut prefix = [0u8; 10];
+    let read = read_full(reader, &mut prefix)?;
+    let magic = read.min(MAGIC.len());
+
+    if prefix[..magic] != MAGIC[..magic] || (read > MAGIC.len() && prefix[8] != VERSION) {
+        return Err(Error::invalid("journal segment header"));
+    }
+
+    if read < prefix.len() {
+        return Ok(None);
+    }
+
+    let mut product_id = vec![0u8; prefix[9] as usize];
+
+    if read_full(reader, &mut product_id)? < product_id.len() {
+        return Ok(None);
+    }
+
+    let product_id = String::from_utf8(product_id)
+        .map_err(|_| Error::invalid("journal product id"))?
+        .into();
+
+    Ok(Some((
+        product_id,
+        (prefix.len() + prefix[9] as usize) as u64,
+    )))
+}
+
+fn segment_path(directory: &Path, first_sequence: u64, extension: &str) -> PathBuf {
+    directory.join(format!("{first_sequence:020}.{extension}"))
+}
+
+/// List the segments in a journal directory by first sequence.
+fn segments(directory: &Path) -> Result<Vec<(u64, PathBuf)>, Error> {
+    let mut segments = vec![];
+
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+
+        if path.extension().and_then(|extension| extension.to_str()) != Some(SEGMENT_EXTENSION) {
+            continue;
+        }
+
+        if let Some(first_sequence) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u64>().ok())
+        {
+            segments.push((first_sequence, path));
+        }
+    }
+
+    segments.sort();
+
+    Ok(segments)
+}
+
+fn read_index(path: &Path) -> Result<Vec<(u64, u64)>, Error> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(error.into()),
+    };
+
+    // A torn trailing entry is ignored.
+    Ok(bytes
+        .chunks_exact(16)
+        .map(|entry| {
+            let (sequence, offset) = entry.split_at(8);
+
+            (
+                u64::from_le_bytes(sequence.try_into().unwrap_or_default()),
+                u64::from_le_bytes(offset.try_into().unwrap_or_default()),
+            )
+        })
+        .collect())
+}
+
+struct Segment {
+    file: BufWriter<File>,
+    index: BufWriter<File>,
+    opened_at: OffsetDateTime,
+    len: u64,
+    indexed_at: Option<u64>,
+}
+
+/// Records level-three messages for one product into a journal directory.
+///
+/// Writes are buffered; call `flush` to persist pending messages, and `sync`
+/// to force them to disk.
+pub struct JournalWriter {
+    directory: PathBuf,
+    config: JournalConfig,
+    product_id: Option<SmartString<LazyCompact>>,
+    segment: Option<Segment>,
+    batch: Vec<u8>,
+    batch_first: Option<u64>,
+    batch_count: usize,
+    batch_time: Option<OffsetDateTime>,
+    last_sequence: Option<u64>,
+}
+
+impl JournalWriter {
+    /// Open a journal directory for appending, creating it if needed. A torn
+    /// frame at the end of the last segment is truncated, a last segment left
+    /// without any frames is removed, and appending continues in a new
+    /// segment.
+    pub fn open(directory: impl AsRef<Path>, config: JournalConfig) -> Result<Self, Error> {
+        let directory = directory.as_ref().to_path_buf();
+
+        std::fs::create_dir_all(&directory)?;
+
+        let mut writer = Self {
+            directory,
+            config,
+            product_id: None,
+            segment: None,
+            batch: vec![],
+            batch_first: None,
+            batch_count: 0,
+            batch_time: None,
+            last_sequence: None,
+        };
+
+        let mut segments = segments(&writer.directory)?;
+
+        while let Some((first_sequence, path)) = segments.pop() {
+            if writer.recover(first_sequence, &path)? {
+                break;
+            }
+        }
+
+        Ok(writer)
+    }
+
+    /// Truncate a torn tail off a segment, returning whether it holds any
+    /// messages. A segment without any is removed, so its name can be reused.
+    fn recover(&mut self, first_sequence: u64, path: &Path) -> Result<bool, Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let Some((product_id, mut valid_len)) = read_header(&mut reader)? else {
+            warn!(?path, "Removing journal segment with a torn header");
+            self.remove_segment(first_sequence, path)?;
+
+            return Ok(false);
+        };
+        let mut last_sequence = None;
+
+        loop {
+            match read_frame(&mut reader)? {
+                ReadFrame::Frame { frame, len } => {
+                    last_sequence = frame
+                        .messages()?
+                        .last()
+                        .map(|message| message.sequence())
+                        .or(last_sequence);
+                    valid_len += len;
+                }
+                ReadFrame::End => break,
+                ReadFrame::Torn => {
+                    warn!(?path, valid_len, "Truncating torn journal frame");
+
+                    OpenOptions::new()
+                        .write(true)
+                        .open(path)?
+                        .set_len(valid_len)?;
+
+                    break;
+                }
+            }
+        }
+
+        let Some(last_sequence) = last_sequence else {
+            warn!(?path, "Removing journal segment without messages");
+            self.remove_segment(first_sequence, path)?;
+
+            return Ok(false);
+        };
+
+        // Drop index entries past the valid data.
+        let index_path = segment_path(&self.directory, first_sequence, INDEX_EXTENSION);
+        let entries = read_index(&index_path)?
+            .into_iter()
+            .filter(|(_, offset)| *offset < valid_len)
+            .collect::<Vec<_>>();
+        let mut index = BufWriter::new(File::create(&index_path)?);
+
+        for (sequence, offset) in entries {
+            index.write_all(&sequence.to_le_bytes())?;
+            index.write_all(&offset.to_le_bytes())?;
+        }
+
+        index.flush()?;
+
+        self.product_id = Some(product_id);
+        self.last_sequence = Some(last_sequence);
+
+        Ok(true)
+    }
+
+    fn remove_segment(&self, first_sequence: u64, path: &Path) -> Result<(), Error> {
+        std::fs::remove_file(path)?;
+
+        match std::fs::remove_file(segment_path(
+            &self.directory,
+            first_sequence,
+            INDEX_EXTENSION,
+        )) {
+            Err(error) if error.kind() != ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn last_sequence(&self) -> Option<u64> {
+        self.last_sequence
+    }
+
+    /// Append a message. Messages must be for one product and in increasing
+    /// sequence order; gaps are recorded as they happened.
+    pub fn append(&mut self, message: &LevelThreeMessage) -> Result<(), Error> {
+        match &self.product_id {
+            Some(product_id) if product_id.as_str() != message.product_id() => {
+                return Err(Error::invalid("journal product id"));
+            }
+            Some(_) => {}
+            None => self.product_id = Some(message.product_id().into()),
+        }
+
+        if self
+            .last_sequence
+            .is_some_and(|last_sequence| message.sequence() <= last_sequence)
+        {
+            return Err(Error::OutOfSequence);
+        }
+
+        codec::encode(&mut self.batch, message);
+        self.batch_first.get_or_insert(message.sequence());
+        self.batch_time.get_or_insert(message.time());
+        self.batch_count += 1;
+        self.last_sequence = Some(message.sequence());
+
+        if self.batch_count >= self.config.batch_size || self.batch.len() >= MAX_BATCH_LEN {
+            self.write_batch()?;
+        }
+
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<(), Error> {
+        let (Some(first_sequence), Some(time)) = (self.batch_first.take(), self.batch_time.take())
+        else {
+            return Ok(());
+        };
+        let frame = Frame {
+            first_sequence,
+            count: std::mem::take(&mut self.batch_count),
+            data: std::mem::take(&mut self.batch),
+        }
+        .encode(self.config.compress);
+
+        let roll = self.segment.as_ref().is_some_and(|segment| {
+            segment.len >= self.config.segment_size
+                || self
+                    .config
+                    .segment_duration
+                    .is_some_and(|duration| time - segment.opened_at >= duration)
+        });
+
+        if roll && let Some(mut segment) = self.segment.take() {
+            segment.file.flush()?;
+            segment.index.flush()?;
+        }
+
+        if self.segment.is_none() {
+            self.segment = Some(self.create_segment(first_sequence, time)?);
+        }
+
+        let index_interval = self.config.index_interval;
+        let segment = self.segment.as_mut().ok_or_else(|| Error::Impossible)?;
+
+        if segment
+            .indexed_at
+            .is_none_or(|indexed_at| segment.len - indexed_at >= index_interval)
+        {
+            segment.index.write_all(&first_sequence.to_le_bytes())?;
+            segment.index.write_all(&segment.len.to_le_bytes())?;
+            segment.indexed_at = Some(segment.len);
+        }
+
+        segment.file.write_all(&frame)?;
+        segment.len += frame.len() as u64;
+
+        Ok(())
+    }
+
+    fn create_segment(
+        &self,
+        first_sequence: u64,
+        opened_at: OffsetDateTime,
+    ) -> Result<Segment, Error> {
+        let path = segment_path(&self.directory, first_sequence, SEGMENT_EXTENSION);
+        let product_id = self.product_id.as_deref().unwrap_or_default();
+
+        debug!(?path, "Creating journal segment");
+
+        let mut file = BufWriter::new(
+            OpenOptions::new()
+                .create_new(true)
+                .write(true)
+                .open(&path)?,
+        );
+        let index = BufWriter::new(File::create(segment_path(
+            &self.directory,
+            first_sequence,
+            INDEX_EXTENSION,
+        ))?);
+        let len = write_header(&mut file, product_id)?;
+
+        Ok(Segment {
+            file,
+            index,
+            opened_at,
+            len,
+            indexed_at: None,
+        })
+    }
+
+    /// Write any pending messages and flush the segment buffers.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.write_batch()?;
+
+        if let Some(segment) = self.segment.as_mut() {
+            segment.file.flush()?;
+            segment.index.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Flush and wait for the segment to reach the disk.
+    pub fn sync(&mut self) -> Result<(), Error> {
+        self.flush()?;
+
+        if let Some(segment) = self.segment.as_mut() {
+            segment.file.get_ref().sync_data()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for JournalWriter {
+    fn drop(&mut self) {
+        if let Err(error) = self.flush() {
+            warn!(%error, "Failed to flush journal");
+        }
+    }
+}
+
+/// Iterates over the messages of a journal in sequence order.
+///
+/// A torn header or frame at the end of the last segment ends the iteration,
+/// while corruption anywhere else is reported as an error.
+pub struct JournalReader {
+    directory: PathBuf,
+    segments: Vec<(u64, PathBuf)>,
+    position: usize,
+    file: Option<BufReader<File>>,
+    messages: VecDeque<LevelThreeMessage>,
+    start: Option<u64>,
+    finished: bool,
+}
+
+impl JournalReader {
+    pub fn open(directory: impl AsRef<Path>) -> Result<Self, Error> {
+        let directory = directory.as_ref().to_path_buf();
+        let segments = segments(&directory)?;
+
+        Ok(Self {
+            directory,
+            segments,
+            position: 0,
+            file: None,
+            messages: VecDeque::new(),
+            start: None,
+            finished: false,
+        })
+    }
+
+    /// Position the reader so the next message is the first one at or after
+    /// `sequence`.
+    pub fn seek(&mut self, sequence: u64) -> Result<(), Error> {
+        let position = self
+            .segments
+            .partition_point(|(first_sequence, _)| *first_sequence <= sequence)
+            .saturating_sub(1);
+
+        self.position = position;
+        self.messages.clear();
+        self.start = Some(sequence);
+        self.finished = false;
+        self.file = None;
+
+        let Some((first_sequence, path)) = self.segments.get(position) else {
+            return Ok(());
+        };
+        let mut file = BufReader::new(File::open(path)?);
+        let Some((_, header_len)) = read_header(&mut file)? else {
+            // Left for `next_frame` to report, unless it's the last segment.
+            return Ok(());
+        };
+        let index = read_index(&segment_path(
+            &self.directory,
+            *first_sequence,
+            INDEX_EXTENSION,
+        ))?;
+        let offset = index
+            .iter()
+            .take_while(|(indexed, _)| *indexed <= sequence)
+            .last()
+            .map(|(_, offset)| *offset)
+            .unwrap_or(header_len);
+
+        file.seek(SeekFrom::Start(offset))?;
+        self.file = Some(file);
+
+        Ok(())
+    }
+
+    fn next_frame(&mut self) -> Result<bool, Error> {
+        loop {
+            if self.file.is_none() {
+                let Some((_, path)) = self.segments.get(self.position) else {
+                    return Ok(false);
+                };
+                let mut file = BufReader::new(File::open(path)?);
+
+                if read_header(&mut file)?.is_none() {
+                    return match self.position + 1 < self.segments.len() {
+                        true => Err(Error::invalid("journal segment header")),
+                        false => Ok(false),
+                    };
+                }
+
+                self.file = Some(file);
+            }
+
+            let file = self.file.as_mut().ok_or_else(|| Error::Impossible)?;
+
+            match read_frame(file)? {
+                ReadFrame::Frame { frame, .. } => {
+                    let start = self.start.unwrap_or(0);
+
+                    self.messages.extend(
+                        frame
+                            .messages()?
+                            .into_iter()
+                            .filter(|message| message.sequence() >= start),
+                    );
+
+                    return Ok(true);
+                }
+                ReadFrame::Torn if self.position + 1 < self.segments.len() => {
+                    return Err(Error::invalid("journal frame"));
+                }
+                ReadFrame::Torn => return Ok(false),
+                ReadFrame::End => {
+                    self.position += 1;
+                    self.file = None;
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for JournalReader {
+    type Item = Result<LevelThreeMessage, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.messages.is_empty() && !self.finished {
+            match self.next_frame() {
+                Ok(true) => {}
+                Ok(false) => self.finished = true,
+                Err(error) => {
+                    self.finished = true;
+
+                    return Some(Err(error));
+                }
+            }
+        }
+
+        self.messages.pop_front().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::exchange::websocket::channels::level_three::Side;
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("{name}-{}", Uuid::new_v4()));
+
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn make_message(sequence: u64) -> LevelThreeMessage {
+        let time = OffsetDateTime::UNIX_EPOCH + Duration::seconds(sequence as i64);
+
+        match sequence % 3 {
+            0 => LevelThreeMessage::Open {
+                product_id: "BTC-USD".into(),
+                sequence,
+                order_id: Uuid::new_v4(),
+                side: Side::Buy,
+                price: Decimal::new(9900, 2),
+                size: Decimal::ONE,
+                time,
+            },
+            1 => LevelThreeMessage::Noop {
+                product_id: "BTC-USD".into(),
+                sequence,
+                time,
+            },
+            _ => LevelThreeMessage::Done {
+                product_id: "BTC-USD".into(),
+                sequence,
+                order_id: Uuid::new_v4(),
+                time,
+            },
+        }
+    }
+
+    fn write(directory: &Path, config: JournalConfig, sequences: std::ops::Range<u64>) {
+        let mut writer = JournalWriter::open(directory, config).unwrap();
+
+        for sequence in sequences {
+            writer.append(&make_message(sequence)).unwrap();
+        }
+
+        writer.flush().unwrap();
+    }
+
+    fn read_sequences(reader: JournalReader) -> Vec<u64> {
+        reader.map(|message| message.unwrap().sequence()).collect()
+    }
+
+    #[test]
+    fn reads_back_across_segments_and_seeks() {
+        for compress in [false, true] {
+            let directory = TempDir::new("journal-segments");
+            let config = JournalConfig::default()
+                .with_batch_size(4)
+                .with_segment_size(512)
+                .with_index_interval(128)
+                .with_compression(compress);
+
+            write(&directory.0, config, 1..201);
+
+            assert!(segments(&directory.0).unwrap().len() > 2);
+            assert_eq!(
+                read_sequences(JournalReader::open(&directory.0).unwrap()),
+                (1..201).collect::<Vec<_>>()
+            );
+
+            let mut reader = JournalReader::open(&directory.0).unwrap();
+
+            reader.seek(137).unwrap();
+
+            assert_eq!(read_sequences(reader), (137..201).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn segments_roll_by_time() {
+        let directory = TempDir::new("journal-time");
+        let config = JournalConfig::default()
+            .with_batch_size(1)
+            .with_segment_duration(Duration::seconds(10));
+
+        write(&directory.0, config, 1..31);
+
+        assert_eq!(segments(&directory.0).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn torn_tail_is_skipped_and_truncated() {
+        let directory = TempDir::new("journal-torn");
+        let config = JournalConfig::default().with_batch_size(1);
+
+        write(&directory.0, config, 1..11);
+
+        // Simulate a crash halfway through writing another frame.
+        let (_, path) = segments(&directory.0).unwrap().pop().unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+
+        file.write_all(&[40, 0, 0, 0, 1, 2, 3, 4, 5]).unwrap();
+        drop(file);
+
+        assert_eq!(
+            read_sequences(JournalReader::open(&directory.0).unwrap()),
+            (1..11).collect::<Vec<_>>()
+        );
+
+        // Reopening truncates the torn frame and continues after it.
+        let mut writer = JournalWriter::open(&directory.0, config).unwrap();
+
+        assert_eq!(writer.last_sequence(), Some(10));
+        assert!(matches!(
+            writer.append(&make_message(10)),
+            Err(Error::OutOfSequence)
+        ));
+
+        writer.append(&make_message(11)).unwrap();
+        drop(writer);
+
+        assert_eq!(
+            read_sequences(JournalReader::open(&directory.0).unwrap()),
+            (1..12).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn torn_segment_header_is_removed() {
+        let directory = TempDir::new("journal-torn-header");
+        let config = JournalConfig::default().with_batch_size(1);
+
+        write(&directory.0, config, 1..11);
+
+        // Simulate a crash while creating the next segment.
+        let path = segment_path(&directory.0, 11, SEGMENT_EXTENSION);
+
+        std::fs::write(&path, &MAGIC[..5]).unwrap();
+        std::fs::write(segment_path(&directory.0, 11, INDEX_EXTENSION), []).unwrap();
+
+        assert_eq!(
+            read_sequences(JournalReader::open(&directory.0).unwrap()),
+            (1..11).collect::<Vec<_>>()
+        );
+
+        let mut writer = JournalWriter::open(&directory.0, config).unwrap();
+
+        assert_eq!(writer.last_sequence(), Some(10));
+
+        writer.append(&make_message(11)).unwrap();
+        drop(writer);
+
+        assert_eq!(segments(&directory.0).unwrap().len(), 2);
+        assert_eq!(
+            read_sequences(JournalReader::open(&directory.0).unwrap()),
+            (1..12).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn empty_tail_segment_resumes_from_the_previous_one() {
+        let directory = TempDir::new("journal-empty-tail");
+        let config = JournalConfig::default().with_batch_size(1);
+
+        write(&directory.0, config, 1..11);
+
+        // Simulate a crash after a new segment's header reached the disk, but
+        // before its first frame did.
+        let mut file = File::create(segment_path(&directory.0, 11, SEGMENT_EXTENSION)).unwrap();
+
+        write_header(&mut file, "BTC-USD").unwrap();
+        file.write_all(&[40, 0, 0]).unwrap();
+        drop(file);
+
+        let mut writer = JournalWriter::open(&directory.0, config).unwrap();
+
+        assert_eq!(writer.last_sequence(), Some(10));
+        assert!(matches!(
+            writer.append(&make_message(10)),
+            Err(Error::OutOfSequence)
+        ));
+
+        writer.append(&make_message(11)).unwrap();
+        drop(writer);
+
+        assert_eq!(
+            read_sequences(JournalReader::open(&directory.0).unwrap()),
+            (1..12).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn long_product_ids_are_rejected() {
+        let product_id = "X".repeat(256);
+
+        assert!(matches!(
+            write_header(&mut vec![], &product_id),
+            Err(Error::Invalid("journal product id"))
+        ));
+    }
+}
@@ -0,0 +1,370 @@
+use crate::{
+    Level, OrderBook,
+    exchange::{
+        common::Error,
+        rest::products::Product,
+        websocket::channels::{level_three::Side, level_two::Message as LevelTwoMessage},
+    },
+};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+use tracing::debug;
+
+/// An order book of aggregate sizes by price, maintained from the `level2`
+/// channels. Levels have an `order_count` of zero, since the feed doesn't
+/// report orders.
+///
+/// The level2 feed isn't sequenced, so the book's sequence counts the
+/// messages it has applied, and an update timed before the last exchange
+/// time seen is out of sequence. Updates are rejected until a snapshot has
+/// been applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct L2OrderBook {
+    pub product: Product,
+    asks: BTreeMap<Decimal, Decimal>,
+    bids: BTreeMap<Decimal, Decimal>,
+    sequence: u64,
+    updated_at: Option<OffsetDateTime>,
+    synced: bool,
+}
+
+impl L2OrderBook {
+    /// Create an empty book that waits for a snapshot.
+    pub fn new(product: Product) -> Self {
+        Self {
+            product,
+            asks: BTreeMap::new(),
+            bids: BTreeMap::new(),
+            sequence: 0,
+            updated_at: None,
+            synced: false,
+        }
+    }
+
+    /// Aggregate a level3 book. The aggregate hasn't applied any level2
+    /// messages, so its sequence starts at zero.
+    pub fn from_order_book(order_book: &OrderBook) -> Self {
+        let side = |side| {
+            order_book
+                .levels(side)
+                .map(|level| (level.price, level.size))
+                .collect()
+        };
+
+        Self {
+            product: order_book.product.clone(),
+            asks: side(Side::Sell),
+            bids: side(Side::Buy),
+            sequence: 0,
+            updated_at: Some(order_book.updated_at()),
+            synced: true,
+        }
+    }
+
+    /// Whether a snapshot has been applied.
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    fn halfbook_mut(&mut self, side: Side) -> &mut BTreeMap<Decimal, Decimal> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    fn halfbook(&self, side: Side) -> &BTreeMap<Decimal, Decimal> {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
+    }
+
+    /// Apply a snapshot, replacing the book, or an update.
+    pub fn update_with(&mut self, message: &LevelTwoMessage) -> Result<(), Error> {
+        if message.product_id() != self.product.id {
+            return Err(Error::invalid("level2 product id"));
+        }
+
+        match message {
+            LevelTwoMessage::Snapshot {
+                bids, asks, time, ..
+            } => {
+                self.bids = bids
+                    .iter()
+                    .copied()
+                    .filter(|(_, size)| !size.is_zero())
+                    .collect();
+                self.asks = asks
+                    .iter()
+                    .copied()
+                    .filter(|(_, size)| !size.is_zero())
+                    .collect();
+                self.updated_at = *time;
+                self.synced = true;
+
+                debug!(
+                    bids = self.bids.len(),
+                    asks = self.asks.len(),
+                    "Applied level2 snapshot"
+                );
+            }
+            LevelTwoMessage::Update { changes, time, .. } => {
+                if !self.synced {
+                    return Err(Error::unavailable("level2 snapshot"));
+                }
+
+                if self.updated_at.is_some_and(|updated_at| *time < updated_at) {
+                    return Err(Error::OutOfSequence);
+                }
+
+                for change in changes {
+                    let halfbook = self.halfbook_mut(change.side);
+
+                    match change.size.is_zero() {
+                        true => halfbook.remove(&change.price),
+                        false => halfbook.insert(change.price, change.size),
+                    };
+                }
+
+                self.updated_at = Some(*time);
+            }
+        }
+
+        self.sequence += 1;
+
+        Ok(())
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Get the last exchange time seen, if any message carried one.
+    pub fn updated_at(&self) -> Option<OffsetDateTime> {
+        self.updated_at
+    }
+
+    /// Get the highest bid level, if any bids are resting.
+    pub fn best_bid(&self) -> Option<Level> {
+        self.levels(Side::Buy).next()
+    }
+
+    /// Get the lowest ask level, if any asks are resting.
+    pub fn best_ask(&self) -> Option<Level> {
+        self.levels(Side::Sell).next()
+    }
+
+    /// Get the difference between the best ask and the best bid.
+    pub fn spread(&self) -> Option<Decimal> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+
+        ask.price.checked_sub(bid.price)
+    }
+
+    /// Get the midpoint between the best bid and the best ask.
+    pub fn mid(&self) -> Option<Decimal> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+
+        bid.price.checked_add(ask.price)?.checked_div(Decimal::TWO)
+    }
+
+    /// Iterate over the price levels on one side of the book, best price first.
+    pub fn levels(&self, side: Side) -> Box<dyn Iterator<Item = Level> + '_> {
+        let levels = self.halfbook(side).iter().map(|(price, size)| Level {
+            price: *price,
+            size: *size,
+            order_count: 0,
+        });
+
+        match side {
+            Side::Buy => Box::new(levels.rev()),
+            Side::Sell => Box::new(levels),
+        }
+    }
+
+    /// Get up to `n` price levels on one side of the book, best price first.
+    pub fn top_levels(&self, side: Side, n: usize) -> Vec<Level> {
+        self.levels(side).take(n).collect()
+    }
+
+    /// Get the price levels on one side of the book whose prices fall within
+    /// `[low, high]`, best price first.
+    pub fn levels_within(&self, side: Side, low: Decimal, high: Decimal) -> Vec<Level> {
+        if low > high {
+            return vec![];
+        }
+
+        let levels = self
+            .halfbook(side)
+            .range(low..=high)
+            .map(|(price, size)| Level {
+                price: *price,
+                size: *size,
+                order_count: 0,
+            });
+
+        match side {
+            Side::Buy => levels.rev().collect(),
+            Side::Sell => levels.collect(),
+        }
+    }
+
+    /// Get the price levels on one side of the book that lie within `bps`
+    /// basis points of the mid price, best price first.
+    pub fn levels_within_bps(&self, side: Side, bps: Decimal) -> Vec<Level> {
+        let Some(mid) = self.mid() else {
+            return vec![];
+        };
+        let Some(offset) = mid
+            .checked_mul(bps)
+            .and_then(|offset| offset.checked_div(Decimal::from(10_000)))
+        else {
+            return vec![];
+        };
+
+        match side {
+            Side::Buy => self.levels_within(side, mid.saturating_sub(offset), mid),
+            Side::Sell => self.levels_within(side, mid, mid.saturating_add(offset)),
+        }
+    }
+
+    /// Get the number of price levels on both sides of the book.
+    pub fn level_count(&self) -> usize {
+        self.bids.len() + self.asks.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        exchange::websocket::channels::level_two::Change,
+        test_util::{make_order_book_with_orders, make_product},
+    };
+    use time::Duration;
+    use uuid::Uuid;
+
+    fn make_snapshot() -> LevelTwoMessage {
+        LevelTwoMessage::Snapshot {
+            product_id: "BTC-USD".into(),
+            bids: vec![
+                (Decimal::new(9900, 2), Decimal::ONE),
+                (Decimal::new(9800, 2), Decimal::TWO),
+            ],
+            asks: vec![(Decimal::new(10100, 2), Decimal::ONE)],
+            time: Some(OffsetDateTime::UNIX_EPOCH),
+        }
+    }
+
+    fn make_update(seconds: i64, changes: Vec<Change>) -> LevelTwoMessage {
+        LevelTwoMessage::Update {
+            product_id: "BTC-USD".into(),
+            changes,
+            time: OffsetDateTime::UNIX_EPOCH + Duration::seconds(seconds),
+        }
+    }
+
+    #[test]
+    fn applies_snapshots_and_updates() {
+        let mut book = L2OrderBook::new(make_product());
+        let update = make_update(
+            1,
+            vec![
+                Change {
+                    side: Side::Buy,
+                    price: Decimal::new(9900, 2),
+                    size: Decimal::ZERO,
+                },
+                Change {
+                    side: Side::Sell,
+                    price: Decimal::new(10050, 2),
+                    size: Decimal::TEN,
+                },
+            ],
+        );
+
+        assert!(matches!(
+            book.update_with(&update),
+            Err(Error::Unavailable(_))
+        ));
+
+        book.update_with(&make_snapshot()).unwrap();
+        book.update_with(&update).unwrap();
+
+        assert_eq!(book.sequence(), 2);
+        assert_eq!(book.best_bid().unwrap().price, Decimal::new(9800, 2));
+        assert_eq!(
+            book.best_ask(),
+            Some(Level {
+                price: Decimal::new(10050, 2),
+                size: Decimal::TEN,
+                order_count: 0,
+            })
+        );
+        assert_eq!(book.spread(), Some(Decimal::new(250, 2)));
+        assert_eq!(book.level_count(), 3);
+        assert!(matches!(
+            book.update_with(&make_update(0, vec![])),
+            Err(Error::OutOfSequence)
+        ));
+    }
+
+    #[test]
+    fn keeps_only_exchange_times() {
+        let mut book = L2OrderBook::new(make_product());
+        let mut snapshot = make_snapshot();
+
+        if let LevelTwoMessage::Snapshot { time, .. } = &mut snapshot {
+            *time = None;
+        }
+
+        book.update_with(&snapshot).unwrap();
+
+        assert_eq!(book.updated_at(), None);
+
+        book.update_with(&make_update(-1, vec![])).unwrap();
+
+        assert_eq!(
+            book.updated_at(),
+            Some(OffsetDateTime::UNIX_EPOCH - Duration::seconds(1))
+        );
+    }
+
+    #[test]
+    fn aggregates_a_level_three_book() {
+        let order_book = make_order_book_with_orders(
+            1000,
+            vec![
+                (Decimal::new(9900, 2), Uuid::new_v4(), Decimal::ONE),
+                (Decimal::new(9900, 2), Uuid::new_v4(), Decimal::TWO),
+                (Decimal::new(9800, 2), Uuid::new_v4(), Decimal::ONE),
+            ],
+            vec![(Decimal::new(10100, 2), Uuid::new_v4(), Decimal::ONE)],
+        );
+        let book = L2OrderBook::from_order_book(&order_book);
+
+        assert_eq!(book.sequence(), 0);
+        assert_eq!(book.updated_at(), Some(order_book.updated_at()));
+        assert_eq!(book.mid(), order_book.mid());
+
+        for side in [Side::Buy, Side::Sell] {
+            let prices = |levels: Vec<Level>| {
+                levels
+                    .into_iter()
+                    .map(|level| (level.price, level.size))
+                    .collect::<Vec<_>>()
+            };
+
+            assert_eq!(
+                prices(book.levels(side).collect()),
+                prices(order_book.levels(side).collect())
+            );
+            assert_eq!(
+                prices(book.levels_within_bps(side, Decimal::from(200))),
+                prices(order_book.levels_within_bps(side, Decimal::from(200)))
+            );
+        }
+    }
+}
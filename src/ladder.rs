@@ -0,0 +1,794 @@
+use crate::{
+    CompactOrderBook, Handle, Level, LevelStorage, Message, Order, OrderBook, Orders,
+    exchange::{
+        common::Error,
+        rest::products::Product,
+        websocket::channels::level_three::{Message as LevelThreeMessage, Side},
+    },
+};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use std::collections::{BTreeMap, HashMap, hash_map::Entry};
+use time::OffsetDateTime;
+use tracing::trace;
+use uuid::Uuid;
+
+const DEFAULT_WIDTH: usize = 8_192;
+
+/// The first set bit at or after `bit`.
+fn next_set(words: &[u64], bit: usize) -> Option<usize> {
+    let mut index = bit / 64;
+    let mut word = *words.get(index)? & (u64::MAX << (bit % 64));
+
+    while word == 0 {
+        index += 1;
+        word = *words.get(index)?;
+    }
+
+    Some(index * 64 + word.trailing_zeros() as usize)
+}
+
+/// The last set bit at or before `bit`.
+fn prev_set(words: &[u64], bit: usize) -> Option<usize> {
+    let mut index = bit / 64;
+    let mut word = *words.get(index)? & (u64::MAX >> (63 - bit % 64));
+
+    while word == 0 {
+        index = index.checked_sub(1)?;
+        word = words[index];
+    }
+
+    Some(index * 64 + 63 - word.leading_zeros() as usize)
+}
+
+/// Which slots of a dense window hold a level. `summary` marks the words of
+/// `words` with any bit set, so finding the next level skips 4096 empty slots
+/// per word read.
+#[derive(Debug)]
+struct Occupancy {
+    words: Vec<u64>,
+    summary: Vec<u64>,
+}
+
+impl Occupancy {
+    fn new(width: usize) -> Self {
+        let words = width.div_ceil(64);
+
+        Self {
+            words: vec![0; words],
+            summary: vec![0; words.div_ceil(64)],
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.summary.iter().all(|word| *word == 0)
+    }
+
+    fn set(&mut self, slot: usize) {
+        self.words[slot / 64] |= 1 << (slot % 64);
+        self.summary[slot / 4096] |= 1 << (slot / 64 % 64);
+    }
+
+    fn clear(&mut self, slot: usize) {
+        let index = slot / 64;
+
+        self.words[index] &= !(1 << (slot % 64));
+
+        if self.words[index] == 0 {
+            self.summary[index / 64] &= !(1 << (index % 64));
+        }
+    }
+
+    /// The first occupied slot at or after `slot`.
+    fn next(&self, slot: usize) -> Option<usize> {
+        let index = slot / 64;
+
+        match *self.words.get(index)? & (u64::MAX << (slot % 64)) {
+            0 => {
+                let index = next_set(&self.summary, index + 1)?;
+
+                Some(index * 64 + self.words[index].trailing_zeros() as usize)
+            }
+            word => Some(index * 64 + word.trailing_zeros() as usize),
+        }
+    }
+
+    /// The last occupied slot at or before `slot`.
+    fn prev(&self, slot: usize) -> Option<usize> {
+        let index = slot / 64;
+
+        match *self.words.get(index)? & (u64::MAX >> (63 - slot % 64)) {
+            0 => {
+                let index = prev_set(&self.summary, index.checked_sub(1)?)?;
+
+                Some(index * 64 + 63 - self.words[index].leading_zeros() as usize)
+            }
+            word => Some(index * 64 + 63 - word.leading_zeros() as usize),
+        }
+    }
+}
+
+/// One side of a `LadderOrderBook`: a dense window of tick-indexed levels
+/// around the touch, with every other level in a sparse overflow map.
+#[derive(Debug)]
+struct Ladder {
+    side: Side,
+    increment: Decimal,
+    /// The tick of `dense[0]`.
+    base: i64,
+    dense: Vec<Option<(Decimal, Orders)>>,
+    occupied: Occupancy,
+    /// Levels outside the window or off the tick grid.
+    overflow: BTreeMap<Decimal, Orders>,
+    best: Option<Decimal>,
+}
+
+impl Ladder {
+    fn new(side: Side, increment: Decimal, width: usize) -> Self {
+        let width = width.max(1);
+
+        Self {
+            side,
+            increment,
+            base: 0,
+            dense: (0..width).map(|_| None).collect(),
+            occupied: Occupancy::new(width),
+            overflow: BTreeMap::new(),
+            best: None,
+        }
+    }
+
+    fn is_better(&self, price: Decimal, than: Decimal) -> bool {
+        match self.side {
+            Side::Buy => price > than,
+            Side::Sell => price < than,
+        }
+    }
+
+    /// The tick of a price on the grid.
+    fn tick(&self, price: Decimal) -> Option<i64> {
+        let ticks = price.checked_div(self.increment)?;
+
+        ticks.fract().is_zero().then(|| ticks.to_i64()).flatten()
+    }
+
+    fn slot_of_tick(&self, tick: i64) -> Option<usize> {
+        let slot = usize::try_from(tick.checked_sub(self.base)?).ok()?;
+
+        (slot < self.dense.len()).then_some(slot)
+    }
+
+    fn slot(&self, price: Decimal) -> Option<usize> {
+        self.slot_of_tick(self.tick(price)?)
+    }
+
+    fn get(&self, price: Decimal) -> Option<&Orders> {
+        match self.slot(price) {
+            Some(slot) => self.dense[slot].as_ref().map(|(_, orders)| orders),
+            None => self.overflow.get(&price),
+        }
+    }
+
+    fn get_mut(&mut self, price: Decimal) -> Option<&mut Orders> {
+        match self.slot(price) {
+            Some(slot) => self.dense[slot].as_mut().map(|(_, orders)| orders),
+            None => self.overflow.get_mut(&price),
+        }
+    }
+
+    fn put(&mut self, price: Decimal, orders: Orders) {
+        match self.slot(price) {
+            Some(slot) => {
+                self.occupied.set(slot);
+                self.dense[slot] = Some((price, orders));
+            }
+            None => {
+                self.overflow.insert(price, orders);
+            }
+        }
+    }
+
+    fn insert(&mut self, price: Decimal, order: Order) -> Result<Handle, Error> {
+        if let Some(orders) = self.get_mut(price) {
+            return orders.insert(order);
+        }
+
+        let mut orders = Orders::default();
+        let handle = orders.insert(order)?;
+
+        self.put(price, orders);
+
+        if self.best.is_none_or(|best| self.is_better(price, best)) {
+            self.best = Some(price);
+            self.recenter_if_needed();
+        }
+
+        Ok(handle)
+    }
+
+    /// Remove a level, finding the next best price if it was the best.
+    fn remove(&mut self, price: Decimal) {
+        match self.slot(price) {
+            Some(slot) => {
+                self.dense[slot] = None;
+                self.occupied.clear(slot);
+            }
+            None => {
+                self.overflow.remove(&price);
+            }
+        }
+
+        if self.best == Some(price) {
+            self.best = self.next_best(price);
+            self.recenter_if_needed();
+        }
+    }
+
+    /// Find the best level behind `price`, which must be at least as good as
+    /// every remaining level.
+    fn next_best(&self, price: Decimal) -> Option<Decimal> {
+        let dense = (!self.occupied.is_empty())
+            .then(|| {
+                let ticks = price.checked_div(self.increment)?;
+                let width = self.dense.len() as i64;
+                let slot = match self.side {
+                    Side::Buy => {
+                        let start = (ticks.floor().to_i64()? - self.base).min(width - 1);
+
+                        self.occupied.prev(usize::try_from(start).ok()?)
+                    }
+                    Side::Sell => {
+                        let start = (ticks.ceil().to_i64()? - self.base).max(0);
+
+                        self.occupied.next(usize::try_from(start).ok()?)
+                    }
+                }?;
+
+                self.dense[slot].as_ref().map(|(price, _)| *price)
+            })
+            .flatten();
+        let overflow = match self.side {
+            Side::Buy => self.overflow.last_key_value(),
+            Side::Sell => self.overflow.first_key_value(),
+        }
+        .map(|(price, _)| *price);
+
+        match (dense, overflow) {
+            (Some(dense), Some(overflow)) if self.is_better(overflow, dense) => Some(overflow),
+            (Some(dense), _) => Some(dense),
+            (None, overflow) => overflow,
+        }
+    }
+
+    /// Move the window when the best price nears its edge.
+    fn recenter_if_needed(&mut self) {
+        let Some(tick) = self.best.and_then(|best| self.tick(best)) else {
+            return;
+        };
+        let width = self.dense.len() as i64;
+        let margin = width / 8;
+
+        match tick.checked_sub(self.base) {
+            Some(offset) if offset >= margin && offset < width - margin => {}
+            _ => self.recenter(tick),
+        }
+    }
+
+    fn recenter(&mut self, tick: i64) {
+        let width = self.dense.len();
+
+        trace!(side = %self.side, tick, "Recentering price ladder");
+
+        let levels = self.take_dense();
+
+        self.base = tick.saturating_sub(width as i64 / 2);
+
+        for (price, orders) in levels {
+            self.put(price, orders);
+        }
+
+        // Pull overflow levels that are now inside the window.
+        let low = Decimal::from(self.base).checked_mul(self.increment);
+        let high =
+            Decimal::from(self.base.saturating_add(width as i64 - 1)).checked_mul(self.increment);
+
+        if let (Some(low), Some(high)) = (low, high) {
+            let inside = self
+                .overflow
+                .range(low..=high)
+                .filter_map(|(price, _)| self.slot(*price).map(|_| *price))
+                .collect::<Vec<_>>();
+
+            for price in inside {
+                if let Some(orders) = self.overflow.remove(&price) {
+                    self.put(price, orders);
+                }
+            }
+        }
+    }
+
+    /// Empty the dense window, returning its levels in price order.
+    fn take_dense(&mut self) -> Vec<(Decimal, Orders)> {
+        let mut levels = vec![];
+        let mut slot = self.occupied.next(0);
+
+        while let Some(occupied) = slot {
+            levels.extend(self.dense[occupied].take());
+            slot = self.occupied.next(occupied + 1);
+        }
+
+        self.occupied = Occupancy::new(self.dense.len());
+
+        levels
+    }
+
+    /// Iterate over the dense levels best price first, jumping between
+    /// occupied slots.
+    fn dense_iter(&self) -> impl Iterator<Item = (Decimal, &Orders)> {
+        let mut slot = match self.side {
+            Side::Buy => self.occupied.prev(self.dense.len() - 1),
+            Side::Sell => self.occupied.next(0),
+        };
+
+        std::iter::from_fn(move || {
+            let occupied = slot?;
+
+            slot = match self.side {
+                Side::Buy => occupied
+                    .checked_sub(1)
+                    .and_then(|slot| self.occupied.prev(slot)),
+                Side::Sell => self.occupied.next(occupied + 1),
+            };
+
+            self.dense[occupied]
+                .as_ref()
+                .map(|(price, orders)| (*price, orders))
+        })
+    }
+
+    /// Iterate over the levels best price first.
+    fn iter(&self) -> impl Iterator<Item = (Decimal, &Orders)> {
+        let overflow = self.overflow.iter().map(|(price, orders)| (*price, orders));
+        let overflow: Box<dyn Iterator<Item = (Decimal, &Orders)>> = match self.side {
+            Side::Buy => Box::new(overflow.rev()),
+            Side::Sell => Box::new(overflow),
+        };
+        let (mut dense, mut overflow) = (self.dense_iter().peekable(), overflow.peekable());
+
+        std::iter::from_fn(move || match (dense.peek(), overflow.peek()) {
+            (Some((a, _)), Some((b, _))) if self.is_better(*b, *a) => overflow.next(),
+            (Some(_), _) => dense.next(),
+            (None, _) => overflow.next(),
+        })
+    }
+}
+
+/// An order book that keeps the levels near the touch in tick-indexed arrays,
+/// so most updates avoid tree lookups. It applies messages exactly as
+/// `OrderBook` does.
+#[derive(Debug)]
+pub struct LadderOrderBook {
+    pub product: Product,
+    bids: Ladder,
+    asks: Ladder,
+    sequence: u64,
+    updated_at: OffsetDateTime,
+    index: HashMap<Uuid, (Side, Decimal, Handle)>,
+}
+
+impl LadderOrderBook {
+    pub fn from_order_book(order_book: &OrderBook) -> Result<Self, Error> {
+        let increment = order_book.product.quote_increment;
+        let mut ladder = Self {
+            product: order_book.product.clone(),
+            bids: Ladder::new(Side::Buy, increment, DEFAULT_WIDTH),
+            asks: Ladder::new(Side::Sell, increment, DEFAULT_WIDTH),
+            sequence: order_book.sequence,
+            updated_at: order_book.updated_at,
+            index: HashMap::with_capacity(order_book.index.len()),
+        };
+
+        for (side, halfbook) in [
+            (Side::Buy, &order_book.bids),
+            (Side::Sell, &order_book.asks),
+        ] {
+            for (price, orders) in halfbook.iter() {
+                for order in orders.iter() {
+                    ladder.insert(side, *price, *order)?;
+                }
+            }
+        }
+
+        Ok(ladder)
+    }
+
+    /// Resize the dense window to `width` ticks per side.
+    pub fn with_width(mut self, width: usize) -> Self {
+        for ladder in [&mut self.bids, &mut self.asks] {
+            let mut resized = Ladder::new(ladder.side, ladder.increment, width);
+
+            resized.overflow.extend(ladder.take_dense());
+
+            resized.overflow.append(&mut ladder.overflow);
+            resized.best = ladder.best;
+
+            let tick = resized
+                .best
+                .and_then(|best| resized.tick(best))
+                .unwrap_or(ladder.base);
+
+            resized.recenter(tick);
+
+            *ladder = resized;
+        }
+
+        self
+    }
+
+    fn ladder_mut(&mut self, side: Side) -> &mut Ladder {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    fn ladder(&self, side: Side) -> &Ladder {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
+    }
+
+    /// Apply a message with the same semantics as `OrderBook::update_with`.
+    pub fn update_with(
+        &mut self,
+        level_three_message: &LevelThreeMessage,
+    ) -> Result<Message, Error> {
+        self.apply(level_three_message)
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn updated_at(&self) -> OffsetDateTime {
+        self.updated_at
+    }
+
+    pub fn best_bid(&self) -> Option<Level> {
+        let price = self.bids.best?;
+
+        self.bids.get(price).map(|orders| Level::new(price, orders))
+    }
+
+    pub fn best_ask(&self) -> Option<Level> {
+        let price = self.asks.best?;
+
+        self.asks.get(price).map(|orders| Level::new(price, orders))
+    }
+
+    pub fn spread(&self) -> Option<Decimal> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+
+        ask.price.checked_sub(bid.price)
+    }
+
+    pub fn mid(&self) -> Option<Decimal> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+
+        bid.price.checked_add(ask.price)?.checked_div(Decimal::TWO)
+    }
+
+    /// Iterate over the price levels on one side of the book, best price first.
+    pub fn levels(&self, side: Side) -> impl Iterator<Item = Level> + '_ {
+        self.ladder(side)
+            .iter()
+            .map(|(price, orders)| Level::new(price, orders))
+    }
+
+    pub fn top_levels(&self, side: Side, n: usize) -> Vec<Level> {
+        self.levels(side).take(n).collect()
+    }
+
+    /// Iterate over the orders queued at a price level in time priority.
+    pub fn orders_at(&self, side: Side, price: Decimal) -> impl Iterator<Item = &Order> {
+        self.ladder(side)
+            .get(price)
+            .into_iter()
+            .flat_map(|orders| orders.iter())
+    }
+
+    pub fn order(&self, order_id: Uuid) -> Option<(Side, Decimal, &Order)> {
+        let (side, price, handle) = *self.index.get(&order_id)?;
+        let order = self.ladder(side).get(price)?.get(handle)?;
+
+        Some((side, price, order))
+    }
+
+    pub fn order_count(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn to_compact(&self) -> CompactOrderBook {
+        let side = |ladder: &Ladder| {
+            let mut levels = ladder
+                .iter()
+                .map(|(price, orders)| (price.into(), orders.clone().into()))
+                .collect::<Vec<_>>();
+
+            // Compact books list both sides in ascending price order.
+            if ladder.side == Side::Buy {
+                levels.reverse();
+            }
+
+            levels
+        };
+
+        CompactOrderBook {
+            product: self.product.clone(),
+            asks: side(&self.asks),
+            bids: side(&self.bids),
+            sequence: self.sequence,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+impl LevelStorage for LadderOrderBook {
+    type Price = Decimal;
+    type Size = Decimal;
+
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    fn advance(&mut self, time: OffsetDateTime) {
+        self.sequence += 1;
+        self.updated_at = time;
+    }
+
+    fn index(&self) -> &HashMap<Uuid, (Side, Decimal, Handle)> {
+        &self.index
+    }
+
+    fn index_mut(&mut self) -> &mut HashMap<Uuid, (Side, Decimal, Handle)> {
+        &mut self.index
+    }
+
+    fn orders_mut(&mut self, side: Side, price: Decimal) -> Option<&mut Orders> {
+        self.ladder_mut(side).get_mut(price)
+    }
+
+    fn insert(&mut self, side: Side, price: Decimal, order: Order) -> Result<(), Error> {
+        let Entry::Vacant(entry) = self.index.entry(order.id) else {
+            return Err(Error::OrderAlreadyExists);
+        };
+        let ladder = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+
+        entry.insert((side, price, ladder.insert(price, order)?));
+
+        Ok(())
+    }
+
+    fn remove_level(&mut self, side: Side, price: Decimal) {
+        self.ladder_mut(side).remove(price);
+    }
+}
+
+impl TryFrom<CompactOrderBook> for LadderOrderBook {
+    type Error = Error;
+
+    fn try_from(compact_book: CompactOrderBook) -> Result<Self, Self::Error> {
+        Self::from_order_book(&OrderBook::try_from(compact_book)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::make_order_book_with_orders;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn assert_same(order_book: &OrderBook, ladder: &LadderOrderBook) {
+        assert_eq!(order_book.sequence(), ladder.sequence());
+        assert_eq!(order_book.best_bid(), ladder.best_bid());
+        assert_eq!(order_book.best_ask(), ladder.best_ask());
+        assert_eq!(order_book.order_count(), ladder.order_count());
+
+        for side in [Side::Buy, Side::Sell] {
+            assert_eq!(
+                order_book.levels(side).collect::<Vec<_>>(),
+                ladder.levels(side).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    fn random_message(rng: &mut StdRng, sequence: u64, ids: &[Uuid]) -> LevelThreeMessage {
+        let product_id = "BTC-USD".into();
+        let time = OffsetDateTime::UNIX_EPOCH;
+        let order_id = match ids.is_empty() || rng.random_bool(0.3) {
+            true => Uuid::new_v4(),
+            false => ids[rng.random_range(0..ids.len())],
+        };
+        // Mostly near the touch on the grid, sometimes far away or off it.
+        let price = match rng.random_range(0..20) {
+            0 => Decimal::new(rng.random_range(1..200_000), 2),
+            1 => Decimal::new(rng.random_range(9_500_000..10_500_000), 5),
+            _ => Decimal::new(rng.random_range(9_800..10_200), 2),
+        };
+        let size = Decimal::new(rng.random_range(1..500), 2);
+
+        match rng.random_range(0..10) {
+            0..4 => LevelThreeMessage::Open {
+                product_id,
+                sequence,
+                order_id,
+                side: match price < Decimal::new(10_000, 2) {
+                    true => Side::Buy,
+                    false => Side::Sell,
+                },
+                price,
+                size,
+                time,
+            },
+            4 => LevelThreeMessage::Change {
+                product_id,
+                sequence,
+                order_id,
+                price,
+                size,
+                time,
+            },
+            5 | 6 => LevelThreeMessage::Match {
+                product_id,
+                sequence,
+                maker_order_id: order_id,
+                taker_order_id: Uuid::new_v4(),
+                price,
+                size,
+                time,
+            },
+            7 => LevelThreeMessage::Noop {
+                product_id,
+                sequence,
+                time,
+            },
+            _ => LevelThreeMessage::Done {
+                product_id,
+                sequence,
+                order_id,
+                time,
+            },
+        }
+    }
+
+    #[test]
+    fn matches_order_book_on_random_messages() {
+        for (seed, width) in [(1, 64), (2, 512), (3, DEFAULT_WIDTH)] {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut order_book = make_order_book_with_orders(1000, vec![], vec![]);
+            let mut ladder = LadderOrderBook::from_order_book(&order_book)
+                .unwrap()
+                .with_width(width);
+            let mut ids = vec![];
+
+            for sequence in 1001..6_001 {
+                let mut message = random_message(&mut rng, sequence, &ids);
+
+                // Aim matches at the maker's real price most of the time.
+                let maker = match &message {
+                    LevelThreeMessage::Match { maker_order_id, .. } => {
+                        order_book.order(*maker_order_id)
+                    }
+                    _ => None,
+                };
+
+                if let Some((_, price, order)) = maker
+                    && rng.random_bool(0.8)
+                {
+                    message = LevelThreeMessage::Match {
+                        product_id: "BTC-USD".into(),
+                        sequence,
+                        maker_order_id: order.id,
+                        taker_order_id: Uuid::new_v4(),
+                        price,
+                        size: order.size.min(Decimal::new(rng.random_range(1..500), 2)),
+                        time: OffsetDateTime::UNIX_EPOCH,
+                    };
+                }
+
+                if let LevelThreeMessage::Open { order_id, .. } = &message {
+                    ids.push(*order_id);
+                }
+
+                let expected = order_book.update_with(&message);
+                let actual = ladder.update_with(&message);
+
+                match (expected, actual) {
+                    (Ok(expected), Ok(actual)) => assert_eq!(expected, actual),
+                    (Err(expected), Err(actual)) => {
+                        assert_eq!(expected.to_string(), actual.to_string())
+                    }
+                    (expected, actual) => panic!("{expected:?} != {actual:?} @ {message:?}"),
+                }
+
+                assert_same(&order_book, &ladder);
+            }
+
+            assert_eq!(
+                ladder.to_compact().to_bytes().unwrap(),
+                order_book.to_compact().to_bytes().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn occupancy_finds_the_nearest_occupied_slots() {
+        let mut rng = StdRng::seed_from_u64(4);
+
+        for width in [1, 64, 100, 4096, 4097, DEFAULT_WIDTH] {
+            let mut occupancy = Occupancy::new(width);
+            let mut slots = vec![false; width];
+
+            for _ in 0..1_000 {
+                let slot = rng.random_range(0..width);
+
+                slots[slot] = rng.random_bool(0.4);
+
+                match slots[slot] {
+                    true => occupancy.set(slot),
+                    false => occupancy.clear(slot),
+                }
+
+                let probe = rng.random_range(0..width);
+
+                assert_eq!(
+                    occupancy.next(probe),
+                    (probe..width).find(|slot| slots[*slot])
+                );
+                assert_eq!(
+                    occupancy.prev(probe),
+                    (0..=probe).rev().find(|slot| slots[*slot])
+                );
+                assert_eq!(occupancy.is_empty(), !slots.contains(&true));
+            }
+        }
+    }
+
+    #[test]
+    fn best_prices_follow_the_window() {
+        let best = Uuid::new_v4();
+        let order_book = make_order_book_with_orders(
+            1000,
+            vec![
+                (Decimal::new(9_900, 2), best, Decimal::ONE),
+                (Decimal::new(5_000, 2), Uuid::new_v4(), Decimal::ONE),
+            ],
+            vec![(Decimal::new(10_100, 2), Uuid::new_v4(), Decimal::ONE)],
+        );
+        let mut ladder = LadderOrderBook::from_order_book(&order_book)
+            .unwrap()
+            .with_width(16);
+
+        assert!(ladder.bids.overflow.contains_key(&Decimal::new(5_000, 2)));
+
+        ladder
+            .update_with(&LevelThreeMessage::Done {
+                product_id: "BTC-USD".into(),
+                sequence: 1001,
+                order_id: best,
+                time: OffsetDateTime::UNIX_EPOCH,
+            })
+            .unwrap();
+
+        // The next best bid is far away, so the window moves to it.
+        assert_eq!(
+            ladder.best_bid().map(|level| level.price),
+            Some(Decimal::new(5_000, 2))
+        );
+        assert!(ladder.bids.overflow.is_empty());
+        assert_eq!(ladder.top_levels(Side::Sell, 5).len(), 1);
+    }
+}
@@ -1,35 +1,67 @@
 pub mod advanced;
+pub mod bbo;
+pub mod candle;
+mod codec;
+pub mod connection;
+pub mod delta;
 pub mod exchange;
+pub mod fixed;
+pub mod impact;
+pub mod journal;
+pub mod l2;
+pub mod ladder;
+pub mod multi;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod queue;
+pub mod reconcile;
+pub mod replay;
+pub mod snapshot;
+pub mod tape;
+#[cfg(test)]
+mod test_util;
+pub mod ticker;
+pub mod validate;
+pub mod warm;
 
 use exchange::common::{Error, authentication::Signer, rate_limit::TokenBucket};
 use exchange::rest::{
     Client, ClientBuilder,
-    products::{Product, Products},
+    products::{Product, ProductBook, Products},
 };
 use exchange::websocket::channels::{
-    Channel, ChannelBuilder,
+    Channel, DIRECT_FEED_DOMAIN, FEED_PORT,
+    full::Message as FullMessage,
     level_three::{Message as LevelThreeMessage, Side},
 };
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use smartstring::{LazyCompact, SmartString};
 use std::{
-    collections::{BTreeMap, HashMap, VecDeque},
+    collections::{BTreeMap, HashMap, VecDeque, hash_map::Entry},
     sync::Arc,
     time::Duration,
 };
 use time::OffsetDateTime;
-use tokio::time::sleep;
+use tokio::{sync::watch, time::sleep};
 use tokio_rustls::rustls::ClientConfig;
 use tracing::{debug, trace, warn};
 use uuid::Uuid;
 
+use crate::bbo::BboTracker;
+use crate::connection::{ConnectionState, Connector, ReconnectPolicy, Resync};
+use crate::delta::DeltaTracker;
 use crate::exchange::common::rate_limit::BackOffBucket;
+use crate::multi::MultiOrderBook;
+use crate::queue::WatchList;
+use crate::reconcile::{Drift, ReconcilePolicy, Reconciler};
+use crate::validate::Validation;
+use crate::warm::WarmStart;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
-struct Order {
-    id: Uuid,
-    size: Decimal,
+pub struct Order<S = Decimal> {
+    pub id: Uuid,
+    pub size: S,
 }
 
 impl From<CompactOrder> for Order {
@@ -41,52 +73,222 @@ impl From<CompactOrder> for Order {
     }
 }
 
-impl Order {
-    pub fn new(id: Uuid, size: Decimal) -> Self {
+impl<S> Order<S> {
+    pub fn new(id: Uuid, size: S) -> Self {
         Self { id, size }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-struct Orders {
-    total_size: Decimal,
-    queue: VecDeque<Order>,
+/// A size that a level's queue can total.
+trait Quantity: Copy + Default {
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_sub(self, other: Self) -> Option<Self>;
+    fn is_zero(&self) -> bool;
 }
 
-impl From<Order> for Orders {
-    fn from(order: Order) -> Self {
-        let total_size = order.size;
-        let mut queue = VecDeque::new();
+impl Quantity for Decimal {
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Decimal::checked_add(self, other)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        Decimal::checked_sub(self, other)
+    }
+
+    fn is_zero(&self) -> bool {
+        Decimal::is_zero(self)
+    }
+}
+
+impl Quantity for u64 {
+    fn checked_add(self, other: Self) -> Option<Self> {
+        u64::checked_add(self, other)
+    }
 
-        queue.push_back(order);
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        u64::checked_sub(self, other)
+    }
 
-        Self { total_size, queue }
+    fn is_zero(&self) -> bool {
+        *self == 0
     }
 }
 
-impl From<CompactOrders> for Orders {
-    fn from(orders: CompactOrders) -> Self {
+/// A slot in a level's queue, stored in the index so an order can be found
+/// without scanning.
+pub(crate) type Handle = u32;
+
+const NIL: Handle = Handle::MAX;
+
+#[derive(Debug, Clone)]
+struct Node<S> {
+    order: Order<S>,
+    prev: Handle,
+    next: Handle,
+    live: bool,
+}
+
+/// The FIFO queue at a price level: a doubly linked list threaded through a
+/// slab, so orders can be removed or resized in constant time by handle.
+#[derive(Debug, Clone)]
+struct Orders<S = Decimal> {
+    total_size: S,
+    nodes: Vec<Node<S>>,
+    free: Vec<Handle>,
+    head: Handle,
+    tail: Handle,
+    len: usize,
+}
+
+impl<S: Quantity> Default for Orders<S> {
+    fn default() -> Self {
         Self {
-            total_size: orders.total_size(),
-            queue: orders
-                .0
-                .into_iter()
-                .map(|order| Order::from(order))
-                .collect(),
+            total_size: S::default(),
+            nodes: vec![],
+            free: vec![],
+            head: NIL,
+            tail: NIL,
+            len: 0,
         }
     }
 }
 
-impl Orders {
-    pub fn reduce_or_delete(&mut self, order_id: Uuid, size: Decimal) -> Result<bool, Error> {
-        let index = self
-            .queue
-            .iter()
-            .enumerate()
-            .find_map(|(index, order)| (order.id == order_id).then_some(index))
-            .ok_or_else(|| Error::OrderDoesNotExist)?;
-        let order = self.queue.get_mut(index).ok_or_else(|| Error::Impossible)?;
-        let order_size = order
+impl<S: Quantity + PartialEq> PartialEq for Orders<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.total_size == other.total_size && self.iter().eq(other.iter())
+    }
+}
+
+impl<S: Quantity + Eq> Eq for Orders<S> {}
+
+impl From<Order> for Orders {
+    fn from(order: Order) -> Self {
+        let mut orders = Self {
+            total_size: order.size,
+            ..Self::default()
+        };
+
+        orders.link(order);
+        orders
+    }
+}
+
+impl TryFrom<CompactOrders> for Orders {
+    type Error = Error;
+
+    fn try_from(orders: CompactOrders) -> Result<Self, Self::Error> {
+        let mut level = Self {
+            total_size: orders.total_size()?,
+            ..Self::default()
+        };
+
+        for order in orders.0 {
+            level.link(Order::from(order));
+        }
+
+        Ok(level)
+    }
+}
+
+impl<S: Quantity> Orders<S> {
+    /// Append an order to the queue without touching the total size.
+    fn link(&mut self, order: Order<S>) -> Handle {
+        let node = Node {
+            order,
+            prev: self.tail,
+            next: NIL,
+            live: true,
+        };
+        let handle = match self.free.pop() {
+            Some(handle) => {
+                self.nodes[handle as usize] = node;
+                handle
+            }
+            None => {
+                self.nodes.push(node);
+                (self.nodes.len() - 1) as Handle
+            }
+        };
+
+        match self.tail {
+            NIL => self.head = handle,
+            tail => self.nodes[tail as usize].next = handle,
+        }
+
+        self.tail = handle;
+        self.len += 1;
+
+        handle
+    }
+
+    /// Remove an order from the queue without touching the total size.
+    fn unlink(&mut self, handle: Handle) -> Order<S> {
+        let node = &mut self.nodes[handle as usize];
+        let (prev, next) = (node.prev, node.next);
+
+        node.live = false;
+
+        match prev {
+            NIL => self.head = next,
+            prev => self.nodes[prev as usize].next = next,
+        }
+
+        match next {
+            NIL => self.tail = prev,
+            next => self.nodes[next as usize].prev = prev,
+        }
+
+        self.free.push(handle);
+        self.len -= 1;
+
+        self.nodes[handle as usize].order
+    }
+
+    /// Find the node of an order, checking the handle still refers to it.
+    fn node(&self, handle: Handle, order_id: Uuid) -> Result<&Node<S>, Error> {
+        self.nodes
+            .get(handle as usize)
+            .filter(|node| node.live && node.order.id == order_id)
+            .ok_or_else(|| Error::OrderDoesNotExist)
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&Order<S>> {
+        self.nodes
+            .get(handle as usize)
+            .filter(|node| node.live)
+            .map(|node| &node.order)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the orders in time priority.
+    pub fn iter(&self) -> impl Iterator<Item = &Order<S>> {
+        let mut handle = self.head;
+
+        std::iter::from_fn(move || {
+            let node = self.nodes.get(handle as usize)?;
+
+            handle = node.next;
+
+            Some(&node.order)
+        })
+    }
+
+    pub fn reduce_or_delete(
+        &mut self,
+        handle: Handle,
+        order_id: Uuid,
+        size: S,
+    ) -> Result<bool, Error> {
+        let order_size = self
+            .node(handle, order_id)?
+            .order
             .size
             .checked_sub(size)
             .ok_or_else(|| Error::math("size exceeds order size", None))?;
@@ -95,11 +297,11 @@ impl Orders {
             .checked_sub(size)
             .ok_or_else(|| Error::math("size exceeds total size", None))?;
 
-        order.size = order_size;
+        self.nodes[handle as usize].order.size = order_size;
         self.total_size = total_size;
 
-        if order.size.is_zero() {
-            self.queue.remove(index).ok_or_else(|| Error::Impossible)?;
+        if order_size.is_zero() {
+            self.unlink(handle);
 
             Ok(true) // order was deleted
         } else {
@@ -107,35 +309,72 @@ impl Orders {
         }
     }
 
-    pub fn insert(&mut self, order: Order) -> Result<(), Error> {
+    /// Change the size of an order without changing its priority.
+    pub fn resize(&mut self, handle: Handle, order_id: Uuid, size: S) -> Result<(), Error> {
+        let old_size = self.node(handle, order_id)?.order.size;
+        let total_size = self
+            .total_size
+            .checked_sub(old_size)
+            .ok_or_else(|| Error::math("old size exceeds total size", None))?
+            .checked_add(size)
+            .ok_or_else(|| Error::math("new size causes overflow", None))?;
+
+        self.nodes[handle as usize].order.size = size;
+        self.total_size = total_size;
+
+        Ok(())
+    }
+
+    pub fn insert(&mut self, order: Order<S>) -> Result<Handle, Error> {
         self.total_size = self
             .total_size
             .checked_add(order.size)
             .ok_or_else(|| Error::math("size overflows total size", None))?;
-        self.queue.push_back(order);
 
-        Ok(())
+        Ok(self.link(order))
     }
 
-    pub fn delete(&mut self, order_id: Uuid) -> Result<(), Error> {
-        let index = self
-            .queue
-            .iter()
-            .enumerate()
-            .find_map(|(index, order)| (order.id == order_id).then_some(index))
-            .ok_or_else(|| Error::OrderDoesNotExist)?;
-        let order = self.queue.remove(index).ok_or_else(|| Error::Impossible)?;
+    pub fn delete(&mut self, handle: Handle, order_id: Uuid) -> Result<Order<S>, Error> {
+        let order_size = self.node(handle, order_id)?.order.size;
         let total_size = self
             .total_size
-            .checked_sub(order.size)
+            .checked_sub(order_size)
             .ok_or_else(|| Error::math("order size exceeds total size", None))?;
 
         self.total_size = total_size;
 
-        Ok(())
+        Ok(self.unlink(handle))
+    }
+}
+
+/// Aggregated view of a single price level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Level {
+    pub price: Decimal,
+    pub size: Decimal,
+    pub order_count: usize,
+}
+
+impl Level {
+    fn new(price: Decimal, orders: &Orders) -> Self {
+        Self {
+            price,
+            size: orders.total_size,
+            order_count: orders.len(),
+        }
     }
 }
 
+/// What a book tracks beyond its orders. This carries over when the book is
+/// rebuilt from a snapshot.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Tracking {
+    pub(crate) watch_list: WatchList,
+    pub(crate) validation: Validation,
+    pub(crate) bbo: BboTracker,
+    pub(crate) deltas: DeltaTracker,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct OrderBook {
     // Product data
@@ -148,353 +387,632 @@ pub struct OrderBook {
     bids: BTreeMap<Decimal, Orders>,
     sequence: u64,
     updated_at: OffsetDateTime,
-    index: HashMap<Uuid, (Side, Decimal)>, // price index by order id
+    index: HashMap<Uuid, (Side, Decimal, Handle)>, // price and queue slot by order id
+
+    // Tracking data
+    tracking: Tracking,
 }
 
-impl OrderBook {
-    fn insert(&mut self, side: Side, price: Decimal, order: Order) -> Result<(), Error> {
-        if self.index.insert(order.id, (side, price)).is_some() {
-            return Err(Error::OrderAlreadyExists);
-        };
+/// A level3 message's effect on a book, with its price and size in the book's
+/// own units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Update<P, S> {
+    Open {
+        order_id: Uuid,
+        side: Side,
+        price: P,
+        size: S,
+    },
+    Change {
+        order_id: Uuid,
+        price: P,
+        size: S,
+    },
+    Match {
+        maker_order_id: Uuid,
+        price: P,
+        size: S,
+    },
+    Noop,
+    Done {
+        order_id: Uuid,
+    },
+}
 
-        match side {
-            Side::Buy => {
-                if let Some(orders) = self.bids.get_mut(&price) {
-                    orders.insert(order)?;
-                } else {
-                    self.bids.insert(price, Orders::from(order));
-                }
+impl From<&LevelThreeMessage> for Update<Decimal, Decimal> {
+    fn from(level_three_message: &LevelThreeMessage) -> Self {
+        match *level_three_message {
+            LevelThreeMessage::Open {
+                order_id,
+                side,
+                price,
+                size,
+                ..
+            } => Update::Open {
+                order_id,
+                side,
+                price,
+                size,
+            },
+            LevelThreeMessage::Change {
+                order_id,
+                price,
+                size,
+                ..
+            } => Update::Change {
+                order_id,
+                price,
+                size,
+            },
+            LevelThreeMessage::Match {
+                maker_order_id,
+                price,
+                size,
+                ..
+            } => Update::Match {
+                maker_order_id,
+                price,
+                size,
+            },
+            LevelThreeMessage::Noop { .. } => Update::Noop,
+            LevelThreeMessage::Done { order_id, .. } => Update::Done { order_id },
+        }
+    }
+}
 
-                self.best_bid = (price > self.best_bid)
-                    .then(|| price)
-                    .unwrap_or(self.best_bid);
-            }
-            Side::Sell => {
-                if let Some(orders) = self.asks.get_mut(&price) {
-                    orders.insert(order)?;
-                } else {
-                    self.asks.insert(price, Orders::from(order));
-                }
+/// Where a book keeps its orders. Books that lay out their price levels or
+/// count prices and sizes differently share `apply_update`, so they handle
+/// every message the same way.
+pub(crate) trait LevelStorage {
+    type Price: Copy + PartialEq;
+    type Size: Quantity + PartialOrd;
 
-                self.best_ask = (price < self.best_ask)
-                    .then(|| price)
-                    .unwrap_or(self.best_ask);
-            }
-        }
+    fn sequence(&self) -> u64;
 
-        Ok(())
-    }
+    /// Move the book to the next sequence.
+    fn advance(&mut self, time: OffsetDateTime);
 
-    fn delete(&mut self, order_id: Uuid) -> Result<Side, Error> {
-        let (side, price) = self
-            .index
-            .remove(&order_id)
-            .ok_or_else(|| Error::OrderDoesNotExist)?;
+    fn index(&self) -> &HashMap<Uuid, (Side, Self::Price, Handle)>;
 
-        match side {
-            Side::Buy => {
-                let orders = self
-                    .bids
-                    .get_mut(&price)
-                    .ok_or_else(|| Error::PriceDoesNotExist { side })?;
+    fn index_mut(&mut self) -> &mut HashMap<Uuid, (Side, Self::Price, Handle)>;
 
-                orders.delete(order_id)?;
+    fn orders_mut(&mut self, side: Side, price: Self::Price) -> Option<&mut Orders<Self::Size>>;
 
-                if orders.queue.is_empty() {
-                    self.bids.remove(&price);
-                    self.best_bid = self
-                        .bids
-                        .last_key_value()
-                        .map(|(price, _)| *price)
-                        .unwrap_or(Decimal::ZERO);
-                }
-            }
-            Side::Sell => {
-                let orders = self
-                    .asks
-                    .get_mut(&price)
-                    .ok_or_else(|| Error::PriceDoesNotExist { side })?;
+    /// Queue a new order at a price, adding the level if it's new.
+    fn insert(
+        &mut self,
+        side: Side,
+        price: Self::Price,
+        order: Order<Self::Size>,
+    ) -> Result<(), Error>;
 
-                orders.delete(order_id)?;
+    /// Remove an emptied level, finding the next best price if it was the
+    /// best.
+    fn remove_level(&mut self, side: Side, price: Self::Price);
 
-                if orders.queue.is_empty() {
-                    self.asks.remove(&price);
-                    self.best_ask = self
-                        .asks
-                        .first_key_value()
-                        .map(|(price, _)| *price)
-                        .unwrap_or(Decimal::MAX);
-                }
-            }
+    fn delete(&mut self, order_id: Uuid) -> Result<Side, Error> {
+        let (side, price, handle) = self
+            .index_mut()
+            .remove(&order_id)
+            .ok_or_else(|| Error::OrderDoesNotExist)?;
+        let orders = self
+            .orders_mut(side, price)
+            .ok_or_else(|| Error::PriceDoesNotExist { side })?;
+
+        orders.delete(handle, order_id)?;
+
+        if orders.is_empty() {
+            self.remove_level(side, price);
         }
 
         Ok(side)
     }
 
-    pub fn update_with(
+    /// Apply the update carried by the message at `sequence`, returning the
+    /// side of the book it touched, if any.
+    fn apply_update(
         &mut self,
-        level_three_message: &LevelThreeMessage,
-    ) -> Result<Message, Error> {
-        match level_three_message {
-            LevelThreeMessage::Open {
-                sequence,
+        sequence: u64,
+        time: OffsetDateTime,
+        update: Update<Self::Price, Self::Size>,
+    ) -> Result<Option<Side>, Error> {
+        match update {
+            Update::Open { order_id, side, .. } => {
+                trace!(seq_b = %self.sequence(), seq_m = %sequence, %order_id, %side, "Open")
+            }
+            Update::Change { order_id, .. } => {
+                trace!(seq_b = %self.sequence(), seq_m = %sequence, %order_id, "Change")
+            }
+            Update::Match { maker_order_id, .. } => {
+                trace!(seq_b = %self.sequence(), seq_m = %sequence, %maker_order_id, "Match")
+            }
+            Update::Noop => trace!(seq_b = %self.sequence(), seq_m = %sequence, "Noop"),
+            Update::Done { order_id } => {
+                trace!(seq_b = %self.sequence(), seq_m = %sequence, %order_id, "Done")
+            }
+        }
+
+        if sequence != self.sequence() + 1 {
+            return Err(Error::OutOfSequence);
+        }
+
+        self.advance(time);
+
+        match update {
+            Update::Open {
                 order_id,
                 side,
                 price,
                 size,
-                time,
-                ..
             } => {
-                trace!(seq_b = %self.sequence, seq_m = %sequence, %order_id, %side, "Open");
-                if *sequence != self.sequence + 1 {
-                    return Err(Error::OutOfSequence);
-                }
-
-                self.sequence += 1;
-                self.updated_at = *time;
-                self.insert(*side, *price, Order::new(*order_id, *size))?;
-
-                let message = Message::Open {
-                    sequence: *sequence,
-                    time: *time,
-                    order_id: *order_id,
-                    side: *side,
-                    price: *price,
-                    size: *size,
-                };
+                self.insert(side, price, Order::new(order_id, size))?;
 
-                Ok(message)
+                Ok(Some(side))
             }
-            LevelThreeMessage::Change {
-                sequence,
+            Update::Change {
                 order_id,
                 price,
                 size,
-                time,
-                ..
             } => {
-                trace!(seq_b = %self.sequence, seq_m = %sequence, %order_id, "Change");
-                if *sequence != self.sequence + 1 {
-                    return Err(Error::OutOfSequence);
-                }
-
-                self.sequence += 1;
-                self.updated_at = *time;
-
-                let message = Message::Change {
-                    sequence: *sequence,
-                    time: *time,
-                    order_id: *order_id,
-                    price: *price,
-                    size: *size,
+                let Some(&(old_side, old_price, handle)) = self.index().get(&order_id) else {
+                    return Ok(None);
                 };
-                let (halfbook, old_side, old_price, old_size, old_index) =
-                    match self.index.get(&order_id) {
-                        Some((old_side, old_price)) => {
-                            let halfbook = match old_side {
-                                Side::Buy => &mut self.bids,
-                                Side::Sell => &mut self.asks,
-                            };
-                            let (old_index, old_size) = halfbook
-                                .get(old_price)
-                                .ok_or_else(|| Error::PriceDoesNotExist { side: *old_side })?
-                                .queue
-                                .iter()
-                                .enumerate()
-                                .find(|(_, order)| order.id == *order_id)
-                                .map(|(index, order)| (index, order.size))
-                                .ok_or_else(|| Error::OrderDoesNotExist)?;
-
-                            (halfbook, *old_side, *old_price, old_size, old_index)
-                        }
-                        None => return Ok(message),
-                    };
-
-                if old_price != *price || old_size < *size {
+                let orders = self
+                    .orders_mut(old_side, old_price)
+                    .ok_or_else(|| Error::PriceDoesNotExist { side: old_side })?;
+                let old_size = orders
+                    .get(handle)
+                    .filter(|order| order.id == order_id)
+                    .ok_or_else(|| Error::OrderDoesNotExist)?
+                    .size;
+
+                if old_price != price || old_size < size {
                     // The price changed or the size increased, so delete the old order.
+                    orders.delete(handle, order_id)?;
+
+                    if orders.is_empty() {
+                        self.remove_level(old_side, old_price);
+                    }
+
                     let _ = self
-                        .index
+                        .index_mut()
                         .remove(&order_id)
                         .ok_or_else(|| Error::Impossible)?;
-                    let orders = halfbook
-                        .get_mut(&old_price)
-                        .ok_or_else(|| Error::Impossible)?;
-                    let _ = orders
-                        .queue
-                        .remove(old_index)
-                        .ok_or_else(|| Error::Impossible)?;
-                    let total_size = orders
-                        .total_size
-                        .checked_sub(old_size)
-                        .ok_or_else(|| Error::math("order size exceeds total size", None))?;
-
-                    orders.total_size = total_size;
-
-                    if orders.queue.is_empty() {
-                        match old_side {
-                            Side::Buy => {
-                                self.bids.remove(&old_price);
-                                self.best_bid = self
-                                    .bids
-                                    .last_key_value()
-                                    .map(|(price, _)| *price)
-                                    .unwrap_or(Decimal::ZERO);
-                            }
-                            Side::Sell => {
-                                self.asks.remove(&old_price);
-                                self.best_ask = self
-                                    .asks
-                                    .first_key_value()
-                                    .map(|(price, _)| *price)
-                                    .unwrap_or(Decimal::MAX);
-                            }
-                        }
-                    }
 
                     // And replace it with (insert) the new order.
-                    self.insert(old_side, *price, Order::new(*order_id, *size))?;
+                    self.insert(old_side, price, Order::new(order_id, size))?;
                 } else {
                     // Only the size decreased, so modify the order in place.
-                    let orders = halfbook
-                        .get_mut(&old_price)
-                        .ok_or_else(|| Error::Impossible)?;
-                    let order = orders
-                        .queue
-                        .get_mut(old_index)
-                        .ok_or_else(|| Error::Impossible)?;
-                    let total_size = orders
-                        .total_size
-                        .checked_sub(old_size)
-                        .ok_or_else(|| Error::math("old size exceeds total size", None))?
-                        .checked_add(*size)
-                        .ok_or_else(|| Error::math("new size causes overflow", None))?;
-
-                    order.size = *size;
-                    orders.total_size = total_size;
+                    orders.resize(handle, order_id, size)?;
                 }
 
-                Ok(message)
+                Ok(Some(old_side))
             }
-            LevelThreeMessage::Match {
-                sequence,
+            Update::Match {
                 maker_order_id,
-                taker_order_id,
                 price,
                 size,
-                time,
-                ..
             } => {
-                trace!(seq_b = %self.sequence, seq_m = %sequence, %maker_order_id, "Match");
-                if *sequence != self.sequence + 1 {
-                    return Err(Error::OutOfSequence);
-                }
-
-                self.sequence += 1;
-                self.updated_at = *time;
-
-                let (side, _) = *self
-                    .index
+                let (side, maker_price, handle) = *self
+                    .index()
                     .get(&maker_order_id)
                     .ok_or_else(|| Error::OrderDoesNotExist)?;
-                let message = Message::Match {
-                    sequence: *sequence,
-                    time: *time,
-                    maker_order_id: *maker_order_id,
-                    taker_order_id: *taker_order_id,
-                    side,
-                    price: *price,
-                    size: *size,
-                };
+                let orders = self
+                    .orders_mut(side, price)
+                    .ok_or_else(|| Error::PriceDoesNotExist { side })?;
 
-                match side {
-                    Side::Buy => {
-                        let orders = self
-                            .bids
-                            .get_mut(price)
-                            .ok_or_else(|| Error::PriceDoesNotExist { side })?;
-
-                        if orders.reduce_or_delete(*maker_order_id, *size)? {
-                            let _ = self.index.remove(&maker_order_id);
-                        }
+                if maker_price != price {
+                    return Err(Error::OrderDoesNotExist);
+                }
 
-                        if orders.queue.is_empty() {
-                            self.bids.remove(price);
-                            self.best_bid = self
-                                .bids
-                                .last_key_value()
-                                .map(|(p, _)| *p)
-                                .unwrap_or(Decimal::ZERO);
-                        }
-                    }
-                    Side::Sell => {
-                        let orders = self
-                            .asks
-                            .get_mut(price)
-                            .ok_or_else(|| Error::PriceDoesNotExist { side })?;
-
-                        if orders.reduce_or_delete(*maker_order_id, *size)? {
-                            let _ = self.index.remove(&maker_order_id);
-                        }
+                let filled = orders.reduce_or_delete(handle, maker_order_id, size)?;
 
-                        if orders.queue.is_empty() {
-                            self.asks.remove(price);
-                            self.best_ask = self
-                                .asks
-                                .first_key_value()
-                                .map(|(p, _)| *p)
-                                .unwrap_or(Decimal::MAX);
-                        }
-                    }
+                if orders.is_empty() {
+                    self.remove_level(side, price);
                 }
 
-                Ok(message)
-            }
-            LevelThreeMessage::Noop { sequence, time, .. } => {
-                trace!(seq_b = %self.sequence, seq_m = %sequence, "Noop");
-                if *sequence != self.sequence + 1 {
-                    return Err(Error::OutOfSequence);
+                if filled {
+                    let _ = self.index_mut().remove(&maker_order_id);
                 }
 
-                self.sequence += 1;
-                self.updated_at = *time;
-
-                let message = Message::Noop {
-                    sequence: *sequence,
-                    time: *time,
-                };
-
-                Ok(message)
+                Ok(Some(side))
             }
+            Update::Noop => Ok(None),
+            Update::Done { order_id } => match self.delete(order_id) {
+                Ok(side) => Ok(Some(side)),
+                Err(Error::OrderDoesNotExist) => Ok(None),
+                Err(error) => Err(error),
+            },
+        }
+    }
+
+    /// Apply a message to a book of decimal prices and sizes.
+    fn apply(&mut self, level_three_message: &LevelThreeMessage) -> Result<Message, Error>
+    where
+        Self: LevelStorage<Price = Decimal, Size = Decimal>,
+    {
+        let side = self.apply_update(
+            level_three_message.sequence(),
+            level_three_message.time(),
+            Update::from(level_three_message),
+        )?;
+        let message = match *level_three_message {
+            LevelThreeMessage::Open {
+                sequence,
+                order_id,
+                side,
+                price,
+                size,
+                time,
+                ..
+            } => Message::Open {
+                sequence,
+                time,
+                order_id,
+                side,
+                price,
+                size,
+            },
+            LevelThreeMessage::Change {
+                sequence,
+                order_id,
+                price,
+                size,
+                time,
+                ..
+            } => Message::Change {
+                sequence,
+                time,
+                order_id,
+                price,
+                size,
+            },
+            LevelThreeMessage::Match {
+                sequence,
+                maker_order_id,
+                taker_order_id,
+                price,
+                size,
+                time,
+                ..
+            } => Message::Match {
+                sequence,
+                time,
+                maker_order_id,
+                taker_order_id,
+                side: side.ok_or_else(|| Error::Impossible)?,
+                price,
+                size,
+            },
+            LevelThreeMessage::Noop { sequence, time, .. } => Message::Noop { sequence, time },
             LevelThreeMessage::Done {
                 sequence,
                 order_id,
                 time,
                 ..
-            } => {
-                trace!(seq_b = %self.sequence, seq_m = %sequence, %order_id, "Done");
-                if *sequence != self.sequence + 1 {
-                    return Err(Error::OutOfSequence);
-                }
+            } => Message::Done {
+                sequence,
+                time,
+                order_id,
+            },
+        };
 
-                self.sequence += 1;
-                self.updated_at = *time;
+        Ok(message)
+    }
+}
 
-                let message = Message::Done {
-                    sequence: *sequence,
-                    time: *time,
-                    order_id: *order_id,
-                };
+impl LevelStorage for OrderBook {
+    type Price = Decimal;
+    type Size = Decimal;
 
-                match self.delete(*order_id) {
-                    Ok(_) => Ok(message),
-                    Err(Error::OrderDoesNotExist) => Ok(message),
-                    Err(error) => Err(error),
-                }
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    fn advance(&mut self, time: OffsetDateTime) {
+        self.sequence += 1;
+        self.updated_at = time;
+    }
+
+    fn index(&self) -> &HashMap<Uuid, (Side, Decimal, Handle)> {
+        &self.index
+    }
+
+    fn index_mut(&mut self) -> &mut HashMap<Uuid, (Side, Decimal, Handle)> {
+        &mut self.index
+    }
+
+    fn orders_mut(&mut self, side: Side, price: Decimal) -> Option<&mut Orders> {
+        match side {
+            Side::Buy => self.bids.get_mut(&price),
+            Side::Sell => self.asks.get_mut(&price),
+        }
+    }
+
+    fn insert(&mut self, side: Side, price: Decimal, order: Order) -> Result<(), Error> {
+        let Entry::Vacant(entry) = self.index.entry(order.id) else {
+            return Err(Error::OrderAlreadyExists);
+        };
+        let halfbook = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        let handle = halfbook.entry(price).or_default().insert(order)?;
+
+        entry.insert((side, price, handle));
+
+        match side {
+            Side::Buy => {
+                self.best_bid = (price > self.best_bid)
+                    .then(|| price)
+                    .unwrap_or(self.best_bid);
+            }
+            Side::Sell => {
+                self.best_ask = (price < self.best_ask)
+                    .then(|| price)
+                    .unwrap_or(self.best_ask);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove_level(&mut self, side: Side, price: Decimal) {
+        match side {
+            Side::Buy => {
+                self.bids.remove(&price);
+                self.best_bid = self
+                    .bids
+                    .last_key_value()
+                    .map(|(price, _)| *price)
+                    .unwrap_or(Decimal::ZERO);
+            }
+            Side::Sell => {
+                self.asks.remove(&price);
+                self.best_ask = self
+                    .asks
+                    .first_key_value()
+                    .map(|(price, _)| *price)
+                    .unwrap_or(Decimal::MAX);
             }
         }
     }
 }
 
+impl OrderBook {
+    pub fn update_with(
+        &mut self,
+        level_three_message: &LevelThreeMessage,
+    ) -> Result<Message, Error> {
+        let touched = (!self.tracking.watch_list.is_empty() || self.tracking.deltas.is_enabled())
+            .then(|| self.touched_levels(level_three_message));
+        let sizes = touched
+            .filter(|_| self.tracking.deltas.is_enabled())
+            .map(|touched| self.level_sizes(&touched));
+        let top = self.top_if_tracked();
+        let message = self.apply(level_three_message)?;
+
+        if let Some(touched) = touched {
+            self.refresh_watch_list_at(&touched);
+        }
+
+        if let Some(sizes) = sizes {
+            self.track_deltas(&sizes);
+        }
+
+        if let Some(top) = top {
+            self.track_bbo(top);
+        }
+
+        self.validate_if_due()?;
+
+        Ok(message)
+    }
+
+    /// Apply a message from the `full` channel. Unsequenced messages leave
+    /// the book untouched and return `None`.
+    pub fn update_with_full(
+        &mut self,
+        full_message: &FullMessage,
+    ) -> Result<Option<Message>, Error> {
+        full_message
+            .to_level_three()
+            .map(|message| self.update_with(&message))
+            .transpose()
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn updated_at(&self) -> OffsetDateTime {
+        self.updated_at
+    }
+
+    /// Get the highest bid level, if any bids are resting.
+    pub fn best_bid(&self) -> Option<Level> {
+        self.bids
+            .get(&self.best_bid)
+            .map(|orders| Level::new(self.best_bid, orders))
+    }
+
+    /// Get the lowest ask level, if any asks are resting.
+    pub fn best_ask(&self) -> Option<Level> {
+        self.asks
+            .get(&self.best_ask)
+            .map(|orders| Level::new(self.best_ask, orders))
+    }
+
+    /// Get the difference between the best ask and the best bid.
+    pub fn spread(&self) -> Option<Decimal> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+
+        ask.price.checked_sub(bid.price)
+    }
+
+    /// Get the midpoint between the best bid and the best ask.
+    pub fn mid(&self) -> Option<Decimal> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+
+        bid.price.checked_add(ask.price)?.checked_div(Decimal::TWO)
+    }
+
+    /// Iterate over the price levels on one side of the book, best price first.
+    pub fn levels(&self, side: Side) -> Box<dyn Iterator<Item = Level> + '_> {
+        match side {
+            Side::Buy => Box::new(
+                self.bids
+                    .iter()
+                    .rev()
+                    .map(|(price, orders)| Level::new(*price, orders)),
+            ),
+            Side::Sell => Box::new(
+                self.asks
+                    .iter()
+                    .map(|(price, orders)| Level::new(*price, orders)),
+            ),
+        }
+    }
+
+    /// Get up to `n` price levels on one side of the book, best price first.
+    pub fn top_levels(&self, side: Side, n: usize) -> Vec<Level> {
+        self.levels(side).take(n).collect()
+    }
+
+    /// Get the price levels on one side of the book whose prices fall within
+    /// `[low, high]`, best price first.
+    pub fn levels_within(&self, side: Side, low: Decimal, high: Decimal) -> Vec<Level> {
+        if low > high {
+            return vec![];
+        }
+
+        let halfbook = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        let levels = halfbook
+            .range(low..=high)
+            .map(|(price, orders)| Level::new(*price, orders));
+
+        match side {
+            Side::Buy => levels.rev().collect(),
+            Side::Sell => levels.collect(),
+        }
+    }
+
+    /// Get the price levels on one side of the book that lie within `bps`
+    /// basis points of the mid price, best price first.
+    pub fn levels_within_bps(&self, side: Side, bps: Decimal) -> Vec<Level> {
+        let Some(mid) = self.mid() else {
+            return vec![];
+        };
+        let Some(offset) = mid
+            .checked_mul(bps)
+            .and_then(|offset| offset.checked_div(Decimal::from(10_000)))
+        else {
+            return vec![];
+        };
+
+        match side {
+            Side::Buy => self.levels_within(side, mid.saturating_sub(offset), mid),
+            Side::Sell => self.levels_within(side, mid, mid.saturating_add(offset)),
+        }
+    }
+
+    /// Iterate over the orders queued at a price level in time priority.
+    pub fn orders_at(&self, side: Side, price: Decimal) -> impl Iterator<Item = &Order> {
+        let halfbook = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+
+        halfbook
+            .get(&price)
+            .into_iter()
+            .flat_map(|orders| orders.iter())
+    }
+
+    /// Look up the side and price of a resting order.
+    pub fn order(&self, order_id: Uuid) -> Option<(Side, Decimal, &Order)> {
+        let (side, price, handle) = *self.index.get(&order_id)?;
+        let halfbook = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        let order = halfbook.get(&price)?.get(handle)?;
+
+        Some((side, price, order))
+    }
+
+    /// Get the number of resting orders in the book.
+    pub fn order_count(&self) -> usize {
+        self.index.len()
+    }
+}
+
+impl OrderBook {
+    fn from_product_book(product: Product, product_book: ProductBook) -> Result<Self, Error> {
+        // Setting up order id-price index.
+        let mut index = HashMap::with_capacity(16_384);
+
+        // Set up the bids.
+        let mut bids = BTreeMap::<Decimal, Orders>::new();
+
+        for (price, size, id) in product_book.bids {
+            let handle = bids
+                .entry(price)
+                .or_default()
+                .insert(Order::new(id, size))?;
+
+            index.insert(id, (Side::Buy, price, handle));
+        }
+
+        // Set up the asks.
+        let mut asks = BTreeMap::<Decimal, Orders>::new();
+
+        for (price, size, id) in product_book.asks {
+            let handle = asks
+                .entry(price)
+                .or_default()
+                .insert(Order::new(id, size))?;
+
+            index.insert(id, (Side::Sell, price, handle));
+        }
+
+        Ok(Self {
+            product,
+            best_ask: *asks.keys().next().unwrap_or(&Decimal::MAX),
+            best_bid: *bids.keys().next_back().unwrap_or(&Decimal::ZERO),
+            asks,
+            bids,
+            sequence: product_book.sequence,
+            updated_at: product_book.time,
+            index,
+            tracking: Tracking::default(),
+        })
+    }
+
+    pub fn to_compact(&self) -> CompactOrderBook {
+        CompactOrderBook {
+            product: self.product.clone(),
+            asks: self
+                .asks
+                .clone()
+                .into_iter()
+                .map(|(price, orders)| (price.into(), CompactOrders::from(orders)))
+                .collect(),
+            bids: self
+                .bids
+                .clone()
+                .into_iter()
+                .map(|(price, orders)| (price.into(), CompactOrders::from(orders)))
+                .collect(),
+            sequence: self.sequence,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
 impl TryFrom<CompactOrderBook> for OrderBook {
     type Error = Error;
 
@@ -508,6 +1026,7 @@ impl TryFrom<CompactOrderBook> for OrderBook {
             sequence: compact_book.sequence,
             updated_at: compact_book.updated_at,
             index: HashMap::new(),
+            tracking: Tracking::default(),
         };
 
         for (price, orders) in compact_book.asks {
@@ -522,54 +1041,203 @@ impl TryFrom<CompactOrderBook> for OrderBook {
             }
         }
 
-        Ok(order_book)
+        Ok(order_book)
+    }
+}
+
+const MAX_RESYNC_ATTEMPTS: usize = 3;
+
+pub struct ConnectedOrderBook {
+    pub order_book: OrderBook,
+    websocket: Channel<LevelThreeMessage>,
+    connector: Connector,
+    resync: bool,
+    reconnect: Option<ReconnectPolicy>,
+    connection_state: watch::Sender<ConnectionState>,
+    reconciler: Option<Reconciler>,
+    drift: watch::Sender<Option<Drift>>,
+}
+
+impl ConnectedOrderBook {
+    pub fn sequence(&self) -> u64 {
+        self.order_book.sequence
+    }
+
+    pub fn updated_at(&self) -> OffsetDateTime {
+        self.order_book.updated_at
+    }
+
+    /// Subscribe to connection state transitions.
+    pub fn connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.connection_state.subscribe()
+    }
+
+    /// Read the next message from the websocket and apply it to the book.
+    ///
+    /// With resynchronization enabled, a message that reveals a sequence gap or a
+    /// diverged book triggers a rebuild from a fresh snapshot, and this method
+    /// returns `Message::Resynced` once the book has caught up with the feed.
+    /// With reconnection enabled, a failed websocket is replaced by a new one
+    /// and this method returns `Message::Reconnected` once the book is synced.
+    ///
+    /// With BBO or delta tracking enabled, the changes made by applied
+    /// messages are collected on the book for `OrderBook::drain_bbo_changes`
+    /// and `OrderBook::drain_deltas`. A rebuild isn't reported as changes, so
+    /// read the top of book or the levels again after one.
+    pub async fn next_message(&mut self) -> Result<Message, Error> {
+        loop {
+            let message = match self.websocket.next().await {
+                Ok(message) => message,
+                Err(error) if self.reconnect.is_some() => return self.reconnect(error).await,
+                Err(error) => return Err(error),
+            };
+
+            match self.order_book.update_with(&message) {
+                Ok(update) => {
+                    self.reconcile(message);
+
+                    return Ok(update);
+                }
+                Err(error) if self.resync && error.is_divergence() => {
+                    // Stale messages can be skipped without rebuilding.
+                    if matches!(error, Error::OutOfSequence)
+                        && message.sequence() <= self.order_book.sequence
+                    {
+                        continue;
+                    }
+
+                    warn!(%error, sequence = message.sequence(), "Resynchronizing order book");
+
+                    return match self.resync(message).await {
+                        Err(error) if self.reconnect.is_some() => self.reconnect(error).await,
+                        result => result,
+                    };
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    async fn resync(&mut self, message: LevelThreeMessage) -> Result<Message, Error> {
+        let product_id = self.order_book.product.id.clone();
+        let mut resync = Resync::new(self.order_book.sequence, message);
+
+        for attempt in 1..=MAX_RESYNC_ATTEMPTS {
+            debug!(attempt, "Fetching level-three order book snapshot");
+            let mut fetch = tokio::spawn({
+                let connector = self.connector.clone();
+                let product_id = product_id.clone();
+
+                async move { connector.fetch_product_book(product_id.as_str()).await }
+            });
+
+            // Buffer live messages while the snapshot is in flight, without
+            // waiting on the feed once it has landed.
+            let product_book = loop {
+                tokio::select! {
+                    fetched = &mut fetch => break fetched??,
+                    message = self.websocket.next() => {
+                        if let Err(error) = message.and_then(|message| resync.push(message)) {
+                            fetch.abort();
+
+                            return Err(error);
+                        }
+                    }
+                }
+            };
+
+            // The snapshot must not predate the first buffered message.
+            if resync.predates(&product_book) {
+                warn!(attempt, "Snapshot predates buffered messages");
+                continue;
+            }
+
+            // And the buffer must reach the snapshot.
+            while !resync.reaches(&product_book) {
+                resync.push(self.websocket.next().await?)?;
+            }
+
+            match resync.replay(self.order_book.product.clone(), product_book) {
+                Ok((order_book, message)) => {
+                    self.replace_order_book(order_book);
+
+                    return Ok(message);
+                }
+                Err(error) => warn!(%error, attempt, "Failed to replay resync buffer"),
+            }
+        }
+
+        Err(Error::unavailable("synchronized order book"))
+    }
+
+    async fn reconnect(&mut self, error: Error) -> Result<Message, Error> {
+        let policy = self.reconnect.ok_or(error)?;
+        let from = self.order_book.sequence;
+
+        // The channel closes itself on read errors and timeouts, but not on
+        // undecodable payloads.
+        let _ = self.websocket.close().await;
+
+        self.set_connection_state(ConnectionState::Disconnected);
+
+        let mut attempt = 1;
+
+        while policy.allows(attempt) {
+            self.set_connection_state(ConnectionState::Reconnecting { attempt });
+
+            let delay = policy.delay(attempt);
+
+            debug!(attempt, ?delay, "Reconnecting order book");
+            sleep(delay).await;
+
+            match self.connector.sync(self.order_book.product.clone()).await {
+                Ok((order_book, websocket)) => {
+                    self.websocket = websocket;
+                    self.replace_order_book(order_book);
+                    self.set_connection_state(ConnectionState::Connected);
+
+                    return Ok(Message::Reconnected {
+                        from,
+                        to: self.order_book.sequence,
+                    });
+                }
+                Err(error) => warn!(%error, attempt, "Failed to reconnect order book"),
+            }
+
+            attempt += 1;
+        }
+
+        self.set_connection_state(ConnectionState::Failed);
+
+        Err(Error::unavailable("order book connection"))
     }
-}
 
-pub struct ConnectedOrderBook {
-    pub order_book: OrderBook,
-    websocket: Channel<LevelThreeMessage>,
-}
+    /// Swap in a rebuilt order book, carrying over any watched orders.
+    fn replace_order_book(&mut self, mut order_book: OrderBook) {
+        order_book.tracking = std::mem::take(&mut self.order_book.tracking);
+        order_book.refresh_watch_list();
 
-impl ConnectedOrderBook {
-    pub fn sequence(&self) -> u64 {
-        self.order_book.sequence
-    }
+        if let Some(reconciler) = self.reconciler.as_mut() {
+            reconciler.reset();
+        }
 
-    pub fn updated_at(&self) -> OffsetDateTime {
-        self.order_book.updated_at
+        self.order_book = order_book;
     }
 
-    pub async fn next_message(&mut self) -> Result<Message, Error> {
-        let message = self.websocket.next().await?;
+    fn set_connection_state(&self, state: ConnectionState) {
+        debug!(?state, "Order book connection state changed");
 
-        self.order_book.update_with(&message)
+        self.connection_state.send_replace(state);
     }
 
     pub async fn shutdown(&mut self) -> Result<(), Error> {
+        self.set_connection_state(ConnectionState::Disconnected);
+
         self.websocket.close().await
     }
 
     pub fn to_compact(&self) -> CompactOrderBook {
-        CompactOrderBook {
-            product: self.order_book.product.clone(),
-            asks: self
-                .order_book
-                .asks
-                .clone()
-                .into_iter()
-                .map(|(price, orders)| (price.into(), CompactOrders::from(orders)))
-                .collect(),
-            bids: self
-                .order_book
-                .bids
-                .clone()
-                .into_iter()
-                .map(|(price, orders)| (price.into(), CompactOrders::from(orders)))
-                .collect(),
-            sequence: self.order_book.sequence,
-            updated_at: self.order_book.updated_at,
-        }
+        self.order_book.to_compact()
     }
 }
 
@@ -579,6 +1247,7 @@ pub struct OrderBookBuilder {
     signer: Option<Signer>,
     passphrase: Option<String>,
     product_id: Option<SmartString<LazyCompact>>,
+    product_ids: Vec<SmartString<LazyCompact>>,
     product: Option<Product>,
     domain: Option<String>,
     port: Option<u16>,
@@ -588,6 +1257,13 @@ pub struct OrderBookBuilder {
     book_backoff_bucket: Option<BackOffBucket>,
     websocket_token_bucket: Option<TokenBucket>,
     tls_config: Option<Arc<ClientConfig>>,
+    resync: bool,
+    reconnect: Option<ReconnectPolicy>,
+    validation_interval: Option<usize>,
+    bbo_tracking: bool,
+    delta_tracking: bool,
+    reconcile: Option<ReconcilePolicy>,
+    warm_start: Option<WarmStart>,
 }
 
 impl OrderBookBuilder {
@@ -628,6 +1304,18 @@ impl OrderBookBuilder {
         self
     }
 
+    /// Add products to a multi-product book. See `build_multi`.
+    pub fn with_product_ids<I, S>(mut self, product_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<SmartString<LazyCompact>>,
+    {
+        self.product_ids
+            .extend(product_ids.into_iter().map(|product_id| product_id.into()));
+
+        self
+    }
+
     pub fn with_product(mut self, product: Product) -> Self {
         self.product = Some(product);
 
@@ -677,151 +1365,232 @@ impl OrderBookBuilder {
         self
     }
 
-    pub async fn build(self) -> Result<ConnectedOrderBook, Error> {
+    /// Rebuild the book from a fresh snapshot instead of returning an error
+    /// when the feed and the book diverge.
+    pub fn with_resync(mut self, resync: bool) -> Self {
+        self.resync = resync;
+
+        self
+    }
+
+    /// Reconnect and resynchronize the book when the websocket fails.
+    pub fn with_reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+
+        self
+    }
+
+    /// Start from a persisted book and journal instead of a REST snapshot
+    /// whenever the live feed continues where the journal ends.
+    pub fn with_warm_start(mut self, warm_start: WarmStart) -> Self {
+        self.warm_start = Some(warm_start);
+
+        self
+    }
+
+    /// Periodically compare the live book with a REST snapshot in the
+    /// background. See `ConnectedOrderBook::drift` and `MultiOrderBook::drift`.
+    pub fn with_reconciliation(mut self, policy: ReconcilePolicy) -> Self {
+        self.reconcile = Some(policy);
+
+        self
+    }
+
+    /// Validate the book's invariants after every `interval` messages. See
+    /// `OrderBook::set_validation_interval`.
+    pub fn with_validation_interval(mut self, interval: usize) -> Self {
+        self.validation_interval = Some(interval);
+
+        self
+    }
+
+    /// Collect BBO changes on the built books. See
+    /// `OrderBook::set_bbo_tracking`.
+    pub fn with_bbo_tracking(mut self, enabled: bool) -> Self {
+        self.bbo_tracking = enabled;
+
+        self
+    }
+
+    /// Collect level deltas on the built books. See
+    /// `OrderBook::set_delta_tracking`.
+    pub fn with_delta_tracking(mut self, enabled: bool) -> Self {
+        self.delta_tracking = enabled;
+
+        self
+    }
+
+    fn connector(&mut self) -> Result<Connector, Error> {
         debug!("Ensuring all required helper variables are present");
         let key = self
             .key
+            .take()
             .ok_or_else(|| Error::unavailable("authentication key"))?;
         let signer = self
             .signer
+            .take()
             .ok_or_else(|| Error::unavailable("authentication signer"))?;
         let passphrase = self
             .passphrase
+            .take()
             .ok_or_else(|| Error::unavailable("authentication passphrase"))?;
         let domain = self
             .domain
-            .unwrap_or_else(|| String::from("ws-direct.exchange.coinbase.com"));
-        let port = self.port.unwrap_or(443);
-        let product_id = self
-            .product_id
-            .ok_or_else(|| Error::unavailable("product id"))?;
+            .take()
+            .unwrap_or_else(|| String::from(DIRECT_FEED_DOMAIN));
+        let port = self.port.unwrap_or(FEED_PORT);
         let cache_delay = self
             .cache_delay
             .unwrap_or_else(|| Duration::from_millis(5_000));
-        let book_backoff_bucket = self.book_backoff_bucket.unwrap_or_else(|| {
+        let book_backoff_bucket = self.book_backoff_bucket.take().unwrap_or_else(|| {
             warn!("Using local backoff token bucket -- this is only acceptible in tests!");
 
             BackOffBucket::new(Duration::from_secs(10), Duration::from_secs(3_600))
         });
         let websocket_token_bucket = self
             .websocket_token_bucket
+            .take()
             .unwrap_or_else(|| TokenBucket::new(1_000, Duration::from_millis(100)));
 
         debug!("Setting up http client");
-        let http_client = match self.rest_client {
+        let http_client = match self.rest_client.take() {
             Some(rest_client) => rest_client,
             None => ClientBuilder::new()
                 .with_token_bucket(
                     self.rest_token_bucket
+                        .take()
                         .unwrap_or_else(|| TokenBucket::new(15, Duration::from_millis(100))),
                 )
                 .build()?,
         };
 
-        let product = match self.product {
-            Some(product) => product,
-            None => {
+        Ok(Connector {
+            key,
+            signer,
+            passphrase,
+            domain,
+            port,
+            cache_delay,
+            http_client,
+            book_backoff_bucket,
+            websocket_token_bucket,
+            tls_config: self.tls_config.take(),
+        })
+    }
+
+    pub async fn build(mut self) -> Result<ConnectedOrderBook, Error> {
+        let warm_start = self.warm_start.take();
+        let product_id = self
+            .product_id
+            .take()
+            .or_else(|| Some(warm_start.as_ref()?.product().id.clone()))
+            .ok_or_else(|| Error::unavailable("product id"))?;
+        let connector = self.connector()?;
+        let product = match (self.product.take(), warm_start.as_ref()) {
+            (Some(product), _) => product,
+            (None, Some(warm_start)) => warm_start.product().clone(),
+            (None, None) => {
                 debug!("Fetching product metadata");
-                http_client.get_single_product(product_id.as_str()).await?
+                connector
+                    .http_client
+                    .get_single_product(product_id.as_str())
+                    .await?
             }
         };
 
-        debug!("Establishing websocket channel");
-        let channel = ChannelBuilder::default()
-            .with_key(key)
-            .with_signer(signer)
-            .with_passphrase(passphrase)
-            .with_endpoint(domain, port)
-            .with_product_id(product_id.as_str())
-            .with_token_bucket(websocket_token_bucket)
-            .with_tls_config(self.tls_config)
-            .connect::<LevelThreeMessage>()
-            .await?;
-
-        debug!("Caching messages in separate task");
-        let caching_channel = channel.cache().await;
-
-        sleep(cache_delay).await;
-
-        debug!("Fetching level-three order book snapshot");
-        let book_token = book_backoff_bucket.get_token().await?;
-        let product_book = http_client.get_product_book(product_id).await;
-
-        // Return the token before handling a potential error.
-        book_backoff_bucket.return_token(book_token).await;
-
-        // Handle the potential error.
-        let product_book = product_book?;
-
-        // Setting up order id-price index.
-        let mut index = HashMap::with_capacity(16_384);
-
-        // Set up the bids.
-        let mut bids = BTreeMap::<Decimal, Orders>::new();
-
-        for (price, size, id) in product_book.bids {
-            let order = Order::new(id, size);
-
-            if let Some(orders) = bids.get_mut(&price) {
-                orders.insert(order)?;
-            } else {
-                bids.insert(price, Orders::from(order));
-            }
-
-            index.insert(id, (Side::Buy, price));
+        if product.id != product_id
+            || warm_start
+                .as_ref()
+                .is_some_and(|warm_start| warm_start.product().id != product_id)
+        {
+            return Err(Error::invalid("product id"));
         }
 
-        // Set up the asks.
-        let mut asks = BTreeMap::<Decimal, Orders>::new();
+        let resumed = match warm_start {
+            Some(warm_start) => connector.warm_start(warm_start).await?,
+            None => None,
+        };
+        let (mut order_book, websocket) = match resumed {
+            Some(resumed) => resumed,
+            None => connector.sync(product).await?,
+        };
+        let (connection_state, _) = watch::channel(ConnectionState::Connected);
 
-        for (price, size, id) in product_book.asks {
-            let order = Order::new(id, size);
+        let (drift, _) = watch::channel(None);
+        let reconciler = self.reconcile.map(|policy| {
+            Reconciler::spawn(connector.clone(), order_book.product.id.as_str(), policy)
+        });
 
-            if let Some(orders) = asks.get_mut(&price) {
-                orders.insert(order)?;
-            } else {
-                asks.insert(price, Orders::from(order));
-            }
+        order_book.set_validation_interval(self.validation_interval);
+        order_book.set_bbo_tracking(self.bbo_tracking);
+        order_book.set_delta_tracking(self.delta_tracking);
+
+        Ok(ConnectedOrderBook {
+            order_book,
+            websocket,
+            connector,
+            resync: self.resync,
+            reconnect: self.reconnect,
+            connection_state,
+            reconciler,
+            drift,
+        })
+    }
 
-            index.insert(id, (Side::Sell, price));
+    /// Build one book per product over a single level-three subscription.
+    /// Books are bootstrapped in the background and become available as
+    /// `MultiOrderBook::next_message` reports them synced. Warm starts aren't
+    /// supported for multiple products.
+    pub async fn build_multi(mut self) -> Result<MultiOrderBook, Error> {
+        if self.warm_start.is_some() {
+            return Err(Error::invalid("warm start needs a single product"));
         }
 
-        debug!("Creating order book");
-        let mut order_book = ConnectedOrderBook {
-            order_book: OrderBook {
-                product,
-                best_ask: *asks.keys().next().unwrap_or(&Decimal::ZERO),
-                best_bid: *bids.keys().rev().next().unwrap_or(&Decimal::ZERO),
-                asks,
-                bids,
-                sequence: product_book.sequence,
-                updated_at: product_book.time,
-                index,
-            },
-            websocket: caching_channel.join().await?,
-        };
-
-        debug!("Extracting last cached message");
-        let last_cached = order_book
-            .websocket
-            .last_cached()
-            .ok_or_else(|| Error::InsufficientCacheDelay)?;
+        let product_ids = self
+            .product_id
+            .take()
+            .into_iter()
+            .chain(std::mem::take(&mut self.product_ids))
+            .collect::<Vec<_>>();
 
-        // Make sure the last cached message is dated after the order book snapshot.
-        if last_cached.sequence() < order_book.order_book.sequence {
-            return Err(Error::InsufficientCacheDelay);
+        if product_ids.is_empty() {
+            return Err(Error::unavailable("product ids"));
         }
 
-        debug!(?last_cached, "Updating order book with cached messages");
-        for message in order_book.websocket.cached_items() {
-            match order_book.order_book.update_with(&message) {
-                Ok(_) => {}
-                Err(Error::OutOfSequence) => {}
-                Err(error) => return Err(error),
+        let connector = self.connector()?;
+        let mut products = Vec::with_capacity(product_ids.len());
+
+        for product_id in product_ids {
+            match self.product.take() {
+                Some(product) if product.id == product_id => products.push(product),
+                product => {
+                    self.product = product;
+
+                    debug!(%product_id, "Fetching product metadata");
+                    products.push(
+                        connector
+                            .http_client
+                            .get_single_product(product_id.as_str())
+                            .await?,
+                    );
+                }
             }
         }
 
-        Ok(order_book)
+        MultiOrderBook::connect(
+            connector,
+            products,
+            self.resync,
+            self.reconnect,
+            self.reconcile,
+            Tracking {
+                validation: Validation::new(self.validation_interval),
+                bbo: BboTracker::new(self.bbo_tracking),
+                deltas: DeltaTracker::new(self.delta_tracking),
+                ..Default::default()
+            },
+        )
+        .await
     }
 }
 
@@ -853,9 +1622,11 @@ impl From<Order> for CompactOrder {
 struct CompactOrders(VecDeque<CompactOrder>);
 
 impl CompactOrders {
-    pub fn total_size(&self) -> Decimal {
-        self.0.iter().fold(Decimal::ZERO, |total, order| {
-            total.saturating_add(order.0.1.0) // can technically overflow
+    pub fn total_size(&self) -> Result<Decimal, Error> {
+        self.0.iter().try_fold(Decimal::ZERO, |total, order| {
+            total
+                .checked_add(order.0.1.0)
+                .ok_or_else(|| Error::math("size overflows total size", None))
         })
     }
 }
@@ -864,9 +1635,8 @@ impl From<Orders> for CompactOrders {
     fn from(orders: Orders) -> Self {
         Self(
             orders
-                .queue
-                .into_iter()
-                .map(|order| CompactOrder::from(order))
+                .iter()
+                .map(|order| CompactOrder::from(*order))
                 .collect(),
         )
     }
@@ -881,7 +1651,7 @@ pub struct CompactOrderBook {
     updated_at: OffsetDateTime,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Message {
     Open {
         sequence: u64,
@@ -916,9 +1686,18 @@ pub enum Message {
         time: OffsetDateTime,
         order_id: Uuid,
     },
+    Resynced {
+        from: u64,
+        to: u64,
+    },
+    Reconnected {
+        from: u64,
+        to: u64,
+    },
 }
 
-#[derive(Debug, Clone, Copy)]
+/// A trade, where `side` is the side of the aggressing (taker) order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Tick {
     pub price: Decimal,
     pub size: Decimal,
@@ -945,10 +1724,37 @@ impl Tick {
     }
 }
 
+impl Message {
+    /// Get the trade described by a `Match`. The book reports the maker's
+    /// side, so the aggressor is on the opposite side.
+    pub fn tick(&self) -> Option<Tick> {
+        match self {
+            Self::Match {
+                sequence,
+                time,
+                side,
+                price,
+                size,
+                ..
+            } => Some(Tick::new(
+                *price,
+                *size,
+                match side {
+                    Side::Buy => Side::Sell,
+                    Side::Sell => Side::Buy,
+                },
+                *time,
+                *sequence,
+            )),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::exchange::rest::products::ProductBook;
+    use crate::test_util::{make_empty_order_book, make_order_book_with_orders, make_product};
     use sqlx::{Pool as SqlxPool, Postgres, types::Json};
     use tracing::{error, info};
 
@@ -1035,27 +1841,21 @@ mod test {
         let mut asks = BTreeMap::<Decimal, Orders>::new();
 
         for (price, size, id) in product_book.bids {
-            let order = Order::new(id, size);
+            let handle = bids
+                .entry(price)
+                .or_default()
+                .insert(Order::new(id, size))?;
 
-            if let Some(orders) = bids.get_mut(&price) {
-                orders.insert(order)?;
-            } else {
-                bids.insert(price, Orders::from(order));
-            }
-
-            index.insert(id, (Side::Buy, price));
+            index.insert(id, (Side::Buy, price, handle));
         }
 
         for (price, size, id) in product_book.asks {
-            let order = Order::new(id, size);
-
-            if let Some(orders) = asks.get_mut(&price) {
-                orders.insert(order)?;
-            } else {
-                asks.insert(price, Orders::from(order));
-            }
+            let handle = asks
+                .entry(price)
+                .or_default()
+                .insert(Order::new(id, size))?;
 
-            index.insert(id, (Side::Sell, price));
+            index.insert(id, (Side::Sell, price, handle));
         }
 
         let end_order_book = OrderBook {
@@ -1067,6 +1867,7 @@ mod test {
             sequence: product_book.sequence,
             updated_at: product_book.time,
             index,
+            tracking: Tracking::default(),
         };
 
         #[derive(Debug, Clone, Copy, sqlx::Type)]
@@ -1198,100 +1999,6 @@ mod test {
         Ok(())
     }
 
-    // ==================== Test Helpers ====================
-
-    fn make_product() -> Product {
-        use crate::exchange::rest::products::Status;
-
-        Product {
-            auction_mode: false,
-            base_currency: "BTC".into(),
-            base_increment: Decimal::new(1, 8),
-            cancel_only: false,
-            display_name: "BTC-USD".into(),
-            fx_stablecoin: false,
-            high_bid_limit_percentage: "".into(),
-            id: "BTC-USD".into(),
-            limit_only: false,
-            margin_enabled: false,
-            max_slippage_percentage: Decimal::ZERO,
-            min_market_funds: Decimal::ONE,
-            post_only: false,
-            quote_currency: "USD".into(),
-            quote_increment: Decimal::new(1, 2),
-            status: Status::Online,
-            status_message: "".into(),
-            trading_disabled: false,
-        }
-    }
-
-    fn make_empty_order_book(sequence: u64) -> OrderBook {
-        OrderBook {
-            product: make_product(),
-            best_ask: Decimal::MAX,
-            best_bid: Decimal::ZERO,
-            asks: BTreeMap::new(),
-            bids: BTreeMap::new(),
-            sequence,
-            updated_at: OffsetDateTime::now_utc(),
-            index: HashMap::new(),
-        }
-    }
-
-    fn make_order_book_with_orders(
-        sequence: u64,
-        bids: Vec<(Decimal, Uuid, Decimal)>, // (price, order_id, size)
-        asks: Vec<(Decimal, Uuid, Decimal)>,
-    ) -> OrderBook {
-        let mut index = HashMap::new();
-        let mut bids_map: BTreeMap<Decimal, Orders> = BTreeMap::new();
-        let mut asks_map: BTreeMap<Decimal, Orders> = BTreeMap::new();
-
-        for (price, order_id, size) in bids {
-            index.insert(order_id, (Side::Buy, price));
-
-            let order = Order::new(order_id, size);
-
-            if let Some(orders) = bids_map.get_mut(&price) {
-                orders.insert(order).unwrap();
-            } else {
-                bids_map.insert(price, Orders::from(order));
-            }
-        }
-
-        for (price, order_id, size) in asks {
-            index.insert(order_id, (Side::Sell, price));
-
-            let order = Order::new(order_id, size);
-
-            if let Some(orders) = asks_map.get_mut(&price) {
-                orders.insert(order).unwrap();
-            } else {
-                asks_map.insert(price, Orders::from(order));
-            }
-        }
-
-        let best_bid = bids_map
-            .last_key_value()
-            .map(|(p, _)| *p)
-            .unwrap_or(Decimal::ZERO);
-        let best_ask = asks_map
-            .first_key_value()
-            .map(|(p, _)| *p)
-            .unwrap_or(Decimal::MAX);
-
-        OrderBook {
-            product: make_product(),
-            best_ask,
-            best_bid,
-            asks: asks_map,
-            bids: bids_map,
-            sequence,
-            updated_at: OffsetDateTime::now_utc(),
-            index,
-        }
-    }
-
     // ==================== Open Message Tests ====================
 
     #[test]
@@ -1369,7 +2076,7 @@ mod test {
 
         let orders = book.bids.get(&price).unwrap();
 
-        assert_eq!(orders.queue.len(), 2);
+        assert_eq!(orders.len(), 2);
         assert_eq!(orders.total_size, Decimal::new(3, 0)); // 1 + 2
     }
 
@@ -1445,8 +2152,8 @@ mod test {
         book.update_with(&message).unwrap();
 
         let orders = book.asks.get(&price).unwrap();
-        assert_eq!(orders.queue.len(), 1);
-        assert_eq!(orders.queue[0].size, Decimal::new(7, 0)); // 10 - 3 = 7
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders.iter().next().unwrap().size, Decimal::new(7, 0)); // 10 - 3 = 7
         assert!(book.index.contains_key(&maker_id)); // Still in index
     }
 
@@ -1701,7 +2408,7 @@ mod test {
         book.update_with(&message).unwrap();
 
         let orders = book.bids.get(&price).unwrap();
-        assert_eq!(orders.queue[0].size, new_size);
+        assert_eq!(orders.iter().next().unwrap().size, new_size);
         assert_eq!(orders.total_size, new_size);
     }
 
@@ -1727,7 +2434,7 @@ mod test {
         book.update_with(&message).unwrap();
 
         let orders = book.bids.get(&price).unwrap();
-        assert_eq!(orders.queue[0].size, new_size);
+        assert_eq!(orders.iter().next().unwrap().size, new_size);
         assert!(book.index.contains_key(&order_id));
     }
 
@@ -1968,10 +2675,21 @@ mod test {
         let book = OrderBook::try_from(compact).unwrap();
 
         let orders = book.bids.get(&price).unwrap();
-        assert_eq!(orders.queue.len(), 3);
+        assert_eq!(orders.len(), 3);
         assert_eq!(orders.total_size, Decimal::new(6, 0)); // 1 + 2 + 3
     }
 
+    #[test]
+    fn compact_total_size_fails_on_overflow() {
+        let orders = CompactOrders(VecDeque::from([
+            CompactOrder((Uuid::new_v4(), Decimal::MAX.into())),
+            CompactOrder((Uuid::new_v4(), Decimal::ONE.into())),
+        ]));
+
+        assert!(matches!(orders.total_size(), Err(Error::Math { .. })));
+        assert!(Orders::try_from(orders).is_err());
+    }
+
     // ==================== Edge Cases ====================
 
     #[test]
@@ -1999,9 +2717,9 @@ mod test {
         }
 
         let orders = book.bids.get(&price).unwrap();
-        assert_eq!(orders.queue[0].id, order1); // First in
-        assert_eq!(orders.queue[1].id, order2);
-        assert_eq!(orders.queue[2].id, order3); // Last in
+        assert_eq!(orders.iter().next().unwrap().id, order1); // First in
+        assert_eq!(orders.iter().nth(1).unwrap().id, order2);
+        assert_eq!(orders.iter().nth(2).unwrap().id, order3); // Last in
     }
 
     #[test]
@@ -2042,8 +2760,8 @@ mod test {
 
         let orders = book.asks.get(&price).unwrap();
 
-        assert_eq!(orders.queue.len(), 1);
-        assert_eq!(orders.queue[0].id, order2); // order1 removed, order2 remains
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders.iter().next().unwrap().id, order2); // order1 removed, order2 remains
     }
 
     #[test]
@@ -2080,4 +2798,251 @@ mod test {
         // best_bid should be highest
         assert_eq!(book.best_bid, Decimal::new(9950, 2));
     }
+
+    #[test]
+    fn level_queue_matches_a_plain_fifo() {
+        use rand::{Rng, SeedableRng, rngs::StdRng};
+        use rust_decimal::prelude::ToPrimitive;
+
+        // The queue as it was kept before handles: a deque searched by id.
+        let mut rng = StdRng::seed_from_u64(19);
+        let mut expected: VecDeque<Order> = VecDeque::new();
+        let mut orders = Orders::default();
+        let mut handles: HashMap<Uuid, Handle> = HashMap::new();
+
+        for _ in 0..10_000 {
+            let victim = match expected.is_empty() {
+                true => None,
+                false => Some(expected[rng.random_range(0..expected.len())]),
+            };
+
+            match (rng.random_range(0..4), victim) {
+                (0, _) | (_, None) => {
+                    let order = Order::new(Uuid::new_v4(), Decimal::from(rng.random_range(1..100)));
+
+                    handles.insert(order.id, orders.insert(order).unwrap());
+                    expected.push_back(order);
+                }
+                (1, Some(victim)) => {
+                    let handle = handles.remove(&victim.id).unwrap();
+
+                    assert_eq!(orders.delete(handle, victim.id).unwrap(), victim);
+                    expected.retain(|order| order.id != victim.id);
+                }
+                (2, Some(victim)) => {
+                    let size = Decimal::from(rng.random_range(1..=victim.size.to_u64().unwrap()));
+                    let handle = handles[&victim.id];
+                    let deleted = orders.reduce_or_delete(handle, victim.id, size).unwrap();
+                    let index = expected
+                        .iter()
+                        .position(|order| order.id == victim.id)
+                        .unwrap();
+
+                    expected[index].size -= size;
+
+                    if expected[index].size.is_zero() {
+                        expected.remove(index);
+                        handles.remove(&victim.id);
+                    }
+
+                    assert_eq!(deleted, !handles.contains_key(&victim.id));
+                }
+                (_, Some(victim)) => {
+                    let size = Decimal::from(rng.random_range(1..=victim.size.to_u64().unwrap()));
+                    let index = expected
+                        .iter()
+                        .position(|order| order.id == victim.id)
+                        .unwrap();
+
+                    orders.resize(handles[&victim.id], victim.id, size).unwrap();
+                    expected[index].size = size;
+                }
+            }
+
+            assert_eq!(orders.len(), expected.len());
+            assert!(orders.iter().eq(expected.iter()));
+            assert_eq!(
+                orders.total_size,
+                expected.iter().map(|order| order.size).sum::<Decimal>()
+            );
+        }
+
+        // A stale handle is rejected even once its slot is reused.
+        let order = Order::new(Uuid::new_v4(), Decimal::ONE);
+        let handle = orders.insert(order).unwrap();
+
+        orders.delete(handle, order.id).unwrap();
+        orders
+            .insert(Order::new(Uuid::new_v4(), Decimal::ONE))
+            .unwrap();
+
+        assert!(matches!(
+            orders.delete(handle, order.id),
+            Err(Error::OrderDoesNotExist)
+        ));
+    }
+
+    #[test]
+    fn full_channel_maintains_order_book() {
+        let mut book = make_empty_order_book(9);
+        let messages = [
+            r#"{"type":"received","time":"2014-11-07T08:19:27.028459Z","product_id":"BTC-USD","sequence":10,"order_id":"d50ec984-77a8-460a-b958-66f114b0de9b","size":"1.34","price":"502.1","side":"buy","order_type":"limit"}"#,
+            r#"{"type":"open","time":"2014-11-07T08:19:27.028459Z","product_id":"BTC-USD","sequence":11,"order_id":"d50ec984-77a8-460a-b958-66f114b0de9b","price":"502.1","remaining_size":"1.34","side":"buy"}"#,
+            r#"{"type":"activate","product_id":"BTC-USD","timestamp":"1483736448.299000","order_id":"7b52009b-64fd-0a2a-49e6-d8a939753077","stop_type":"entry","side":"buy","stop_price":"80","size":"2"}"#,
+            r#"{"type":"match","trade_id":10,"sequence":12,"maker_order_id":"d50ec984-77a8-460a-b958-66f114b0de9b","taker_order_id":"132fb6ae-456b-4654-b4e0-d681ac05cea1","time":"2014-11-07T08:19:27.028459Z","product_id":"BTC-USD","size":"0.34","price":"502.1","side":"buy"}"#,
+            r#"{"type":"change","reason":"STP","time":"2014-11-07T08:19:27.028459Z","sequence":13,"order_id":"d50ec984-77a8-460a-b958-66f114b0de9b","side":"buy","product_id":"BTC-USD","new_size":"0.5","old_size":"1","price":"502.1"}"#,
+            r#"{"type":"change","time":"2014-11-07T08:19:27.028459Z","sequence":14,"order_id":"132fb6ae-456b-4654-b4e0-d681ac05cea1","side":"sell","product_id":"BTC-USD","new_funds":"5","old_funds":"12","price":null}"#,
+        ];
+
+        for message in messages {
+            book.update_with_full(&serde_json::from_str(message).unwrap())
+                .unwrap();
+        }
+
+        assert_eq!(book.sequence(), 14);
+        assert_eq!(
+            book.best_bid(),
+            Some(Level {
+                price: Decimal::new(5021, 1),
+                size: Decimal::new(5, 1),
+                order_count: 1,
+            })
+        );
+
+        let done = r#"{"type":"done","time":"2014-11-07T08:19:27.028459Z","product_id":"BTC-USD","sequence":15,"price":"502.1","order_id":"d50ec984-77a8-460a-b958-66f114b0de9b","reason":"canceled","side":"buy","remaining_size":"0.5"}"#;
+
+        book.update_with_full(&serde_json::from_str(done).unwrap())
+            .unwrap();
+
+        assert_eq!(book.order_count(), 0);
+    }
+
+    // ==================== Depth Query Tests ====================
+
+    fn make_depth_order_book() -> OrderBook {
+        make_order_book_with_orders(
+            1000,
+            vec![
+                (Decimal::new(9900, 2), Uuid::new_v4(), Decimal::ONE), // 99.00
+                (Decimal::new(9900, 2), Uuid::new_v4(), Decimal::TWO), // 99.00
+                (Decimal::new(9800, 2), Uuid::new_v4(), Decimal::ONE), // 98.00
+                (Decimal::new(9000, 2), Uuid::new_v4(), Decimal::ONE), // 90.00
+            ],
+            vec![
+                (Decimal::new(10100, 2), Uuid::new_v4(), Decimal::ONE), // 101.00
+                (Decimal::new(10200, 2), Uuid::new_v4(), Decimal::TWO), // 102.00
+                (Decimal::new(11000, 2), Uuid::new_v4(), Decimal::ONE), // 110.00
+            ],
+        )
+    }
+
+    #[test]
+    fn best_levels_spread_and_mid() {
+        let book = make_depth_order_book();
+
+        let best_bid = book.best_bid().unwrap();
+        let best_ask = book.best_ask().unwrap();
+
+        assert_eq!(best_bid.price, Decimal::new(9900, 2));
+        assert_eq!(best_bid.size, Decimal::new(3, 0));
+        assert_eq!(best_bid.order_count, 2);
+        assert_eq!(best_ask.price, Decimal::new(10100, 2));
+        assert_eq!(book.spread(), Some(Decimal::new(200, 2)));
+        assert_eq!(book.mid(), Some(Decimal::new(10000, 2)));
+    }
+
+    #[test]
+    fn empty_book_has_no_best_levels() {
+        let book = make_empty_order_book(1000);
+
+        assert!(book.best_bid().is_none());
+        assert!(book.best_ask().is_none());
+        assert!(book.spread().is_none());
+        assert!(book.mid().is_none());
+        assert!(book.top_levels(Side::Buy, 5).is_empty());
+    }
+
+    #[test]
+    fn top_levels_are_ordered_best_first() {
+        let book = make_depth_order_book();
+
+        let bids = book.top_levels(Side::Buy, 2);
+        let asks = book.top_levels(Side::Sell, 10);
+
+        assert_eq!(bids.len(), 2);
+        assert_eq!(bids[0].price, Decimal::new(9900, 2));
+        assert_eq!(bids[1].price, Decimal::new(9800, 2));
+        assert_eq!(asks.len(), 3);
+        assert_eq!(asks[0].price, Decimal::new(10100, 2));
+        assert_eq!(asks[2].price, Decimal::new(11000, 2));
+    }
+
+    #[test]
+    fn levels_within_price_band_and_bps() {
+        let book = make_depth_order_book();
+
+        let bids = book.levels_within(Side::Buy, Decimal::new(9500, 2), Decimal::new(9950, 2));
+
+        assert_eq!(bids.len(), 2);
+        assert_eq!(bids[0].price, Decimal::new(9900, 2));
+
+        // 500 bps of a 100.00 mid => [95.00, 105.00]
+        let bids = book.levels_within_bps(Side::Buy, Decimal::from(500));
+        let asks = book.levels_within_bps(Side::Sell, Decimal::from(500));
+
+        assert_eq!(bids.len(), 2);
+        assert_eq!(asks.len(), 2);
+        assert_eq!(asks[1].price, Decimal::new(10200, 2));
+    }
+
+    #[test]
+    fn orders_at_iterates_in_time_priority() {
+        let order1 = Uuid::new_v4();
+        let order2 = Uuid::new_v4();
+        let price = Decimal::new(9900, 2);
+
+        let book = make_order_book_with_orders(
+            1000,
+            vec![(price, order1, Decimal::ONE), (price, order2, Decimal::TWO)],
+            vec![],
+        );
+
+        let ids: Vec<_> = book
+            .orders_at(Side::Buy, price)
+            .map(|order| order.id)
+            .collect();
+
+        assert_eq!(ids, vec![order1, order2]);
+        assert_eq!(book.orders_at(Side::Sell, price).count(), 0);
+        assert_eq!(book.order(order2).unwrap().2.size, Decimal::TWO);
+        assert_eq!(book.order_count(), 2);
+    }
+
+    // ==================== Snapshot Tests ====================
+
+    #[test]
+    fn from_product_book_builds_book_with_sentinels() {
+        let bid1 = Uuid::new_v4();
+        let bid2 = Uuid::new_v4();
+        let product_book = ProductBook {
+            asks: vec![],
+            auction: None,
+            auction_mode: false,
+            bids: vec![
+                (Decimal::new(9900, 2), Decimal::ONE, bid1),
+                (Decimal::new(9900, 2), Decimal::TWO, bid2),
+                (Decimal::new(9800, 2), Decimal::ONE, Uuid::new_v4()),
+            ],
+            sequence: 5000,
+            time: OffsetDateTime::now_utc(),
+        };
+
+        let book = OrderBook::from_product_book(make_product(), product_book).unwrap();
+
+        assert_eq!(book.best_bid, Decimal::new(9900, 2));
+        assert_eq!(book.best_ask, Decimal::MAX);
+        assert_eq!(book.sequence, 5000);
+        assert_eq!(book.queue_position(bid2).unwrap().orders_ahead, 1);
+        assert_eq!(book.order_count(), 3);
+    }
 }
@@ -0,0 +1,729 @@
+use crate::{
+    Message, OrderBook, Tracking,
+    connection::{ConnectionState, Connector, MAX_RESYNC_BUFFER, ReconnectPolicy},
+    exchange::{
+        common::Error,
+        rest::products::{Product, ProductBook},
+        websocket::channels::{Channel, level_three::Message as LevelThreeMessage},
+    },
+    reconcile::{Drift, ReconcilePolicy, Reconciler},
+};
+use smartstring::{LazyCompact, SmartString};
+use std::collections::{HashMap, VecDeque};
+use tokio::{sync::watch, task::JoinHandle, time::sleep};
+use tracing::{debug, trace, warn};
+
+/// A book update tagged with the product it belongs to.
+#[derive(Debug, Clone)]
+pub struct ProductMessage {
+    pub product_id: SmartString<LazyCompact>,
+    pub message: Message,
+}
+
+/// A book that is waiting for a snapshot and buffering live messages until it
+/// can be rebuilt.
+struct Bootstrap {
+    product: Product,
+    from: u64,
+    buffer: Vec<LevelThreeMessage>,
+    fetch: Option<JoinHandle<Result<ProductBook, Error>>>,
+    snapshot: Option<ProductBook>,
+    attempt: usize,
+    tracking: Tracking,
+}
+
+impl Bootstrap {
+    fn new(product: Product, tracking: Tracking) -> Self {
+        Self {
+            product,
+            from: 0,
+            buffer: vec![],
+            fetch: None,
+            snapshot: None,
+            attempt: 0,
+            tracking,
+        }
+    }
+
+    fn from_order_book(order_book: OrderBook) -> Self {
+        Self {
+            product: order_book.product,
+            from: order_book.sequence,
+            buffer: vec![],
+            fetch: None,
+            snapshot: None,
+            attempt: 0,
+            tracking: order_book.tracking,
+        }
+    }
+
+    /// Buffer a live message. A full buffer is dropped along with any snapshot,
+    /// so the bootstrap starts over from the next message.
+    fn push(&mut self, message: LevelThreeMessage) {
+        if self.buffer.len() >= MAX_RESYNC_BUFFER {
+            warn!(product_id = %self.product.id, "Resync buffer is full, restarting bootstrap");
+
+            if let Some(fetch) = self.fetch.take() {
+                fetch.abort();
+            }
+
+            self.buffer.clear();
+            self.snapshot = None;
+        }
+
+        self.buffer.push(message);
+    }
+
+    /// Whether the snapshot can be brought up to date with the buffer alone.
+    fn is_ready(&mut self) -> bool {
+        let Some(snapshot) = &self.snapshot else {
+            return false;
+        };
+
+        // A snapshot that predates the buffer leaves a gap, so fetch another.
+        if self
+            .buffer
+            .first()
+            .is_some_and(|message| message.sequence() > snapshot.sequence + 1)
+        {
+            debug!(product_id = %self.product.id, "Snapshot predates buffered messages");
+            self.snapshot = None;
+
+            return false;
+        }
+
+        // Otherwise wait until the buffer reaches the snapshot.
+        self.buffer
+            .last()
+            .is_some_and(|message| message.sequence() >= snapshot.sequence)
+    }
+
+    fn replay(&mut self) -> Result<OrderBook, Error> {
+        let snapshot = self.snapshot.take().ok_or_else(|| Error::Impossible)?;
+        let snapshot_sequence = snapshot.sequence;
+        let mut order_book = OrderBook::from_product_book(self.product.clone(), snapshot)?;
+
+        // Later snapshots can only be newer, so older messages are never needed again.
+        self.buffer
+            .retain(|message| message.sequence() > snapshot_sequence);
+
+        for message in self.buffer.iter() {
+            order_book.update_with(message)?;
+        }
+
+        order_book.tracking = std::mem::take(&mut self.tracking);
+        order_book.refresh_watch_list();
+
+        Ok(order_book)
+    }
+}
+
+enum Book {
+    Live(OrderBook),
+    Syncing(Bootstrap),
+}
+
+/// The background reconciliation of one product's book.
+struct Reconciliation {
+    reconciler: Reconciler,
+    drift: watch::Sender<Option<Drift>>,
+}
+
+impl Reconciliation {
+    /// Report the drift of a finished diff, rebuilding the book if needed.
+    fn reconcile(&mut self, order_book: &mut OrderBook, message: LevelThreeMessage) {
+        let Some((drift, rebuilt)) = self.reconciler.poll(order_book, message) else {
+            return;
+        };
+
+        if let Some(mut rebuilt) = rebuilt {
+            rebuilt.tracking = std::mem::take(&mut order_book.tracking);
+            rebuilt.refresh_watch_list();
+            *order_book = rebuilt;
+        }
+
+        self.drift.send_replace(Some(drift));
+    }
+}
+
+/// The routing and bootstrapping state of a `MultiOrderBook`, independent of
+/// its connection.
+#[derive(Default)]
+struct Books {
+    books: HashMap<SmartString<LazyCompact>, Book>,
+    synced: VecDeque<ProductMessage>,
+    resync: bool,
+    reconciliations: HashMap<SmartString<LazyCompact>, Reconciliation>,
+}
+
+impl Books {
+    fn new(products: Vec<Product>, tracking: Tracking, resync: bool) -> Self {
+        Self {
+            books: products
+                .into_iter()
+                .map(|product| {
+                    (
+                        product.id.clone(),
+                        Book::Syncing(Bootstrap::new(product, tracking.clone())),
+                    )
+                })
+                .collect(),
+            synced: VecDeque::new(),
+            resync,
+            reconciliations: HashMap::new(),
+        }
+    }
+
+    /// Start reconciling every book in the background.
+    fn reconcile(&mut self, connector: &Connector, policy: ReconcilePolicy) {
+        self.reconciliations = self
+            .books
+            .keys()
+            .map(|product_id| {
+                let reconciliation = Reconciliation {
+                    reconciler: Reconciler::spawn(connector.clone(), product_id, policy),
+                    drift: watch::channel(None).0,
+                };
+
+                (product_id.clone(), reconciliation)
+            })
+            .collect();
+    }
+
+    /// Apply a message to its product's book, or buffer it if the book is syncing.
+    fn route(&mut self, message: LevelThreeMessage) -> Result<Option<ProductMessage>, Error> {
+        let diverged = match self.books.get_mut(message.product_id()) {
+            None => {
+                trace!(
+                    product_id = message.product_id(),
+                    "Ignoring unknown product"
+                );
+
+                return Ok(None);
+            }
+            Some(Book::Syncing(bootstrap)) => {
+                bootstrap.push(message);
+
+                return Ok(None);
+            }
+            Some(Book::Live(order_book)) => match order_book.update_with(&message) {
+                Ok(update) => {
+                    if let Some(reconciliation) = self.reconciliations.get_mut(message.product_id())
+                    {
+                        reconciliation.reconcile(order_book, message);
+                    }
+
+                    return Ok(Some(ProductMessage {
+                        product_id: order_book.product.id.clone(),
+                        message: update,
+                    }));
+                }
+                Err(error) if !self.resync => return Err(error),
+                // Stale messages can be skipped without rebuilding.
+                Err(Error::OutOfSequence) if message.sequence() <= order_book.sequence => {
+                    return Ok(None);
+                }
+                Err(error) if error.is_divergence() => {
+                    warn!(%error, product_id = message.product_id(), "Resynchronizing order book");
+
+                    message
+                }
+                Err(error) => return Err(error),
+            },
+        };
+
+        if let Some((product_id, Book::Live(order_book))) =
+            self.books.remove_entry(diverged.product_id())
+        {
+            if let Some(reconciliation) = self.reconciliations.get_mut(&product_id) {
+                reconciliation.reconciler.reset();
+            }
+
+            let mut bootstrap = Bootstrap::from_order_book(order_book);
+
+            bootstrap.buffer.push(diverged);
+            self.books.insert(product_id, Book::Syncing(bootstrap));
+        }
+
+        Ok(None)
+    }
+
+    /// Start snapshot requests for syncing books that don't have one in flight.
+    fn spawn_fetches(&mut self, connector: &Connector) {
+        for book in self.books.values_mut() {
+            let Book::Syncing(bootstrap) = book else {
+                continue;
+            };
+
+            if bootstrap.fetch.is_some() || bootstrap.snapshot.is_some() {
+                continue;
+            }
+
+            bootstrap.attempt += 1;
+            debug!(
+                product_id = %bootstrap.product.id,
+                attempt = bootstrap.attempt,
+                "Fetching level-three order book snapshot"
+            );
+            bootstrap.fetch = Some(tokio::spawn({
+                let connector = connector.clone();
+                let product_id = bootstrap.product.id.clone();
+
+                async move { connector.fetch_product_book(product_id.as_str()).await }
+            }));
+        }
+    }
+
+    /// Collect the snapshots whose requests have finished.
+    async fn collect_fetches(&mut self) -> Result<(), Error> {
+        for book in self.books.values_mut() {
+            let Book::Syncing(bootstrap) = book else {
+                continue;
+            };
+
+            if !bootstrap
+                .fetch
+                .as_ref()
+                .is_some_and(|fetch| fetch.is_finished())
+            {
+                continue;
+            }
+
+            if let Some(fetch) = bootstrap.fetch.take() {
+                match fetch.await? {
+                    Ok(snapshot) => bootstrap.snapshot = Some(snapshot),
+                    Err(error) => {
+                        warn!(%error, product_id = %bootstrap.product.id, "Failed to fetch snapshot")
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rebuild every syncing book whose snapshot has been caught up by the buffer.
+    fn complete(&mut self) {
+        let ready = self
+            .books
+            .iter_mut()
+            .filter_map(|(product_id, book)| match book {
+                Book::Syncing(bootstrap) => bootstrap.is_ready().then(|| product_id.clone()),
+                Book::Live(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        for product_id in ready {
+            let Some(Book::Syncing(mut bootstrap)) = self.books.remove(&product_id) else {
+                continue;
+            };
+
+            match bootstrap.replay() {
+                Ok(order_book) => {
+                    self.synced.push_back(ProductMessage {
+                        product_id: product_id.clone(),
+                        message: Message::Resynced {
+                            from: bootstrap.from,
+                            to: order_book.sequence,
+                        },
+                    });
+                    self.books.insert(product_id, Book::Live(order_book));
+                }
+                Err(error) => {
+                    warn!(%error, %product_id, "Failed to replay buffered messages");
+                    self.books.insert(product_id, Book::Syncing(bootstrap));
+                }
+            }
+        }
+    }
+
+    /// Put every book back into bootstrapping after the feed was interrupted.
+    fn restart(&mut self) {
+        for reconciliation in self.reconciliations.values_mut() {
+            reconciliation.reconciler.reset();
+        }
+
+        self.books = std::mem::take(&mut self.books)
+            .into_iter()
+            .map(|(product_id, book)| {
+                let bootstrap = match book {
+                    Book::Live(order_book) => Bootstrap::from_order_book(order_book),
+                    Book::Syncing(mut bootstrap) => {
+                        // The buffer no longer connects to the new feed.
+                        if let Some(fetch) = bootstrap.fetch.take() {
+                            fetch.abort();
+                        }
+
+                        bootstrap.buffer.clear();
+                        bootstrap.snapshot = None;
+                        bootstrap
+                    }
+                };
+
+                (product_id, Book::Syncing(bootstrap))
+            })
+            .collect();
+    }
+}
+
+/// Order books for several products maintained over one level-three
+/// subscription. Each book is bootstrapped on its own and, with resync
+/// enabled, resynchronized on its own, so a gap in one product doesn't
+/// interrupt the others.
+pub struct MultiOrderBook {
+    books: Books,
+    websocket: Channel<LevelThreeMessage>,
+    connector: Connector,
+    reconnect: Option<ReconnectPolicy>,
+    connection_state: watch::Sender<ConnectionState>,
+}
+
+impl MultiOrderBook {
+    pub(crate) async fn connect(
+        connector: Connector,
+        products: Vec<Product>,
+        resync: bool,
+        reconnect: Option<ReconnectPolicy>,
+        reconcile: Option<ReconcilePolicy>,
+        tracking: Tracking,
+    ) -> Result<Self, Error> {
+        let product_ids = products
+            .iter()
+            .map(|product| product.id.clone())
+            .collect::<Vec<_>>();
+
+        debug!(?product_ids, "Establishing websocket channel");
+        let websocket = connector.connect::<LevelThreeMessage>(&product_ids).await?;
+        let mut books = Books::new(products, tracking, resync);
+
+        if let Some(policy) = reconcile {
+            books.reconcile(&connector, policy);
+        }
+
+        books.spawn_fetches(&connector);
+
+        let (connection_state, _) = watch::channel(ConnectionState::Connected);
+
+        Ok(Self {
+            books,
+            websocket,
+            connector,
+            reconnect,
+            connection_state,
+        })
+    }
+
+    /// Get a product's book, if it is currently synced.
+    pub fn book(&self, product_id: &str) -> Option<&OrderBook> {
+        match self.books.books.get(product_id)? {
+            Book::Live(order_book) => Some(order_book),
+            Book::Syncing(_) => None,
+        }
+    }
+
+    pub fn book_mut(&mut self, product_id: &str) -> Option<&mut OrderBook> {
+        match self.books.books.get_mut(product_id)? {
+            Book::Live(order_book) => Some(order_book),
+            Book::Syncing(_) => None,
+        }
+    }
+
+    pub fn product_ids(&self) -> impl Iterator<Item = &str> {
+        self.books
+            .books
+            .keys()
+            .map(|product_id| product_id.as_str())
+    }
+
+    pub fn is_synced(&self, product_id: &str) -> bool {
+        self.book(product_id).is_some()
+    }
+
+    /// Subscribe to connection state transitions.
+    pub fn connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.connection_state.subscribe()
+    }
+
+    /// Subscribe to the drift reports of a product's background
+    /// reconciliation, if it is enabled.
+    pub fn drift(&self, product_id: &str) -> Option<watch::Receiver<Option<Drift>>> {
+        self.books
+            .reconciliations
+            .get(product_id)
+            .map(|reconciliation| reconciliation.drift.subscribe())
+    }
+
+    /// Read messages until one of the books reports an update.
+    ///
+    /// A book that finishes bootstrapping reports `Message::Resynced`, where
+    /// `from` is zero for the initial sync. Without resync, a book that
+    /// diverges from the feed fails this method instead.
+    ///
+    /// With BBO or delta tracking enabled, each live book collects its changes
+    /// for `OrderBook::drain_bbo_changes` and `OrderBook::drain_deltas`.
+    pub async fn next_message(&mut self) -> Result<ProductMessage, Error> {
+        loop {
+            if let Some(message) = self.books.synced.pop_front() {
+                return Ok(message);
+            }
+
+            let message = match self.websocket.next().await {
+                Ok(message) => message,
+                Err(error) => {
+                    self.reconnect(error).await?;
+
+                    continue;
+                }
+            };
+
+            if let Some(message) = self.books.route(message)? {
+                return Ok(message);
+            }
+
+            self.books.spawn_fetches(&self.connector);
+            self.books.collect_fetches().await?;
+            self.books.complete();
+        }
+    }
+
+    async fn reconnect(&mut self, error: Error) -> Result<(), Error> {
+        let policy = self.reconnect.ok_or(error)?;
+        let product_ids = self.books.books.keys().cloned().collect::<Vec<_>>();
+
+        // The channel closes itself on read errors and timeouts, but not on
+        // undecodable payloads.
+        let _ = self.websocket.close().await;
+
+        self.set_connection_state(ConnectionState::Disconnected);
+
+        let mut attempt = 1;
+
+        while policy.allows(attempt) {
+            self.set_connection_state(ConnectionState::Reconnecting { attempt });
+
+            let delay = policy.delay(attempt);
+
+            debug!(attempt, ?delay, "Reconnecting multi-product order book");
+            sleep(delay).await;
+
+            match self
+                .connector
+                .connect::<LevelThreeMessage>(&product_ids)
+                .await
+            {
+                Ok(websocket) => {
+                    self.websocket = websocket;
+                    self.books.restart();
+                    self.books.spawn_fetches(&self.connector);
+                    self.set_connection_state(ConnectionState::Connected);
+
+                    return Ok(());
+                }
+                Err(error) => warn!(%error, attempt, "Failed to reconnect order books"),
+            }
+
+            attempt += 1;
+        }
+
+        self.set_connection_state(ConnectionState::Failed);
+
+        Err(Error::unavailable("order book connection"))
+    }
+
+    fn set_connection_state(&self, state: ConnectionState) {
+        debug!(?state, "Order book connection state changed");
+
+        self.connection_state.send_replace(state);
+    }
+
+    pub async fn shutdown(&mut self) -> Result<(), Error> {
+        self.set_connection_state(ConnectionState::Disconnected);
+
+        self.websocket.close().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        exchange::websocket::channels::level_three::Side,
+        test_util::{make_order_book_with_orders, make_product},
+    };
+    use rust_decimal::Decimal;
+    use time::OffsetDateTime;
+    use uuid::Uuid;
+
+    fn make_eth_product() -> Product {
+        Product {
+            id: "ETH-USD".into(),
+            base_currency: "ETH".into(),
+            display_name: "ETH-USD".into(),
+            ..make_product()
+        }
+    }
+
+    fn make_open(product_id: &str, sequence: u64, price: Decimal) -> LevelThreeMessage {
+        LevelThreeMessage::Open {
+            product_id: product_id.into(),
+            sequence,
+            order_id: Uuid::new_v4(),
+            side: Side::Buy,
+            price,
+            size: Decimal::ONE,
+            time: OffsetDateTime::now_utc(),
+        }
+    }
+
+    fn make_books() -> Books {
+        let btc = make_order_book_with_orders(1000, vec![], vec![]);
+        let mut eth = make_order_book_with_orders(500, vec![], vec![]);
+
+        eth.product = make_eth_product();
+
+        Books {
+            books: HashMap::from([
+                ("BTC-USD".into(), Book::Live(btc)),
+                ("ETH-USD".into(), Book::Live(eth)),
+            ]),
+            synced: VecDeque::new(),
+            resync: true,
+            reconciliations: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn routes_messages_by_product_id() {
+        let mut books = make_books();
+
+        let btc = books
+            .route(make_open("BTC-USD", 1001, Decimal::new(9900, 2)))
+            .unwrap()
+            .unwrap();
+        let eth = books
+            .route(make_open("ETH-USD", 501, Decimal::new(300, 0)))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(btc.product_id, "BTC-USD");
+        assert_eq!(eth.product_id, "ETH-USD");
+        assert!(
+            books
+                .route(make_open("SOL-USD", 1, Decimal::ONE))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn gap_resyncs_only_the_affected_book() {
+        let mut books = make_books();
+
+        // ETH skips sequence 501.
+        assert!(
+            books
+                .route(make_open("ETH-USD", 502, Decimal::new(300, 0)))
+                .unwrap()
+                .is_none()
+        );
+        assert!(matches!(books.books["ETH-USD"], Book::Syncing(_)));
+
+        // BTC keeps flowing, while ETH buffers.
+        assert!(
+            books
+                .route(make_open("BTC-USD", 1001, Decimal::new(9900, 2)))
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            books
+                .route(make_open("ETH-USD", 503, Decimal::new(301, 0)))
+                .unwrap()
+                .is_none()
+        );
+
+        // A snapshot at 502 plus the buffered 503 completes the resync.
+        if let Some(Book::Syncing(bootstrap)) = books.books.get_mut("ETH-USD") {
+            bootstrap.snapshot = Some(ProductBook {
+                asks: vec![],
+                auction: None,
+                auction_mode: false,
+                bids: vec![(Decimal::new(300, 0), Decimal::ONE, Uuid::new_v4())],
+                sequence: 502,
+                time: OffsetDateTime::now_utc(),
+            });
+        }
+
+        books.complete();
+
+        let synced = books.synced.pop_front().unwrap();
+
+        assert_eq!(synced.product_id, "ETH-USD");
+        assert!(matches!(
+            synced.message,
+            Message::Resynced { from: 500, to: 503 }
+        ));
+
+        let Book::Live(eth) = &books.books["ETH-USD"] else {
+            panic!("ETH-USD should be live");
+        };
+
+        assert_eq!(eth.best_bid().unwrap().price, Decimal::new(301, 0));
+        assert_eq!(eth.order_count(), 2);
+    }
+
+    #[test]
+    fn snapshot_older_than_buffer_is_discarded() {
+        let mut bootstrap = Bootstrap::new(make_product(), Tracking::default());
+
+        bootstrap
+            .buffer
+            .push(make_open("BTC-USD", 1010, Decimal::new(9900, 2)));
+        bootstrap.snapshot = Some(ProductBook {
+            asks: vec![],
+            auction: None,
+            auction_mode: false,
+            bids: vec![],
+            sequence: 1000,
+            time: OffsetDateTime::now_utc(),
+        });
+
+        assert!(!bootstrap.is_ready());
+        assert!(bootstrap.snapshot.is_none());
+    }
+
+    #[test]
+    fn gap_fails_without_resync() {
+        let mut books = make_books();
+
+        books.resync = false;
+
+        assert!(matches!(
+            books.route(make_open("ETH-USD", 502, Decimal::new(300, 0))),
+            Err(Error::OutOfSequence)
+        ));
+        assert!(matches!(books.books["ETH-USD"], Book::Live(_)));
+    }
+
+    #[test]
+    fn full_buffer_restarts_the_bootstrap() {
+        let mut bootstrap = Bootstrap::new(make_product(), Tracking::default());
+
+        for sequence in 1..=MAX_RESYNC_BUFFER as u64 {
+            bootstrap.push(make_open("BTC-USD", sequence, Decimal::ONE));
+        }
+
+        bootstrap.snapshot = Some(ProductBook {
+            asks: vec![],
+            auction: None,
+            auction_mode: false,
+            bids: vec![],
+            sequence: 10,
+            time: OffsetDateTime::now_utc(),
+        });
+        bootstrap.push(make_open("BTC-USD", 0, Decimal::ONE));
+
+        assert_eq!(bootstrap.buffer.len(), 1);
+        assert!(bootstrap.snapshot.is_none());
+    }
+}
@@ -0,0 +1,384 @@
+use crate::{
+    OrderBook,
+    exchange::websocket::channels::level_three::{Message as LevelThreeMessage, Side},
+};
+use rust_decimal::Decimal;
+use std::collections::{HashMap, VecDeque};
+use tracing::warn;
+use uuid::Uuid;
+
+/// The most queue events kept between drains. Older events are dropped first.
+pub const MAX_QUEUE_EVENTS: usize = 4096;
+
+/// Where an order sits in the FIFO queue at its price level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuePosition {
+    pub side: Side,
+    pub price: Decimal,
+    pub orders_ahead: usize,
+    pub size_ahead: Decimal,
+    pub size: Decimal,
+}
+
+impl QueuePosition {
+    fn is_ahead_of(&self, other: &Self) -> bool {
+        self.side == other.side
+            && self.price == other.price
+            && (self.orders_ahead < other.orders_ahead || self.size_ahead < other.size_ahead)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueEvent {
+    Advanced {
+        sequence: u64,
+        order_id: Uuid,
+        from: QueuePosition,
+        to: QueuePosition,
+    },
+    Removed {
+        sequence: u64,
+        order_id: Uuid,
+        last: QueuePosition,
+    },
+}
+
+/// Orders whose queue position is tracked across book updates, together with
+/// the events raised since the last drain.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct WatchList {
+    positions: HashMap<Uuid, Option<QueuePosition>>,
+    events: VecDeque<QueueEvent>,
+}
+
+impl WatchList {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    fn push(&mut self, event: QueueEvent) {
+        if self.events.len() == MAX_QUEUE_EVENTS {
+            warn!("Queue events aren't being drained, dropping the oldest");
+            self.events.pop_front();
+        }
+
+        self.events.push_back(event);
+    }
+}
+
+/// The price levels a message can reorder, looked up before it's applied.
+pub(crate) type TouchedLevels = [Option<(Side, Decimal)>; 2];
+
+impl OrderBook {
+    /// Get the queue position of a resting order.
+    pub fn queue_position(&self, order_id: Uuid) -> Option<QueuePosition> {
+        let (side, price, _) = *self.index.get(&order_id)?;
+        let mut size_ahead = Decimal::ZERO;
+
+        for (orders_ahead, order) in self.orders_at(side, price).enumerate() {
+            if order.id == order_id {
+                return Some(QueuePosition {
+                    side,
+                    price,
+                    orders_ahead,
+                    size_ahead,
+                    size: order.size,
+                });
+            }
+
+            size_ahead = size_ahead.saturating_add(order.size);
+        }
+
+        None
+    }
+
+    /// Start tracking an order's queue position. The order doesn't need to be
+    /// in the book yet; tracking begins once it's opened.
+    pub fn watch(&mut self, order_id: Uuid) -> Option<QueuePosition> {
+        let position = self.queue_position(order_id);
+
+        self.tracking
+            .watch_list
+            .positions
+            .insert(order_id, position);
+
+        position
+    }
+
+    pub fn unwatch(&mut self, order_id: Uuid) {
+        self.tracking.watch_list.positions.remove(&order_id);
+    }
+
+    /// Take the queue events raised since the last call, oldest first. At most
+    /// `MAX_QUEUE_EVENTS` are kept between calls.
+    pub fn drain_queue_events(&mut self) -> Vec<QueueEvent> {
+        self.tracking.watch_list.events.drain(..).collect()
+    }
+
+    pub(crate) fn touched_levels(&self, message: &LevelThreeMessage) -> TouchedLevels {
+        let level = |order_id| {
+            self.index
+                .get(order_id)
+                .map(|(side, price, _)| (*side, *price))
+        };
+
+        match message {
+            LevelThreeMessage::Open { side, price, .. } => [Some((*side, *price)), None],
+            LevelThreeMessage::Change {
+                order_id, price, ..
+            } => {
+                let old = level(order_id);
+
+                [old, old.map(|(side, _)| (side, *price))]
+            }
+            LevelThreeMessage::Match { maker_order_id, .. } => [level(maker_order_id), None],
+            LevelThreeMessage::Done { order_id, .. } => [level(order_id), None],
+            LevelThreeMessage::Noop { .. } => [None, None],
+        }
+    }
+
+    /// Recompute the positions of the watched orders resting at, or just
+    /// opened at, the levels an update touched.
+    pub(crate) fn refresh_watch_list_at(&mut self, levels: &TouchedLevels) {
+        let touched = |side, price| levels.contains(&Some((side, price)));
+        let order_ids = self
+            .tracking
+            .watch_list
+            .positions
+            .iter()
+            .filter(|(order_id, position)| match position {
+                Some(position) => touched(position.side, position.price),
+                None => self
+                    .index
+                    .get(order_id)
+                    .is_some_and(|(side, price, _)| touched(*side, *price)),
+            })
+            .map(|(order_id, _)| *order_id)
+            .collect::<Vec<_>>();
+
+        for order_id in order_ids {
+            self.refresh_position(order_id);
+        }
+    }
+
+    /// Recompute the positions of all watched orders.
+    pub(crate) fn refresh_watch_list(&mut self) {
+        let order_ids = self
+            .tracking
+            .watch_list
+            .positions
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+
+        for order_id in order_ids {
+            self.refresh_position(order_id);
+        }
+    }
+
+    fn refresh_position(&mut self, order_id: Uuid) {
+        let sequence = self.sequence;
+        let current = self.queue_position(order_id);
+        let previous = self
+            .tracking
+            .watch_list
+            .positions
+            .insert(order_id, current)
+            .flatten();
+
+        match (previous, current) {
+            (Some(from), Some(to)) if to.is_ahead_of(&from) => {
+                self.tracking.watch_list.push(QueueEvent::Advanced {
+                    sequence,
+                    order_id,
+                    from,
+                    to,
+                });
+            }
+            (Some(last), None) => {
+                self.tracking.watch_list.positions.remove(&order_id);
+                self.tracking.watch_list.push(QueueEvent::Removed {
+                    sequence,
+                    order_id,
+                    last,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        exchange::websocket::channels::level_three::Message as LevelThreeMessage,
+        test_util::make_order_book_with_orders,
+    };
+    use time::OffsetDateTime;
+
+    #[test]
+    fn queue_position_counts_orders_and_size_ahead() {
+        let order1 = Uuid::new_v4();
+        let order2 = Uuid::new_v4();
+        let order3 = Uuid::new_v4();
+        let price = Decimal::new(9900, 2);
+
+        let book = make_order_book_with_orders(
+            1000,
+            vec![
+                (price, order1, Decimal::ONE),
+                (price, order2, Decimal::TWO),
+                (price, order3, Decimal::ONE),
+            ],
+            vec![],
+        );
+
+        let position = book.queue_position(order3).unwrap();
+
+        assert_eq!(position.orders_ahead, 2);
+        assert_eq!(position.size_ahead, Decimal::new(3, 0));
+        assert_eq!(position.size, Decimal::ONE);
+        assert_eq!(book.queue_position(order1).unwrap().orders_ahead, 0);
+        assert!(book.queue_position(Uuid::new_v4()).is_none());
+    }
+
+    #[test]
+    fn watched_order_emits_advanced_and_removed_events() {
+        let order1 = Uuid::new_v4();
+        let order2 = Uuid::new_v4();
+        let price = Decimal::new(10000, 2);
+
+        let mut book = make_order_book_with_orders(
+            1000,
+            vec![],
+            vec![(price, order1, Decimal::TWO), (price, order2, Decimal::ONE)],
+        );
+
+        book.watch(order2);
+
+        // A partial fill of the order ahead moves the watched order forward.
+        book.update_with(&LevelThreeMessage::Match {
+            product_id: "BTC-USD".into(),
+            sequence: 1001,
+            maker_order_id: order1,
+            taker_order_id: Uuid::new_v4(),
+            price,
+            size: Decimal::ONE,
+            time: OffsetDateTime::now_utc(),
+        })
+        .unwrap();
+
+        // Cancelling the order ahead moves it to the front.
+        book.update_with(&LevelThreeMessage::Done {
+            product_id: "BTC-USD".into(),
+            sequence: 1002,
+            order_id: order1,
+            time: OffsetDateTime::now_utc(),
+        })
+        .unwrap();
+
+        // Filling the watched order removes it.
+        book.update_with(&LevelThreeMessage::Match {
+            product_id: "BTC-USD".into(),
+            sequence: 1003,
+            maker_order_id: order2,
+            taker_order_id: Uuid::new_v4(),
+            price,
+            size: Decimal::ONE,
+            time: OffsetDateTime::now_utc(),
+        })
+        .unwrap();
+
+        let events = book.drain_queue_events();
+
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            events[0],
+            QueueEvent::Advanced { sequence: 1001, to, .. } if to.size_ahead == Decimal::ONE
+        ));
+        assert!(matches!(
+            events[1],
+            QueueEvent::Advanced { sequence: 1002, to, .. } if to.orders_ahead == 0
+        ));
+        assert!(matches!(
+            events[2],
+            QueueEvent::Removed { sequence: 1003, .. }
+        ));
+        assert!(book.drain_queue_events().is_empty());
+    }
+
+    #[test]
+    fn watched_order_does_not_advance_when_requeued() {
+        let order1 = Uuid::new_v4();
+        let order2 = Uuid::new_v4();
+        let price = Decimal::new(9900, 2);
+
+        let mut book = make_order_book_with_orders(
+            1000,
+            vec![(price, order1, Decimal::ONE), (price, order2, Decimal::ONE)],
+            vec![],
+        );
+
+        book.watch(order1);
+
+        // A size increase loses time priority.
+        book.update_with(&LevelThreeMessage::Change {
+            product_id: "BTC-USD".into(),
+            sequence: 1001,
+            order_id: order1,
+            price,
+            size: Decimal::TWO,
+            time: OffsetDateTime::now_utc(),
+        })
+        .unwrap();
+
+        assert!(book.drain_queue_events().is_empty());
+        assert_eq!(book.queue_position(order1).unwrap().orders_ahead, 1);
+    }
+
+    #[test]
+    fn watched_order_is_tracked_once_opened_and_events_are_bounded() {
+        let order1 = Uuid::new_v4();
+        let order2 = Uuid::new_v4();
+        let price = Decimal::new(9900, 2);
+        let mut book =
+            make_order_book_with_orders(1000, vec![(price, order1, Decimal::ONE)], vec![]);
+
+        assert_eq!(book.watch(order2), None);
+
+        book.update_with(&LevelThreeMessage::Open {
+            product_id: "BTC-USD".into(),
+            sequence: 1001,
+            order_id: order2,
+            side: Side::Buy,
+            price,
+            size: Decimal::ONE,
+            time: OffsetDateTime::now_utc(),
+        })
+        .unwrap();
+
+        // Each resize of the order ahead advances the watched order by size.
+        for sequence in 1002..1002 + MAX_QUEUE_EVENTS as u64 + 10 {
+            let size = Decimal::new(1_000_000 - sequence as i64, 6);
+
+            book.update_with(&LevelThreeMessage::Change {
+                product_id: "BTC-USD".into(),
+                sequence,
+                order_id: order1,
+                price,
+                size,
+                time: OffsetDateTime::now_utc(),
+            })
+            .unwrap();
+        }
+
+        let events = book.drain_queue_events();
+
+        assert_eq!(events.len(), MAX_QUEUE_EVENTS);
+        assert!(matches!(
+            events[0],
+            QueueEvent::Advanced { sequence: 1012, .. }
+        ));
+    }
+}
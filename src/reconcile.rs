@@ -0,0 +1,549 @@
+use crate::{
+    ConnectedOrderBook, OrderBook,
+    connection::{Connector, MAX_RESYNC_BUFFER, Resync},
+    exchange::{
+        common::Error,
+        rest::products::ProductBook,
+        websocket::channels::level_three::{Message as LevelThreeMessage, Side},
+    },
+};
+use rust_decimal::Decimal;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, oneshot, oneshot::error::TryRecvError, watch},
+    task::JoinHandle,
+    time::sleep,
+};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// How often to check the live book against a REST snapshot, and whether to
+/// replace it with the snapshot when they disagree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconcilePolicy {
+    pub interval: Duration,
+    pub rebuild: bool,
+}
+
+impl ReconcilePolicy {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            rebuild: false,
+        }
+    }
+
+    pub fn with_rebuild(mut self, rebuild: bool) -> Self {
+        self.rebuild = rebuild;
+
+        self
+    }
+}
+
+/// The differences between the live book and a snapshot at the same sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Drift {
+    pub sequence: u64,
+    pub orders_compared: usize,
+    /// Orders in the snapshot but not in the book.
+    pub missing_orders: usize,
+    pub missing_size: Decimal,
+    /// Orders in the book but not in the snapshot.
+    pub unexpected_orders: usize,
+    pub unexpected_size: Decimal,
+    /// Orders in both with a different side, price or size.
+    pub mismatched_orders: usize,
+    /// Whether the book was replaced by the snapshot.
+    pub rebuilt: bool,
+}
+
+impl Drift {
+    pub fn is_clean(&self) -> bool {
+        self.missing_orders == 0 && self.unexpected_orders == 0 && self.mismatched_orders == 0
+    }
+}
+
+impl OrderBook {
+    /// Compare the book order by order with a snapshot at the same sequence.
+    pub fn diff(&self, snapshot: &ProductBook) -> Result<Drift, Error> {
+        if snapshot.sequence != self.sequence {
+            return Err(Error::OutOfSequence);
+        }
+
+        let expected = snapshot
+            .bids
+            .iter()
+            .map(|(price, size, order_id)| (*order_id, (Side::Buy, *price, *size)))
+            .chain(
+                snapshot
+                    .asks
+                    .iter()
+                    .map(|(price, size, order_id)| (*order_id, (Side::Sell, *price, *size))),
+            )
+            .collect();
+
+        Ok(self.diff_orders(expected))
+    }
+
+    /// Compare the book order by order with another at the same sequence.
+    pub fn diff_book(&self, other: &OrderBook) -> Result<Drift, Error> {
+        if other.sequence != self.sequence {
+            return Err(Error::OutOfSequence);
+        }
+
+        Ok(self.diff_orders(other.resting_orders().collect()))
+    }
+
+    /// Iterate over every resting order as its id, side, price and size.
+    fn resting_orders(&self) -> impl Iterator<Item = (Uuid, (Side, Decimal, Decimal))> + '_ {
+        [Side::Buy, Side::Sell].into_iter().flat_map(move |side| {
+            self.levels(side).flat_map(move |level| {
+                self.orders_at(side, level.price)
+                    .map(move |order| (order.id, (side, level.price, order.size)))
+            })
+        })
+    }
+
+    fn diff_orders(&self, expected: HashMap<Uuid, (Side, Decimal, Decimal)>) -> Drift {
+        let mut drift = Drift {
+            sequence: self.sequence,
+            orders_compared: expected.len(),
+            missing_orders: 0,
+            missing_size: Decimal::ZERO,
+            unexpected_orders: 0,
+            unexpected_size: Decimal::ZERO,
+            mismatched_orders: 0,
+            rebuilt: false,
+        };
+
+        for (order_id, (side, price, size)) in expected.iter() {
+            match self.order(*order_id) {
+                Some((actual_side, actual_price, order))
+                    if actual_side == *side && actual_price == *price && order.size == *size => {}
+                Some(_) => drift.mismatched_orders += 1,
+                None => {
+                    drift.missing_orders += 1;
+                    drift.missing_size = drift.missing_size.saturating_add(*size);
+                }
+            }
+        }
+
+        for (order_id, (_, _, size)) in self.resting_orders() {
+            if !expected.contains_key(&order_id) {
+                drift.unexpected_orders += 1;
+                drift.unexpected_size = drift.unexpected_size.saturating_add(size);
+            }
+        }
+
+        drift
+    }
+}
+
+/// A diff running on a copy of the book, and the messages applied to the live
+/// book since, to replay over the caught up snapshot if it has to be rebuilt.
+struct Diffing {
+    result: oneshot::Receiver<Result<(Drift, OrderBook), Error>>,
+    since: Option<Resync>,
+}
+
+/// Fetches snapshots in the background and holds the next one until the live
+/// book reaches its sequence. The most recent messages applied to the book
+/// are kept to bring a snapshot that arrives behind the book up to date.
+pub(crate) struct Reconciler {
+    rebuild: bool,
+    task: JoinHandle<()>,
+    snapshots: mpsc::Receiver<ProductBook>,
+    pending: Option<ProductBook>,
+    recent: VecDeque<LevelThreeMessage>,
+    diffing: Option<Diffing>,
+}
+
+impl Reconciler {
+    pub(crate) fn spawn(connector: Connector, product_id: &str, policy: ReconcilePolicy) -> Self {
+        let (sender, snapshots) = mpsc::channel(1);
+        let product_id = product_id.to_string();
+        let task = tokio::spawn(async move {
+            loop {
+                sleep(policy.interval).await;
+
+                debug!("Fetching level-three order book snapshot for reconciliation");
+                match connector.fetch_product_book(product_id.as_str()).await {
+                    Ok(snapshot) => {
+                        if sender.send(snapshot).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(error) => warn!(%error, "Failed to fetch reconciliation snapshot"),
+                }
+            }
+        });
+
+        Self {
+            rebuild: policy.rebuild,
+            task,
+            snapshots,
+            pending: None,
+            recent: VecDeque::new(),
+            diffing: None,
+        }
+    }
+
+    /// Drop a diff in flight and the recent messages, for a book that was
+    /// rebuilt some other way.
+    pub(crate) fn reset(&mut self) {
+        self.diffing = None;
+        self.recent.clear();
+    }
+
+    /// Keep a message applied to the book, dropping the oldest beyond
+    /// `MAX_RESYNC_BUFFER`.
+    fn remember(&mut self, message: &LevelThreeMessage) {
+        if self.recent.len() >= MAX_RESYNC_BUFFER {
+            self.recent.pop_front();
+        }
+
+        self.recent.push_back(message.clone());
+    }
+
+    /// Check on reconciliation after `order_book` applied `message`,
+    /// returning the drift of a finished diff and the rebuilt book, if the
+    /// policy asks for one.
+    ///
+    /// Once the book reaches a snapshot's sequence, it is copied and compared
+    /// on the blocking pool, so the feed isn't held up by the diff. A snapshot
+    /// behind the book is first brought up to date with the recent messages.
+    /// Copying is still linear in the size of the book.
+    pub(crate) fn poll(
+        &mut self,
+        order_book: &OrderBook,
+        message: LevelThreeMessage,
+    ) -> Option<(Drift, Option<OrderBook>)> {
+        self.remember(&message);
+
+        if let Some(diffing) = self.diffing.as_mut() {
+            if let Some(since) = diffing.since.as_mut()
+                && since.push(message).is_err()
+            {
+                warn!("Too many messages during reconciliation, the book won't be rebuilt");
+                diffing.since = None;
+            }
+
+            return match diffing.result.try_recv() {
+                Ok(result) => self.finish(result),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Closed) => {
+                    self.diffing = None;
+
+                    None
+                }
+            };
+        }
+
+        if self.pending.is_none() {
+            self.pending = self.snapshots.try_recv().ok();
+        }
+
+        let snapshot = self
+            .pending
+            .take_if(|snapshot| snapshot.sequence <= order_book.sequence)?;
+
+        let mut catch_up = Resync::since(snapshot.sequence);
+
+        for message in self
+            .recent
+            .iter()
+            .skip_while(|message| message.sequence() <= snapshot.sequence)
+        {
+            catch_up.push(message.clone()).ok()?;
+        }
+
+        if catch_up.predates(&snapshot) {
+            debug!(
+                snapshot = snapshot.sequence,
+                book = order_book.sequence,
+                "Skipping reconciliation snapshot older than the recent messages"
+            );
+
+            return None;
+        }
+
+        let (sender, result) = oneshot::channel();
+        let product = order_book.product.clone();
+        let compact = order_book.to_compact();
+
+        tokio::task::spawn_blocking(move || {
+            let diffed = catch_up
+                .replay(product, snapshot)
+                .and_then(|(snapshot, _)| {
+                    let drift = OrderBook::try_from(compact)?.diff_book(&snapshot)?;
+
+                    Ok((drift, snapshot))
+                });
+            let _ = sender.send(diffed);
+        });
+
+        self.diffing = Some(Diffing {
+            result,
+            since: self.rebuild.then(|| Resync::since(order_book.sequence)),
+        });
+
+        None
+    }
+
+    fn finish(
+        &mut self,
+        result: Result<(Drift, OrderBook), Error>,
+    ) -> Option<(Drift, Option<OrderBook>)> {
+        let diffing = self.diffing.take()?;
+        let (mut drift, snapshot) = result
+            .inspect_err(|error| warn!(%error, "Failed to reconcile order book"))
+            .ok()?;
+
+        if drift.is_clean() {
+            return Some((drift, None));
+        }
+
+        warn!(?drift, "Order book drifted from snapshot");
+
+        let rebuilt = diffing.since.and_then(|since| {
+            since
+                .replay_onto(snapshot)
+                .inspect_err(|error| warn!(%error, "Failed to rebuild drifted order book"))
+                .ok()
+        });
+
+        drift.rebuilt = rebuilt.is_some();
+
+        Some((drift, rebuilt.map(|(order_book, _)| order_book)))
+    }
+}
+
+impl Drop for Reconciler {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl ConnectedOrderBook {
+    /// Subscribe to the drift reports of background reconciliation.
+    pub fn drift(&self) -> watch::Receiver<Option<Drift>> {
+        self.drift.subscribe()
+    }
+
+    /// Report the drift of a finished diff, rebuilding the book if needed.
+    pub(crate) fn reconcile(&mut self, message: LevelThreeMessage) {
+        let Some(reconciler) = self.reconciler.as_mut() else {
+            return;
+        };
+        let Some((drift, rebuilt)) = reconciler.poll(&self.order_book, message) else {
+            return;
+        };
+
+        if let Some(order_book) = rebuilt {
+            self.replace_order_book(order_book);
+        }
+
+        self.drift.send_replace(Some(drift));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::make_order_book_with_orders;
+    use time::OffsetDateTime;
+
+    #[test]
+    fn diff_counts_order_level_drift() {
+        let (bid, ask, extra, missing) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        let book = make_order_book_with_orders(
+            1000,
+            vec![
+                (Decimal::new(9900, 2), bid, Decimal::ONE),
+                (Decimal::new(9800, 2), extra, Decimal::TWO),
+            ],
+            vec![(Decimal::new(10100, 2), ask, Decimal::TWO)],
+        );
+        let snapshot = ProductBook {
+            asks: vec![
+                (Decimal::new(10100, 2), Decimal::ONE, ask),
+                (Decimal::new(10200, 2), Decimal::TEN, missing),
+            ],
+            auction: None,
+            auction_mode: false,
+            bids: vec![(Decimal::new(9900, 2), Decimal::ONE, bid)],
+            sequence: 1000,
+            time: OffsetDateTime::now_utc(),
+        };
+
+        let drift = book.diff(&snapshot).unwrap();
+
+        assert_eq!(drift.orders_compared, 3);
+        assert_eq!(drift.mismatched_orders, 1);
+        assert_eq!(drift.missing_orders, 1);
+        assert_eq!(drift.missing_size, Decimal::TEN);
+        assert_eq!(drift.unexpected_orders, 1);
+        assert_eq!(drift.unexpected_size, Decimal::TWO);
+        assert!(!drift.is_clean());
+    }
+
+    #[test]
+    fn diff_requires_matching_sequence() {
+        let book = make_order_book_with_orders(1000, vec![], vec![]);
+        let snapshot = ProductBook {
+            asks: vec![],
+            auction: None,
+            auction_mode: false,
+            bids: vec![],
+            sequence: 1001,
+            time: OffsetDateTime::now_utc(),
+        };
+
+        assert!(matches!(book.diff(&snapshot), Err(Error::OutOfSequence)));
+    }
+
+    fn make_reconciler() -> (mpsc::Sender<ProductBook>, Reconciler) {
+        let (sender, snapshots) = mpsc::channel(1);
+        let reconciler = Reconciler {
+            rebuild: true,
+            task: tokio::spawn(async {}),
+            snapshots,
+            pending: None,
+            recent: VecDeque::new(),
+            diffing: None,
+        };
+
+        (sender, reconciler)
+    }
+
+    fn make_noop(sequence: u64) -> LevelThreeMessage {
+        LevelThreeMessage::Noop {
+            product_id: "BTC-USD".into(),
+            sequence,
+            time: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// Feed noops to the book until a diff finishes, as the live feed would.
+    async fn feed_until_reconciled(
+        book: &mut OrderBook,
+        reconciler: &mut Reconciler,
+    ) -> (Drift, Option<OrderBook>) {
+        for _ in 0..1000 {
+            let noop = make_noop(book.sequence() + 1);
+
+            book.update_with(&noop).unwrap();
+
+            if let Some(reconciled) = reconciler.poll(book, noop) {
+                return reconciled;
+            }
+
+            sleep(Duration::from_millis(1)).await;
+        }
+
+        panic!("reconciliation didn't finish");
+    }
+
+    /// A book with a bid and an ask, the order that opens after it, and a
+    /// snapshot including that order that disagrees on the ask's size.
+    fn make_drifted_book() -> (OrderBook, LevelThreeMessage, ProductBook, Uuid) {
+        let (bid, ask, new_bid) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let book = make_order_book_with_orders(
+            1000,
+            vec![(Decimal::new(9900, 2), bid, Decimal::ONE)],
+            vec![(Decimal::new(10100, 2), ask, Decimal::TWO)],
+        );
+        let open = LevelThreeMessage::Open {
+            product_id: "BTC-USD".into(),
+            sequence: 1001,
+            order_id: new_bid,
+            side: Side::Buy,
+            price: Decimal::new(9800, 2),
+            size: Decimal::ONE,
+            time: OffsetDateTime::now_utc(),
+        };
+        let snapshot = ProductBook {
+            asks: vec![(Decimal::new(10100, 2), Decimal::ONE, ask)],
+            auction: None,
+            auction_mode: false,
+            bids: vec![
+                (Decimal::new(9900, 2), Decimal::ONE, bid),
+                (Decimal::new(9800, 2), Decimal::ONE, new_bid),
+            ],
+            sequence: 1001,
+            time: OffsetDateTime::now_utc(),
+        };
+
+        (book, open, snapshot, ask)
+    }
+
+    #[tokio::test]
+    async fn drift_is_found_off_the_feed_and_rebuilt_with_later_messages() {
+        let (mut book, open, snapshot, ask) = make_drifted_book();
+        let (sender, mut reconciler) = make_reconciler();
+
+        sender.send(snapshot).await.unwrap();
+        book.update_with(&open).unwrap();
+        assert!(reconciler.poll(&book, open).is_none());
+
+        // The feed keeps flowing while the diff runs.
+        let (drift, rebuilt) = feed_until_reconciled(&mut book, &mut reconciler).await;
+        let rebuilt = rebuilt.unwrap();
+
+        assert_eq!(drift.sequence, 1001);
+        assert_eq!(drift.mismatched_orders, 1);
+        assert!(drift.rebuilt);
+        assert_eq!(rebuilt.sequence(), book.sequence());
+        assert_eq!(rebuilt.order(ask).unwrap().2.size, Decimal::ONE);
+    }
+
+    #[tokio::test]
+    async fn snapshots_behind_the_book_are_caught_up_with_recent_messages() {
+        let (mut book, open, snapshot, ask) = make_drifted_book();
+        let (sender, mut reconciler) = make_reconciler();
+
+        book.update_with(&open).unwrap();
+        assert!(reconciler.poll(&book, open).is_none());
+
+        // The book has moved well past the snapshot by the time it arrives.
+        for sequence in 1002..1500 {
+            let noop = make_noop(sequence);
+
+            book.update_with(&noop).unwrap();
+            assert!(reconciler.poll(&book, noop).is_none());
+        }
+
+        sender.send(snapshot).await.unwrap();
+
+        let (drift, rebuilt) = feed_until_reconciled(&mut book, &mut reconciler).await;
+        let rebuilt = rebuilt.unwrap();
+
+        assert_eq!(drift.sequence, 1500);
+        assert_eq!(drift.orders_compared, 3);
+        assert_eq!(drift.mismatched_orders, 1);
+        assert!(drift.rebuilt);
+        assert_eq!(rebuilt.sequence(), book.sequence());
+        assert_eq!(rebuilt.order(ask).unwrap().2.size, Decimal::ONE);
+
+        // Once the recent messages no longer reach back to a snapshot, it's
+        // skipped rather than diffed.
+        let (_, _, snapshot, _) = make_drifted_book();
+
+        reconciler.reset();
+        sender.send(snapshot).await.unwrap();
+
+        let noop = make_noop(book.sequence() + 1);
+
+        book.update_with(&noop).unwrap();
+        assert!(reconciler.poll(&book, noop).is_none());
+        assert!(reconciler.pending.is_none() && reconciler.diffing.is_none());
+    }
+}
@@ -0,0 +1,234 @@
+use crate::{
+    CompactOrderBook, OrderBook,
+    exchange::{common::Error, websocket::channels::level_three::Message as LevelThreeMessage},
+};
+use futures_util::{
+    Stream, StreamExt,
+    stream::{self, Iter},
+};
+use time::OffsetDateTime;
+use tokio::time::{Instant, sleep_until};
+use tracing::debug;
+
+type Callback = Box<dyn FnMut(&LevelThreeMessage, &OrderBook) + Send>;
+
+/// Rebuilds an order book from a snapshot and a recorded message stream, for
+/// research and for reproducing incidents.
+pub struct Replayer<S> {
+    snapshot: CompactOrderBook,
+    messages: S,
+    speed: Option<f64>,
+    stop_at_sequence: Option<u64>,
+    stop_at_time: Option<OffsetDateTime>,
+    callbacks: Vec<Callback>,
+}
+
+impl<I> Replayer<Iter<I>>
+where
+    I: Iterator<Item = Result<LevelThreeMessage, Error>>,
+{
+    /// Replay messages that are already in memory or read synchronously, such
+    /// as from a `JournalReader`.
+    pub fn from_iterator<M>(snapshot: CompactOrderBook, messages: M) -> Self
+    where
+        M: IntoIterator<IntoIter = I>,
+    {
+        Self::new(snapshot, stream::iter(messages))
+    }
+}
+
+impl<S> Replayer<S>
+where
+    S: Stream<Item = Result<LevelThreeMessage, Error>>,
+{
+    /// Replay `messages` on top of `snapshot` as fast as possible. Messages
+    /// for other products or at or before the snapshot's sequence are skipped.
+    pub fn new(snapshot: CompactOrderBook, messages: S) -> Self {
+        Self {
+            snapshot,
+            messages,
+            speed: None,
+            stop_at_sequence: None,
+            stop_at_time: None,
+            callbacks: vec![],
+        }
+    }
+
+    /// Pace messages by their exchange time, `speed` times faster than they
+    /// happened.
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = Some(speed);
+
+        self
+    }
+
+    /// Stop once the message with this sequence has been applied.
+    pub fn with_stop_at_sequence(mut self, sequence: u64) -> Self {
+        self.stop_at_sequence = Some(sequence);
+
+        self
+    }
+
+    /// Stop before the first message after this time.
+    pub fn with_stop_at_time(mut self, time: OffsetDateTime) -> Self {
+        self.stop_at_time = Some(time);
+
+        self
+    }
+
+    /// Call `callback` with each message after it's been applied.
+    pub fn with_callback<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&LevelThreeMessage, &OrderBook) + Send + 'static,
+    {
+        self.callbacks.push(Box::new(callback));
+
+        self
+    }
+
+    /// Run the replay to the end of the messages or the stop condition and
+    /// return the resulting book.
+    pub async fn run(mut self) -> Result<OrderBook, Error> {
+        if self
+            .speed
+            .is_some_and(|speed| !speed.is_finite() || speed <= 0.0)
+        {
+            return Err(Error::invalid("replay speed"));
+        }
+
+        let mut order_book = OrderBook::try_from(self.snapshot)?;
+        let mut origin: Option<(OffsetDateTime, Instant)> = None;
+        let mut applied = 0usize;
+
+        if self
+            .stop_at_sequence
+            .is_some_and(|sequence| sequence <= order_book.sequence)
+        {
+            return Ok(order_book);
+        }
+
+        let mut messages = std::pin::pin!(self.messages);
+
+        while let Some(message) = messages.next().await {
+            let message = message?;
+
+            if message.product_id() != order_book.product.id
+                || message.sequence() <= order_book.sequence
+            {
+                continue;
+            }
+
+            if self.stop_at_time.is_some_and(|time| message.time() > time) {
+                break;
+            }
+
+            if let Some(speed) = self.speed {
+                let (time, instant) = *origin.get_or_insert((message.time(), Instant::now()));
+                let delay = std::time::Duration::try_from((message.time() - time) / speed)
+                    .unwrap_or_default();
+
+                sleep_until(instant + delay).await;
+            }
+
+            order_book.update_with(&message)?;
+            applied += 1;
+
+            for callback in self.callbacks.iter_mut() {
+                callback(&message, &order_book);
+            }
+
+            if self
+                .stop_at_sequence
+                .is_some_and(|sequence| message.sequence() >= sequence)
+            {
+                break;
+            }
+        }
+
+        debug!(applied, sequence = order_book.sequence, "Finished replay");
+
+        Ok(order_book)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        exchange::websocket::channels::level_three::Side, test_util::make_order_book_with_orders,
+    };
+    use rust_decimal::Decimal;
+    use std::sync::{Arc, Mutex};
+    use time::Duration;
+    use uuid::Uuid;
+
+    fn make_messages(count: u64, spacing: Duration) -> Vec<Result<LevelThreeMessage, Error>> {
+        (1001..1001 + count)
+            .map(|sequence| {
+                Ok(LevelThreeMessage::Open {
+                    product_id: "BTC-USD".into(),
+                    sequence,
+                    order_id: Uuid::new_v4(),
+                    side: Side::Buy,
+                    price: Decimal::new(9900, 2),
+                    size: Decimal::ONE,
+                    time: OffsetDateTime::UNIX_EPOCH + spacing * (sequence - 1001) as u32,
+                })
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn replays_with_callbacks_and_stops() {
+        let snapshot = make_order_book_with_orders(1000, vec![], vec![]).to_compact();
+        let seen = Arc::new(Mutex::new(vec![]));
+        let order_book =
+            Replayer::from_iterator(snapshot.clone(), make_messages(10, Duration::SECOND))
+                .with_callback({
+                    let seen = seen.clone();
+
+                    move |message, order_book| {
+                        assert_eq!(message.sequence(), order_book.sequence());
+                        seen.lock().unwrap().push(message.sequence());
+                    }
+                })
+                .with_stop_at_sequence(1005)
+                .run()
+                .await
+                .unwrap();
+
+        assert_eq!(order_book.sequence(), 1005);
+        assert_eq!(*seen.lock().unwrap(), (1001..=1005).collect::<Vec<_>>());
+
+        let order_book = Replayer::from_iterator(snapshot, make_messages(10, Duration::SECOND))
+            .with_stop_at_time(OffsetDateTime::UNIX_EPOCH + Duration::seconds(3))
+            .run()
+            .await
+            .unwrap();
+
+        assert_eq!(order_book.sequence(), 1004);
+        assert_eq!(order_book.order_count(), 4);
+    }
+
+    #[tokio::test]
+    async fn paces_by_message_time() {
+        let snapshot = make_order_book_with_orders(1000, vec![], vec![]).to_compact();
+        let started = Instant::now();
+
+        Replayer::from_iterator(snapshot.clone(), make_messages(5, Duration::SECOND))
+            .with_speed(20.0)
+            .run()
+            .await
+            .unwrap();
+
+        // Four seconds of messages at 20x.
+        assert!(started.elapsed() >= std::time::Duration::from_millis(200));
+
+        let result = Replayer::from_iterator(snapshot, make_messages(5, Duration::SECOND))
+            .with_speed(0.0)
+            .run()
+            .await;
+
+        assert!(matches!(result, Err(Error::Invalid(_))));
+    }
+}
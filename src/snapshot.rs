@@ -0,0 +1,262 @@
+//! A lossless binary encoding of `CompactOrderBook`.
+//!
+//! The JSON form writes prices and sizes as floats, which can't represent
+//! every decimal. The binary form stores each decimal as its exact mantissa
+//! and scale:
+//!
+//! ```text
+//! magic "CBL3BOOK" | version: u8 | product id | sequence | updated at
+//! | product (JSON) | asks | bids | crc32: u32
+//! ```
+//!
+//! Each side is a level count, then each level's price, order count and
+//! orders as id and size. The checksum covers everything before it.
+
+use crate::{
+    CompactOrder, CompactOrderBook, CompactOrders,
+    codec::{Decoder, Errors, put_decimal, put_signed, put_varint},
+    exchange::{common::Error, rest::products::Product},
+};
+use smartstring::{LazyCompact, SmartString};
+use std::collections::VecDeque;
+use time::OffsetDateTime;
+
+const MAGIC: &[u8; 8] = b"CBL3BOOK";
+const VERSION: u8 = 1;
+
+const ERRORS: Errors = Errors {
+    truncated: "truncated snapshot",
+    varint: "snapshot varint overflow",
+    length: "snapshot length",
+    sequence: "snapshot sequence",
+    timestamp: "snapshot timestamp",
+    decimal: "snapshot decimal",
+};
+
+/// The fields readable without decoding the whole snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotHeader {
+    pub version: u8,
+    pub product_id: SmartString<LazyCompact>,
+    pub sequence: u64,
+    pub updated_at: OffsetDateTime,
+}
+
+fn put_side(buffer: &mut Vec<u8>, levels: &[(crate::CompactDecimal, CompactOrders)]) {
+    put_varint(buffer, levels.len() as u128);
+
+    for (price, orders) in levels {
+        put_decimal(buffer, &price.0);
+        put_varint(buffer, orders.0.len() as u128);
+
+        for CompactOrder((id, size)) in orders.0.iter() {
+            buffer.extend_from_slice(id.as_bytes());
+            put_decimal(buffer, &size.0);
+        }
+    }
+}
+
+fn read_header(decoder: &mut Decoder<'_>) -> Result<SnapshotHeader, Error> {
+    if decoder.take(MAGIC.len())? != MAGIC {
+        return Err(Error::invalid("snapshot magic"));
+    }
+
+    let version = decoder.byte()?;
+
+    if version != VERSION {
+        return Err(Error::invalid("snapshot version"));
+    }
+
+    let len = decoder.length()?;
+    let product_id = std::str::from_utf8(decoder.take(len)?)
+        .map_err(|_| Error::invalid("snapshot product id"))?
+        .into();
+
+    Ok(SnapshotHeader {
+        version,
+        product_id,
+        sequence: decoder.sequence()?,
+        updated_at: decoder.time()?,
+    })
+}
+
+fn read_side(
+    decoder: &mut Decoder<'_>,
+) -> Result<Vec<(crate::CompactDecimal, CompactOrders)>, Error> {
+    let count = decoder.length()?;
+    let mut levels = Vec::with_capacity(count.min(1 << 16));
+
+    for _ in 0..count {
+        let price = decoder.decimal()?;
+        let count = decoder.length()?;
+        let mut orders = VecDeque::with_capacity(count.min(1 << 16));
+
+        for _ in 0..count {
+            orders.push_back(CompactOrder((decoder.uuid()?, decoder.decimal()?.into())));
+        }
+
+        levels.push((price.into(), CompactOrders(orders)));
+    }
+
+    Ok(levels)
+}
+
+impl CompactOrderBook {
+    pub fn product(&self) -> &Product {
+        &self.product
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Encode the book in the lossless binary format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let product = serde_json::to_vec(&self.product)?;
+        let mut buffer = Vec::with_capacity(
+            64 + product.len() + 48 * self.asks.len().saturating_add(self.bids.len()),
+        );
+
+        buffer.extend_from_slice(MAGIC);
+        buffer.push(VERSION);
+        put_varint(&mut buffer, self.product.id.len() as u128);
+        buffer.extend_from_slice(self.product.id.as_bytes());
+        put_varint(&mut buffer, self.sequence as u128);
+        put_signed(&mut buffer, self.updated_at.unix_timestamp_nanos());
+        put_varint(&mut buffer, product.len() as u128);
+        buffer.extend_from_slice(&product);
+        put_side(&mut buffer, &self.asks);
+        put_side(&mut buffer, &self.bids);
+
+        let checksum = crc32fast::hash(&buffer);
+
+        buffer.extend_from_slice(&checksum.to_le_bytes());
+
+        Ok(buffer)
+    }
+
+    /// Decode a book in the binary format, verifying its checksum.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let (body, checksum) = bytes
+            .split_last_chunk::<4>()
+            .ok_or_else(|| Error::invalid("truncated snapshot"))?;
+
+        if crc32fast::hash(body) != u32::from_le_bytes(*checksum) {
+            return Err(Error::invalid("snapshot checksum"));
+        }
+
+        let mut decoder = Decoder::new(body, &ERRORS);
+        let header = read_header(&mut decoder)?;
+        let len = decoder.length()?;
+        let product = serde_json::from_slice::<Product>(decoder.take(len)?)?;
+
+        if product.id != header.product_id {
+            return Err(Error::invalid("snapshot product id"));
+        }
+
+        let asks = read_side(&mut decoder)?;
+        let bids = read_side(&mut decoder)?;
+
+        if !decoder.is_empty() {
+            return Err(Error::invalid("trailing snapshot bytes"));
+        }
+
+        Ok(Self {
+            product,
+            asks,
+            bids,
+            sequence: header.sequence,
+            updated_at: header.updated_at,
+        })
+    }
+
+    /// Read the header of a binary snapshot without decoding or verifying
+    /// the rest.
+    pub fn read_header(bytes: &[u8]) -> Result<SnapshotHeader, Error> {
+        read_header(&mut Decoder::new(bytes, &ERRORS))
+    }
+
+    /// Decode a book in either the binary or the older JSON format.
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        match bytes.starts_with(MAGIC) {
+            true => Self::from_bytes(bytes),
+            false => Ok(serde_json::from_slice(bytes)?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{OrderBook, test_util::make_order_book_with_orders};
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    fn make_compact() -> CompactOrderBook {
+        make_order_book_with_orders(
+            1000,
+            vec![
+                // Not representable as a float.
+                (
+                    Decimal::new(9_900_000_000_000_001, 14),
+                    Uuid::new_v4(),
+                    Decimal::new(123_456_789_012_345_678, 17),
+                ),
+                (Decimal::new(9800, 2), Uuid::new_v4(), Decimal::TWO),
+                (Decimal::new(9800, 2), Uuid::new_v4(), Decimal::ONE),
+            ],
+            vec![(Decimal::new(10100, 2), Uuid::new_v4(), Decimal::new(1, 8))],
+        )
+        .to_compact()
+    }
+
+    #[test]
+    fn binary_round_trip_is_exact() {
+        let compact = make_compact();
+        let bytes = compact.to_bytes().unwrap();
+        let header = CompactOrderBook::read_header(&bytes).unwrap();
+
+        assert_eq!(header.product_id, "BTC-USD");
+        assert_eq!(header.sequence, 1000);
+
+        let decoded = CompactOrderBook::decode(&bytes).unwrap();
+
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+        assert_eq!(
+            OrderBook::try_from(decoded).unwrap(),
+            OrderBook::try_from(compact).unwrap()
+        );
+    }
+
+    #[test]
+    fn corruption_fails_the_checksum() {
+        let mut bytes = make_compact().to_bytes().unwrap();
+        let middle = bytes.len() / 2;
+
+        bytes[middle] ^= 1;
+
+        assert!(matches!(
+            CompactOrderBook::from_bytes(&bytes),
+            Err(Error::Invalid("snapshot checksum"))
+        ));
+        assert!(CompactOrderBook::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn json_is_still_readable() {
+        let compact = make_order_book_with_orders(
+            1000,
+            vec![(Decimal::new(9900, 2), Uuid::new_v4(), Decimal::ONE)],
+            vec![],
+        )
+        .to_compact();
+        let json = serde_json::to_vec(&compact).unwrap();
+        let decoded = CompactOrderBook::decode(&json).unwrap();
+
+        assert_eq!(decoded.sequence(), 1000);
+        assert_eq!(
+            OrderBook::try_from(decoded).unwrap(),
+            OrderBook::try_from(compact).unwrap()
+        );
+    }
+}
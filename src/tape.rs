@@ -0,0 +1,280 @@
+use crate::{
+    Message, Tick,
+    exchange::{common::Error, websocket::channels::level_three::Side},
+};
+use rust_decimal::Decimal;
+use std::collections::VecDeque;
+use time::{Duration, OffsetDateTime};
+
+/// Aggregates over a window of the tape.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TapeStats {
+    pub trade_count: usize,
+    pub volume: Decimal,
+    pub buy_volume: Decimal,
+    pub sell_volume: Decimal,
+    pub quote_volume: Decimal,
+    pub largest: Option<Tick>,
+}
+
+impl TapeStats {
+    fn add(&mut self, tick: &Tick) -> Result<(), Error> {
+        let funds = tick
+            .price
+            .checked_mul(tick.size)
+            .ok_or_else(|| Error::math("trade funds overflow", None))?;
+        let side_volume = match tick.side {
+            Side::Buy => &mut self.buy_volume,
+            Side::Sell => &mut self.sell_volume,
+        };
+
+        *side_volume = side_volume
+            .checked_add(tick.size)
+            .ok_or_else(|| Error::math("side volume overflow", None))?;
+        self.volume = self
+            .volume
+            .checked_add(tick.size)
+            .ok_or_else(|| Error::math("volume overflow", None))?;
+        self.quote_volume = self
+            .quote_volume
+            .checked_add(funds)
+            .ok_or_else(|| Error::math("quote volume overflow", None))?;
+        self.trade_count += 1;
+
+        if self.largest.is_none_or(|largest| tick.size > largest.size) {
+            self.largest = Some(*tick);
+        }
+
+        Ok(())
+    }
+
+    fn from_ticks<'a>(ticks: impl Iterator<Item = &'a Tick>) -> Result<Self, Error> {
+        let mut stats = Self::default();
+
+        for tick in ticks {
+            stats.add(tick)?;
+        }
+
+        Ok(stats)
+    }
+
+    pub fn vwap(&self) -> Option<Decimal> {
+        self.quote_volume.checked_div(self.volume)
+    }
+
+    /// Get the net aggressor volume as a fraction of total volume, from -1
+    /// (all sells) to 1 (all buys).
+    pub fn imbalance(&self) -> Option<Decimal> {
+        self.buy_volume
+            .checked_sub(self.sell_volume)?
+            .checked_div(self.volume)
+    }
+}
+
+/// A bounded, chronological record of recent trades.
+#[derive(Debug, Clone)]
+pub struct TradeTape {
+    capacity: usize,
+    ticks: VecDeque<Tick>,
+    // Candidates for the largest trade, in decreasing size.
+    largest: VecDeque<Tick>,
+}
+
+impl TradeTape {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+
+        Self {
+            capacity,
+            ticks: VecDeque::with_capacity(capacity),
+            largest: VecDeque::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.ticks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ticks.is_empty()
+    }
+
+    /// Record a trade, evicting the oldest one if the tape is full.
+    pub fn push(&mut self, tick: Tick) {
+        if self.ticks.len() == self.capacity
+            && let Some(evicted) = self.ticks.pop_front()
+            && self.largest.front() == Some(&evicted)
+        {
+            self.largest.pop_front();
+        }
+
+        while self
+            .largest
+            .back()
+            .is_some_and(|candidate| candidate.size <= tick.size)
+        {
+            self.largest.pop_back();
+        }
+
+        self.largest.push_back(tick);
+        self.ticks.push_back(tick);
+    }
+
+    /// Record the trade from a `Match`, ignoring any other message.
+    pub fn push_message(&mut self, message: &Message) {
+        if let Some(tick) = message.tick() {
+            self.push(tick);
+        }
+    }
+
+    /// Iterate over the recorded trades, newest first.
+    pub fn ticks(&self) -> impl Iterator<Item = &Tick> {
+        self.ticks.iter().rev()
+    }
+
+    pub fn last(&self) -> Option<&Tick> {
+        self.ticks.back()
+    }
+
+    /// Get the largest trade still on the tape.
+    pub fn largest(&self) -> Option<&Tick> {
+        self.largest.front()
+    }
+
+    /// Aggregate the most recent `count` trades.
+    pub fn stats_last(&self, count: usize) -> Result<TapeStats, Error> {
+        TapeStats::from_ticks(self.ticks().take(count))
+    }
+
+    /// Aggregate the trades at or after `start`.
+    pub fn stats_since(&self, start: OffsetDateTime) -> Result<TapeStats, Error> {
+        TapeStats::from_ticks(self.ticks().take_while(|tick| tick.time >= start))
+    }
+
+    /// Aggregate the trades within `window` of the most recent trade.
+    pub fn stats_within(&self, window: Duration) -> Result<TapeStats, Error> {
+        let Some(last) = self.last() else {
+            return Ok(TapeStats::default());
+        };
+        let start = last
+            .time
+            .checked_sub(window)
+            .ok_or_else(|| Error::math("tape window overflow", None))?;
+
+        self.stats_since(start)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn make_tick(price: i64, size: i64, side: Side, seconds: i64, sequence: u64) -> Tick {
+        Tick::new(
+            Decimal::from(price),
+            Decimal::from(size),
+            side,
+            OffsetDateTime::UNIX_EPOCH + Duration::seconds(seconds),
+            sequence,
+        )
+    }
+
+    #[test]
+    fn match_ticks_carry_the_aggressor_side() {
+        let message = Message::Match {
+            sequence: 1,
+            time: OffsetDateTime::UNIX_EPOCH,
+            maker_order_id: uuid::Uuid::new_v4(),
+            taker_order_id: uuid::Uuid::new_v4(),
+            side: Side::Sell,
+            price: Decimal::ONE,
+            size: Decimal::ONE,
+        };
+        let mut tape = TradeTape::new(10);
+
+        tape.push_message(&message);
+        tape.push_message(&Message::Noop {
+            sequence: 2,
+            time: OffsetDateTime::UNIX_EPOCH,
+        });
+
+        // Lifting a resting ask is a buy.
+        assert_eq!(tape.len(), 1);
+        assert_eq!(tape.last().unwrap().side, Side::Buy);
+    }
+
+    #[test]
+    fn rolling_windows_aggregate_recent_trades() {
+        let mut tape = TradeTape::new(10);
+
+        tape.push(make_tick(100, 1, Side::Buy, 0, 1));
+        tape.push(make_tick(102, 3, Side::Buy, 10, 2));
+        tape.push(make_tick(101, 1, Side::Sell, 20, 3));
+
+        let stats = tape.stats_within(Duration::seconds(10)).unwrap();
+
+        assert_eq!(stats.trade_count, 2);
+        assert_eq!(stats.volume, Decimal::from(4));
+        // (306 + 101) / 4
+        assert_eq!(stats.vwap(), Some(Decimal::new(10175, 2)));
+        assert_eq!(stats.imbalance(), Some(Decimal::new(5, 1)));
+        assert_eq!(stats.largest.unwrap().sequence, 2);
+
+        let stats = tape.stats_last(1).unwrap();
+
+        assert_eq!(stats.trade_count, 1);
+        assert_eq!(stats.sell_volume, Decimal::ONE);
+        assert_eq!(
+            tape.stats_since(OffsetDateTime::UNIX_EPOCH)
+                .unwrap()
+                .trade_count,
+            3
+        );
+    }
+
+    #[test]
+    fn largest_trade_follows_evictions() {
+        let mut tape = TradeTape::new(3);
+
+        tape.push(make_tick(100, 5, Side::Buy, 0, 1));
+        tape.push(make_tick(100, 2, Side::Buy, 1, 2));
+        tape.push(make_tick(100, 3, Side::Sell, 2, 3));
+
+        assert_eq!(tape.largest().unwrap().sequence, 1);
+
+        tape.push(make_tick(100, 1, Side::Sell, 3, 4));
+
+        assert_eq!(tape.len(), 3);
+        assert_eq!(tape.largest().unwrap().sequence, 3);
+
+        tape.push(make_tick(100, 1, Side::Sell, 4, 5));
+        tape.push(make_tick(100, 1, Side::Sell, 5, 6));
+
+        assert_eq!(tape.largest().unwrap().sequence, 6);
+        assert_eq!(
+            tape.ticks().map(|tick| tick.sequence).collect::<Vec<_>>(),
+            vec![6, 5, 4]
+        );
+    }
+
+    #[test]
+    fn overflowing_stats_are_errors() {
+        let mut tape = TradeTape::new(10);
+
+        tape.push(make_tick(1, 1, Side::Buy, 0, 1));
+        tape.push(Tick::new(
+            Decimal::MAX,
+            Decimal::MAX,
+            Side::Buy,
+            OffsetDateTime::UNIX_EPOCH,
+            2,
+        ));
+
+        assert!(matches!(tape.stats_last(2), Err(Error::Math { .. })));
+        assert_eq!(tape.stats_last(0).unwrap(), TapeStats::default());
+    }
+}
@@ -0,0 +1,100 @@
+//! Fixtures shared by the crate's unit tests.
+
+use crate::{
+    Order, OrderBook, Orders, Tracking,
+    exchange::{rest::products::Product, websocket::channels::level_three::Side},
+};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+pub(crate) fn make_product() -> Product {
+    use crate::exchange::rest::products::Status;
+
+    Product {
+        auction_mode: false,
+        base_currency: "BTC".into(),
+        base_increment: Decimal::new(1, 8),
+        cancel_only: false,
+        display_name: "BTC-USD".into(),
+        fx_stablecoin: false,
+        high_bid_limit_percentage: "".into(),
+        id: "BTC-USD".into(),
+        limit_only: false,
+        margin_enabled: false,
+        max_slippage_percentage: Decimal::ZERO,
+        min_market_funds: Decimal::ONE,
+        post_only: false,
+        quote_currency: "USD".into(),
+        quote_increment: Decimal::new(1, 2),
+        status: Status::Online,
+        status_message: "".into(),
+        trading_disabled: false,
+    }
+}
+
+pub(crate) fn make_empty_order_book(sequence: u64) -> OrderBook {
+    OrderBook {
+        product: make_product(),
+        best_ask: Decimal::MAX,
+        best_bid: Decimal::ZERO,
+        asks: BTreeMap::new(),
+        bids: BTreeMap::new(),
+        sequence,
+        updated_at: OffsetDateTime::now_utc(),
+        index: HashMap::new(),
+        tracking: Tracking::default(),
+    }
+}
+
+pub(crate) fn make_order_book_with_orders(
+    sequence: u64,
+    bids: Vec<(Decimal, Uuid, Decimal)>, // (price, order_id, size)
+    asks: Vec<(Decimal, Uuid, Decimal)>,
+) -> OrderBook {
+    let mut index = HashMap::new();
+    let mut bids_map: BTreeMap<Decimal, Orders> = BTreeMap::new();
+    let mut asks_map: BTreeMap<Decimal, Orders> = BTreeMap::new();
+
+    for (price, order_id, size) in bids {
+        let handle = bids_map
+            .entry(price)
+            .or_default()
+            .insert(Order::new(order_id, size))
+            .unwrap();
+
+        index.insert(order_id, (Side::Buy, price, handle));
+    }
+
+    for (price, order_id, size) in asks {
+        let handle = asks_map
+            .entry(price)
+            .or_default()
+            .insert(Order::new(order_id, size))
+            .unwrap();
+
+        index.insert(order_id, (Side::Sell, price, handle));
+    }
+
+    let best_bid = bids_map
+        .last_key_value()
+        .map(|(p, _)| *p)
+        .unwrap_or(Decimal::ZERO);
+    let best_ask = asks_map
+        .first_key_value()
+        .map(|(p, _)| *p)
+        .unwrap_or(Decimal::MAX);
+
+    OrderBook {
+        product: make_product(),
+        best_ask,
+        best_bid,
+        asks: asks_map,
+        bids: bids_map,
+        sequence,
+        updated_at: OffsetDateTime::now_utc(),
+        index,
+        tracking: Tracking::default(),
+    }
+}
@@ -0,0 +1,86 @@
+use crate::exchange::websocket::channels::ticker::Ticker;
+use smartstring::{LazyCompact, SmartString};
+use std::collections::HashMap;
+
+/// The latest ticker for each product seen on a `ticker` or `ticker_batch`
+/// channel.
+#[derive(Debug, Clone, Default)]
+pub struct TickerCache {
+    tickers: HashMap<SmartString<LazyCompact>, Ticker>,
+}
+
+impl TickerCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep `ticker` unless the cache already has a later one for its product.
+    /// Returns whether it was kept.
+    pub fn update(&mut self, ticker: Ticker) -> bool {
+        match self.tickers.get(&ticker.product_id) {
+            Some(latest) if latest.sequence >= ticker.sequence => false,
+            _ => {
+                self.tickers.insert(ticker.product_id.clone(), ticker);
+
+                true
+            }
+        }
+    }
+
+    pub fn get(&self, product_id: &str) -> Option<&Ticker> {
+        self.tickers.get(product_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Ticker> {
+        self.tickers.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tickers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tickers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn make_ticker(product_id: &str, sequence: u64, price: Decimal) -> Ticker {
+        Ticker {
+            sequence,
+            product_id: product_id.into(),
+            price,
+            best_bid: price,
+            best_bid_size: Decimal::ONE,
+            best_ask: price,
+            best_ask_size: Decimal::ONE,
+            open_24h: price,
+            high_24h: price,
+            low_24h: price,
+            volume_24h: Decimal::ONE,
+            volume_30d: Decimal::ONE,
+            last_size: None,
+            side: None,
+            trade_id: None,
+            time: None,
+        }
+    }
+
+    #[test]
+    fn keeps_the_latest_ticker_per_product() {
+        let mut cache = TickerCache::new();
+
+        assert!(cache.update(make_ticker("BTC-USD", 2, Decimal::TEN)));
+        assert!(cache.update(make_ticker("ETH-USD", 1, Decimal::ONE)));
+        assert!(!cache.update(make_ticker("BTC-USD", 1, Decimal::TWO)));
+        assert_eq!(cache.get("BTC-USD").unwrap().price, Decimal::TEN);
+        assert!(cache.update(make_ticker("BTC-USD", 3, Decimal::TWO)));
+        assert_eq!(cache.get("BTC-USD").unwrap().price, Decimal::TWO);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("SOL-USD").is_none());
+    }
+}
@@ -0,0 +1,282 @@
+use crate::{
+    OrderBook,
+    exchange::{common::Error, websocket::channels::level_three::Side},
+};
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+
+pub use crate::exchange::common::Discrepancy;
+
+/// Settings for validating the book as it is updated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Validation {
+    interval: Option<usize>,
+    messages: usize,
+}
+
+impl Validation {
+    pub(crate) fn new(interval: Option<usize>) -> Self {
+        Self {
+            interval: interval.map(|interval| interval.max(1)),
+            messages: 0,
+        }
+    }
+
+    /// Count a message, returning whether validation is due.
+    fn tick(&mut self) -> bool {
+        let Some(interval) = self.interval else {
+            return false;
+        };
+
+        self.messages += 1;
+
+        if self.messages < interval {
+            return false;
+        }
+
+        self.messages = 0;
+
+        true
+    }
+}
+
+impl OrderBook {
+    /// Check the internal consistency of the book, returning every broken
+    /// invariant.
+    pub fn validate(&self) -> Result<(), Vec<Discrepancy>> {
+        let mut discrepancies = vec![];
+        let mut queued = HashSet::with_capacity(self.index.len());
+
+        for (side, halfbook) in [(Side::Buy, &self.bids), (Side::Sell, &self.asks)] {
+            for (price, orders) in halfbook.iter() {
+                let price = *price;
+
+                if orders.is_empty() {
+                    discrepancies.push(Discrepancy::EmptyLevel { side, price });
+                }
+
+                let queue_size = orders
+                    .iter()
+                    .fold(Decimal::ZERO, |sum, order| sum.saturating_add(order.size));
+
+                if queue_size != orders.total_size {
+                    discrepancies.push(Discrepancy::TotalSizeMismatch {
+                        side,
+                        price,
+                        total_size: orders.total_size,
+                        queue_size,
+                    });
+                }
+
+                for order in orders.iter() {
+                    let order_id = order.id;
+
+                    if !queued.insert(order_id) {
+                        discrepancies.push(Discrepancy::DuplicateOrder { order_id });
+
+                        continue;
+                    }
+
+                    match self.index.get(&order_id) {
+                        None => discrepancies.push(Discrepancy::UnindexedOrder {
+                            order_id,
+                            side,
+                            price,
+                        }),
+                        Some(&(indexed_side, indexed_price, handle))
+                            if indexed_side != side
+                                || indexed_price != price
+                                || orders.get(handle).map(|order| order.id) != Some(order_id) =>
+                        {
+                            discrepancies.push(Discrepancy::Misindexed {
+                                order_id,
+                                side,
+                                price,
+                                indexed_side,
+                                indexed_price,
+                            });
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+
+        for (order_id, (side, price, _)) in self.index.iter() {
+            if !queued.contains(order_id) {
+                discrepancies.push(Discrepancy::IndexedOrderMissing {
+                    order_id: *order_id,
+                    side: *side,
+                    price: *price,
+                });
+            }
+        }
+
+        let best_bid = self.bids.last_key_value().map(|(price, _)| *price);
+        let best_ask = self.asks.first_key_value().map(|(price, _)| *price);
+
+        if self.best_bid != best_bid.unwrap_or(Decimal::ZERO) {
+            discrepancies.push(Discrepancy::BestPriceMismatch {
+                side: Side::Buy,
+                cached: self.best_bid,
+                actual: best_bid,
+            });
+        }
+
+        if self.best_ask != best_ask.unwrap_or(Decimal::MAX) {
+            discrepancies.push(Discrepancy::BestPriceMismatch {
+                side: Side::Sell,
+                cached: self.best_ask,
+                actual: best_ask,
+            });
+        }
+
+        if let (Some(best_bid), Some(best_ask)) = (best_bid, best_ask)
+            && best_bid >= best_ask
+        {
+            discrepancies.push(Discrepancy::Crossed { best_bid, best_ask });
+        }
+
+        match discrepancies.is_empty() {
+            true => Ok(()),
+            false => Err(discrepancies),
+        }
+    }
+
+    /// Whether the best bid is at or above the best ask.
+    pub fn is_crossed(&self) -> bool {
+        !self.bids.is_empty() && !self.asks.is_empty() && self.best_bid >= self.best_ask
+    }
+
+    /// Run `validate` after every `interval` updates, failing the update with
+    /// `Error::Inconsistent` if any invariant is broken. Pass `None` to stop
+    /// validating.
+    ///
+    /// Validation runs after the update is applied, so the book keeps the
+    /// changes of an update that fails it. Treat the book as diverged and
+    /// rebuild it, as resynchronization does.
+    pub fn set_validation_interval(&mut self, interval: Option<usize>) {
+        self.tracking.validation = Validation::new(interval);
+    }
+
+    pub(crate) fn validate_if_due(&mut self) -> Result<(), Error> {
+        if !self.tracking.validation.tick() {
+            return Ok(());
+        }
+
+        self.validate()
+            .map_err(|discrepancies| Error::Inconsistent {
+                sequence: self.sequence,
+                discrepancies,
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        Order, Orders, exchange::websocket::channels::level_three::Message as LevelThreeMessage,
+        test_util::make_order_book_with_orders,
+    };
+    use time::OffsetDateTime;
+    use uuid::Uuid;
+
+    fn make_book(bid: Uuid, ask: Uuid) -> OrderBook {
+        make_order_book_with_orders(
+            1000,
+            vec![(Decimal::new(9900, 2), bid, Decimal::ONE)],
+            vec![(Decimal::new(10100, 2), ask, Decimal::TWO)],
+        )
+    }
+
+    #[test]
+    fn consistent_book_is_valid() {
+        let book = make_book(Uuid::new_v4(), Uuid::new_v4());
+
+        assert!(book.validate().is_ok());
+        assert!(!book.is_crossed());
+    }
+
+    #[test]
+    fn reports_every_discrepancy() {
+        let (bid, ask) = (Uuid::new_v4(), Uuid::new_v4());
+        let stray = Uuid::new_v4();
+        let mut book = make_book(bid, ask);
+        let bid_price = Decimal::new(9900, 2);
+        let ask_price = Decimal::new(10100, 2);
+
+        // Drop the ask from the index, index an order that doesn't exist,
+        // corrupt a level total and cross the book with a stale best bid.
+        book.index.remove(&ask);
+        book.index.insert(stray, (Side::Buy, bid_price, 0));
+
+        let orders = book.bids.get_mut(&bid_price).unwrap();
+
+        orders.insert(Order::new(bid, Decimal::ONE)).unwrap();
+        orders.total_size = Decimal::TEN;
+        book.bids.insert(Decimal::new(10200, 2), Orders::default());
+
+        let discrepancies = book.validate().unwrap_err();
+
+        assert!(discrepancies.contains(&Discrepancy::UnindexedOrder {
+            order_id: ask,
+            side: Side::Sell,
+            price: ask_price,
+        }));
+        assert!(discrepancies.contains(&Discrepancy::IndexedOrderMissing {
+            order_id: stray,
+            side: Side::Buy,
+            price: bid_price,
+        }));
+        assert!(discrepancies.contains(&Discrepancy::DuplicateOrder { order_id: bid }));
+        assert!(discrepancies.contains(&Discrepancy::TotalSizeMismatch {
+            side: Side::Buy,
+            price: bid_price,
+            total_size: Decimal::TEN,
+            queue_size: Decimal::TWO,
+        }));
+        assert!(discrepancies.contains(&Discrepancy::EmptyLevel {
+            side: Side::Buy,
+            price: Decimal::new(10200, 2),
+        }));
+        assert!(discrepancies.contains(&Discrepancy::BestPriceMismatch {
+            side: Side::Buy,
+            cached: bid_price,
+            actual: Some(Decimal::new(10200, 2)),
+        }));
+        assert!(discrepancies.contains(&Discrepancy::Crossed {
+            best_bid: Decimal::new(10200, 2),
+            best_ask: ask_price,
+        }));
+    }
+
+    #[test]
+    fn debug_mode_fails_update_on_interval() {
+        let (bid, ask) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut book = make_book(bid, ask);
+        let noop = |sequence| LevelThreeMessage::Noop {
+            product_id: "BTC-USD".into(),
+            sequence,
+            time: OffsetDateTime::now_utc(),
+        };
+
+        book.set_validation_interval(Some(2));
+        book.index.remove(&bid);
+
+        assert!(book.update_with(&noop(1001)).is_ok());
+
+        let error = book.update_with(&noop(1002)).unwrap_err();
+
+        assert!(error.is_divergence());
+        assert!(matches!(
+            error,
+            Error::Inconsistent { sequence: 1002, ref discrepancies }
+                if discrepancies == &[Discrepancy::UnindexedOrder {
+                    order_id: bid,
+                    side: Side::Buy,
+                    price: Decimal::new(9900, 2),
+                }]
+        ));
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{
+    CompactOrderBook, OrderBook,
+    connection::Connector,
+    exchange::{
+        common::Error,
+        rest::products::Product,
+        websocket::channels::{Channel, level_three::Message as LevelThreeMessage},
+    },
+};
+use std::cmp::Ordering;
+use tracing::{debug, warn};
+
+type Journal = Box<dyn Iterator<Item = Result<LevelThreeMessage, Error>> + Send>;
+
+/// A persisted book and the messages recorded after it, used to start an
+/// order book without fetching a REST snapshot.
+pub struct WarmStart {
+    snapshot: CompactOrderBook,
+    journal: Journal,
+}
+
+impl WarmStart {
+    pub fn new(snapshot: CompactOrderBook) -> Self {
+        Self {
+            snapshot,
+            journal: Box::new(std::iter::empty()),
+        }
+    }
+
+    /// Replay these messages on top of the snapshot. Messages for other
+    /// products or at or before the snapshot's sequence are skipped.
+    pub fn with_journal<I>(mut self, journal: I) -> Self
+    where
+        I: IntoIterator<Item = Result<LevelThreeMessage, Error>>,
+        I::IntoIter: Send + 'static,
+    {
+        self.journal = Box::new(journal.into_iter());
+
+        self
+    }
+
+    pub fn product(&self) -> &Product {
+        &self.snapshot.product
+    }
+
+    /// Rebuild the book and bring it up to the journal tail. Fails with
+    /// `Error::OutOfSequence` if the journal has a gap.
+    pub fn replay(self) -> Result<OrderBook, Error> {
+        let mut order_book = OrderBook::try_from(self.snapshot)?;
+
+        for message in self.journal {
+            let message = message?;
+
+            if message.product_id() != order_book.product.id
+                || message.sequence() <= order_book.sequence
+            {
+                continue;
+            }
+
+            order_book.update_with(&message)?;
+        }
+
+        Ok(order_book)
+    }
+}
+
+impl Connector {
+    /// Start from a warm book, returning `None` if continuity with the live
+    /// feed can't be proven and a REST snapshot is needed after all.
+    ///
+    /// The live feed is subscribed and cached before the journal is replayed,
+    /// so the feed only has to start at or before the journal tail, and the
+    /// messages cached meanwhile are spliced onto it.
+    pub(crate) async fn warm_start(
+        &self,
+        warm_start: WarmStart,
+    ) -> Result<Option<(OrderBook, Channel<LevelThreeMessage>)>, Error> {
+        debug!("Establishing websocket channel");
+        let channel = self
+            .connect::<LevelThreeMessage>(std::slice::from_ref(&warm_start.product().id))
+            .await?;
+
+        debug!("Caching messages while replaying the warm start journal");
+        let caching_channel = channel.cache().await;
+        let replayed = tokio::task::spawn_blocking(move || warm_start.replay()).await;
+        let mut channel = caching_channel.join().await?;
+
+        match replayed? {
+            Ok(order_book) => resume(order_book, channel).await,
+            Err(error) => {
+                warn!(%error, "Failed to replay warm start journal");
+                let _ = channel.close().await;
+
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Continue a book with the messages cached while its journal was replayed,
+/// then from the live feed if none of them went past the book.
+async fn resume(
+    mut order_book: OrderBook,
+    mut channel: Channel<LevelThreeMessage>,
+) -> Result<Option<(OrderBook, Channel<LevelThreeMessage>)>, Error> {
+    let cached = channel.cached_items();
+    let mut first = cached.len() == 0;
+
+    match splice(&mut order_book, cached) {
+        Continuity::Covered => {}
+        Continuity::Resumed => {
+            debug!(sequence = order_book.sequence, "Resumed warm order book");
+
+            return Ok(Some((order_book, channel)));
+        }
+        Continuity::Broken => {
+            let _ = channel.close().await;
+
+            return Ok(None);
+        }
+    }
+
+    loop {
+        let message = channel.next().await?;
+
+        match continuity(&mut order_book, &message, first) {
+            Continuity::Covered => {}
+            Continuity::Resumed => {
+                debug!(sequence = order_book.sequence, "Resumed warm order book");
+
+                return Ok(Some((order_book, channel)));
+            }
+            Continuity::Broken => {
+                let _ = channel.close().await;
+
+                return Ok(None);
+            }
+        }
+
+        first = false;
+    }
+}
+
+/// How a warm book continues into a message from the live feed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Continuity {
+    /// Already covered by the warm book.
+    Covered,
+    /// The book applied the message and is live.
+    Resumed,
+    /// The book can't be continued, so a REST snapshot is needed.
+    Broken,
+}
+
+/// Apply the cached messages that follow the book. `Covered` means none of
+/// them did, so the live feed has to continue the book.
+fn splice<I>(order_book: &mut OrderBook, cached: I) -> Continuity
+where
+    I: IntoIterator<Item = LevelThreeMessage>,
+{
+    let mut spliced = Continuity::Covered;
+
+    for (index, message) in cached.into_iter().enumerate() {
+        match continuity(order_book, &message, index == 0) {
+            Continuity::Covered => {}
+            Continuity::Resumed => spliced = Continuity::Resumed,
+            Continuity::Broken => return Continuity::Broken,
+        }
+    }
+
+    spliced
+}
+
+fn continuity(order_book: &mut OrderBook, message: &LevelThreeMessage, first: bool) -> Continuity {
+    match message.sequence().cmp(&(order_book.sequence + 1)) {
+        Ordering::Less => Continuity::Covered,
+        Ordering::Equal => match order_book.update_with(message) {
+            Ok(_) => Continuity::Resumed,
+            Err(error) => {
+                warn!(%error, sequence = message.sequence(), "Failed to resume warm order book");
+
+                Continuity::Broken
+            }
+        },
+        Ordering::Greater => {
+            match first {
+                true => debug!(
+                    book = order_book.sequence,
+                    feed = message.sequence(),
+                    "Warm order book is too old for the live feed"
+                ),
+                // The feed itself skipped a message.
+                false => warn!(
+                    book = order_book.sequence,
+                    feed = message.sequence(),
+                    "Live feed skipped a message while resuming"
+                ),
+            }
+
+            Continuity::Broken
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        exchange::websocket::channels::level_three::Side, test_util::make_order_book_with_orders,
+    };
+    use rust_decimal::Decimal;
+    use time::OffsetDateTime;
+    use uuid::Uuid;
+
+    fn make_open(product_id: &str, sequence: u64) -> Result<LevelThreeMessage, Error> {
+        Ok(LevelThreeMessage::Open {
+            product_id: product_id.into(),
+            sequence,
+            order_id: Uuid::new_v4(),
+            side: Side::Buy,
+            price: Decimal::new(9900, 2),
+            size: Decimal::ONE,
+            time: OffsetDateTime::now_utc(),
+        })
+    }
+
+    #[test]
+    fn replay_applies_the_journal_tail() {
+        let snapshot = make_order_book_with_orders(1000, vec![], vec![]).to_compact();
+        let order_book = WarmStart::new(snapshot)
+            .with_journal(vec![
+                make_open("BTC-USD", 999),
+                make_open("BTC-USD", 1000),
+                make_open("ETH-USD", 1001),
+                make_open("BTC-USD", 1001),
+                make_open("BTC-USD", 1002),
+            ])
+            .replay()
+            .unwrap();
+
+        assert_eq!(order_book.sequence(), 1002);
+        assert_eq!(order_book.order_count(), 2);
+    }
+
+    #[test]
+    fn replay_rejects_a_gap_in_the_journal() {
+        let snapshot = make_order_book_with_orders(1000, vec![], vec![]).to_compact();
+        let result = WarmStart::new(snapshot)
+            .with_journal(vec![make_open("BTC-USD", 1001), make_open("BTC-USD", 1003)])
+            .replay();
+
+        assert!(matches!(result, Err(Error::OutOfSequence)));
+    }
+
+    #[test]
+    fn resuming_falls_back_when_the_feed_doesnt_continue_the_book() {
+        let bid = Uuid::new_v4();
+        let mut order_book = make_order_book_with_orders(
+            1000,
+            vec![(Decimal::new(9900, 2), bid, Decimal::ONE)],
+            vec![],
+        );
+
+        assert_eq!(
+            continuity(&mut order_book, &make_open("BTC-USD", 1000).unwrap(), true),
+            Continuity::Covered
+        );
+        assert_eq!(
+            continuity(&mut order_book, &make_open("BTC-USD", 1002).unwrap(), false),
+            Continuity::Broken
+        );
+
+        // The next message doesn't apply to the warm book.
+        let open = LevelThreeMessage::Open {
+            product_id: "BTC-USD".into(),
+            sequence: 1001,
+            order_id: bid,
+            side: Side::Buy,
+            price: Decimal::new(9900, 2),
+            size: Decimal::ONE,
+            time: OffsetDateTime::now_utc(),
+        };
+
+        assert_eq!(
+            continuity(&mut order_book, &open, false),
+            Continuity::Broken
+        );
+
+        let mut order_book = make_order_book_with_orders(1000, vec![], vec![]);
+
+        assert_eq!(
+            continuity(&mut order_book, &make_open("BTC-USD", 1001).unwrap(), true),
+            Continuity::Resumed
+        );
+        assert_eq!(order_book.sequence(), 1001);
+    }
+
+    #[test]
+    fn cached_messages_are_spliced_onto_the_journal_tail() {
+        let snapshot = make_order_book_with_orders(1000, vec![], vec![]).to_compact();
+        let journal = (1001..=1005).map(|sequence| make_open("BTC-USD", sequence));
+        let cached = (1003..=1008)
+            .map(|sequence| make_open("BTC-USD", sequence).unwrap())
+            .collect::<Vec<_>>();
+
+        // The feed was cached from 1003 while the journal replayed up to 1005.
+        let mut order_book = WarmStart::new(snapshot.clone())
+            .with_journal(journal)
+            .replay()
+            .unwrap();
+
+        assert_eq!(splice(&mut order_book, cached.clone()), Continuity::Resumed);
+        assert_eq!(order_book.sequence(), 1008);
+        assert_eq!(order_book.order_count(), 8);
+
+        // Nothing cached went past the tail, so the live feed has to.
+        let mut order_book = make_order_book_with_orders(1010, vec![], vec![]);
+
+        assert_eq!(splice(&mut order_book, cached.clone()), Continuity::Covered);
+
+        // The cache starts after the tail's next sequence.
+        let mut order_book = WarmStart::new(snapshot)
+            .with_journal(vec![make_open("BTC-USD", 1001)])
+            .replay()
+            .unwrap();
+
+        assert_eq!(splice(&mut order_book, cached), Continuity::Broken);
+    }
+}